@@ -0,0 +1,498 @@
+use std::collections::HashMap;
+use std::env;
+use std::ffi::OsStr;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use anyhow;
+use anyhow::Context;
+use postgres;
+use postgres_native_tls;
+use native_tls;
+use crate::output;
+use crate::secrets;
+use crate::utils;
+
+#[cfg(test)]
+mod tests;
+
+
+// connection parameter -> value, e.g. "host" -> "localhost"
+pub type Params = HashMap<String, String>;
+
+// connection string -> resolved parameters, so that service and password files are read once per run
+static RESOLVED: Mutex<Option<HashMap<String, Params>>> = Mutex::new(None);
+
+// supported libpq connection parameters with environment variables providing their defaults
+const PARAMS: &[(&str, Option<&str>)] = &[
+    ("host", Some("PGHOST")),
+    ("port", Some("PGPORT")),
+    ("dbname", Some("PGDATABASE")),
+    ("user", Some("PGUSER")),
+    ("password", Some("PGPASSWORD")),
+    ("passfile", Some("PGPASSFILE")),
+    ("service", Some("PGSERVICE")),
+    ("options", Some("PGOPTIONS")),
+    ("application_name", Some("PGAPPNAME")),
+    ("connect_timeout", Some("PGCONNECT_TIMEOUT")),
+    ("keepalives", None),
+    ("keepalives_idle", None),
+    ("target_session_attrs", Some("PGTARGETSESSIONATTRS")),
+    ("channel_binding", Some("PGCHANNELBINDING")),
+    ("sslmode", Some("PGSSLMODE")),
+    ("sslrootcert", Some("PGSSLROOTCERT")),
+];
+
+// parameters handled by wo itself, not passed to postgres config
+const LIBPQ_PARAMS: &[&str] = &["passfile", "service", "sslmode", "sslrootcert"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SslMode {
+    Disable,
+    // tls if server supports it, certificate is not verified
+    Prefer,
+    // tls, certificate is not verified
+    Require,
+    // tls, certificate is verified against root certificate
+    VerifyCa,
+    // tls, certificate is verified against root certificate and its name has to match host
+    VerifyFull,
+}
+
+fn validate_keyword(keyword: &str) -> anyhow::Result<()> {
+    if !PARAMS.iter().any(|(k, _)| *k == keyword) {
+        bail!("unsupported connection parameter {:?}", keyword);
+    }
+    return Ok(());
+}
+
+/* text with %XX sequences decoded */
+fn percent_decode(text: &str) -> anyhow::Result<String> {
+    let bytes = text.as_bytes();
+    let mut result = vec![];
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let byte = text.get(i + 1..i + 3)
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+                .ok_or(anyhow!("invalid percent-encoded token in connection uri: {:?}", text))?;
+            result.push(byte);
+            i += 3;
+        } else {
+            result.push(bytes[i]);
+            i += 1;
+        }
+    }
+    return String::from_utf8(result).context("invalid percent-encoded token in connection uri");
+}
+
+/* postgresql://[user[:password]@][host[:port][,...]][/dbname][?param=value&...] */
+fn parse_uri(uri: &str) -> anyhow::Result<Params> {
+    let mut result = Params::new();
+    let rest = uri.split_once("://").map(|(_, rest)| rest).unwrap_or("");
+    let (rest, query) = match rest.split_once('?') {
+        Some((rest, query)) => (rest, Some(query)),
+        None => (rest, None),
+    };
+    let (authority, path) = match rest.split_once('/') {
+        Some((authority, path)) => (authority, Some(path)),
+        None => (rest, None),
+    };
+    let (credentials, hosts) = match authority.rsplit_once('@') {
+        Some((credentials, hosts)) => (Some(credentials), hosts),
+        None => (None, authority),
+    };
+
+    if let Some(credentials) = credentials {
+        let (user, password) = match credentials.split_once(':') {
+            Some((user, password)) => (user, Some(password)),
+            None => (credentials, None),
+        };
+        if !user.is_empty() {
+            result.insert("user".into(), percent_decode(user)?);
+        }
+        if let Some(password) = password {
+            result.insert("password".into(), percent_decode(password)?);
+        }
+    }
+
+    if !hosts.is_empty() {
+        let mut host_list = vec![];
+        let mut port_list = vec![];
+        for host in hosts.split(',') {
+            let (host, port) = if host.starts_with('[') {
+                // ipv6 address, e.g. [::1]:5432
+                let end = host.find(']')
+                    .ok_or(anyhow!("invalid ipv6 host in connection uri: {:?}", host))?;
+                (&host[1..end], host[end + 1..].strip_prefix(':'))
+            } else {
+                match host.rsplit_once(':') {
+                    Some((host, port)) => (host, Some(port)),
+                    None => (host, None),
+                }
+            };
+            host_list.push(percent_decode(host)?);
+            port_list.push(port.unwrap_or(""));
+        }
+        if host_list.iter().any(|h| !h.is_empty()) {
+            result.insert("host".into(), host_list.join(","));
+        }
+        if port_list.iter().any(|p| !p.is_empty()) {
+            let ports: Vec<&str> = port_list.iter().map(|p| if p.is_empty() { "5432" } else { p }).collect();
+            result.insert("port".into(), ports.join(","));
+        }
+    }
+
+    if let Some(path) = path {
+        if !path.is_empty() {
+            result.insert("dbname".into(), percent_decode(path)?);
+        }
+    }
+
+    if let Some(query) = query {
+        for pair in query.split('&').filter(|pair| !pair.is_empty()) {
+            let (keyword, value) = pair.split_once('=')
+                .ok_or(anyhow!("missing \"=\" after {:?} in connection uri", pair))?;
+            result.insert(percent_decode(keyword)?, percent_decode(value)?);
+        }
+    }
+    return Ok(result);
+}
+
+/* keyword = value pairs separated by whitespace, values can be single quoted, backslash escapes next character */
+fn parse_keyword_value(text: &str) -> anyhow::Result<Params> {
+    let mut result = Params::new();
+    let mut chars = text.chars().peekable();
+    loop {
+        while chars.next_if(|c| c.is_whitespace()).is_some() {}
+        if chars.peek().is_none() {
+            break;
+        }
+        let mut keyword = String::new();
+        while let Some(c) = chars.next_if(|c| *c != '=' && !c.is_whitespace()) {
+            keyword.push(c);
+        }
+        while chars.next_if(|c| c.is_whitespace()).is_some() {}
+        if chars.next() != Some('=') {
+            bail!("missing \"=\" after {:?} in connection string", keyword);
+        }
+        while chars.next_if(|c| c.is_whitespace()).is_some() {}
+
+        let mut value = String::new();
+        if chars.next_if_eq(&'\'').is_some() {
+            loop {
+                match chars.next() {
+                    Some('\\') => value.extend(chars.next()),
+                    Some('\'') => break,
+                    Some(c) => value.push(c),
+                    None => bail!("unterminated quoted value of {:?} in connection string", keyword),
+                }
+            }
+        } else {
+            while let Some(c) = chars.next_if(|c| !c.is_whitespace()) {
+                if c == '\\' {
+                    value.extend(chars.next());
+                } else {
+                    value.push(c);
+                }
+            }
+        }
+        result.insert(keyword, value);
+    }
+    return Ok(result);
+}
+
+/* parameters of connection string in keyword = value or uri format */
+pub fn parse_connection_string(connection_string: &str) -> anyhow::Result<Params> {
+    let trimmed = connection_string.trim();
+    let result = if trimmed.starts_with("postgresql://") || trimmed.starts_with("postgres://") {
+        parse_uri(trimmed)?
+    } else {
+        parse_keyword_value(trimmed)?
+    };
+    for keyword in result.keys() {
+        validate_keyword(keyword)?;
+    }
+    return Ok(result);
+}
+
+/* parameters of the [service] section of pg_service.conf content, none if the section is missing */
+pub fn parse_service_file(content: &str, service: &str) -> anyhow::Result<Option<Params>> {
+    let mut result = None;
+    let mut in_section = false;
+    for (i, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        if line.starts_with('[') && line.ends_with(']') {
+            if in_section {
+                break;
+            }
+            in_section = &line[1..line.len() - 1] == service;
+            if in_section {
+                result = Some(Params::new());
+            }
+            continue;
+        }
+        if !in_section {
+            continue;
+        }
+        let (keyword, value) = line.split_once('=')
+            .ok_or(anyhow!("syntax error in service file at line {}", i + 1))?;
+        let keyword = keyword.trim();
+        validate_keyword(keyword)
+            .context(format!("invalid parameter in service {:?} at line {}", service, i + 1))?;
+        if let Some(params) = result.as_mut() {
+            params.insert(keyword.into(), value.trim().into());
+        }
+    }
+    return Ok(result);
+}
+
+/* PGSERVICEFILE or ~/.pg_service.conf, then pg_service.conf in PGSYSCONFDIR (default /etc/postgresql-common) */
+fn get_service_file_paths() -> Vec<PathBuf> {
+    let mut result = vec![];
+    if let Ok(path) = env::var("PGSERVICEFILE") {
+        result.push(PathBuf::from(path));
+    } else if let Ok(home) = env::var("HOME") {
+        result.push(PathBuf::from(home).join(".pg_service.conf"));
+    }
+    let sysconfdir = env::var("PGSYSCONFDIR").unwrap_or("/etc/postgresql-common".into());
+    result.push(PathBuf::from(sysconfdir).join("pg_service.conf"));
+    return result;
+}
+
+fn load_service(service: &str) -> anyhow::Result<Params> {
+    for path_buf in get_service_file_paths().iter() {
+        if !path_buf.exists() {
+            continue;
+        }
+        let content = utils::read_file(path_buf)?;
+        let params = parse_service_file(&content, service)
+            .context(format!("failed to parse service file {:?}", path_buf))?;
+        if let Some(params) = params {
+            return Ok(params);
+        }
+    }
+    bail!("definition of service {:?} not found", service);
+}
+
+/* first value of comma separated list, e.g. host=a,b */
+pub fn get_first<'p>(params: &'p Params, keyword: &str) -> &'p str {
+    return params.get(keyword)
+        .and_then(|value| value.split(',').next())
+        .unwrap_or("");
+}
+
+/* password of passfile (.pgpass) entry matching first host */
+fn find_password(params: &Params) -> anyhow::Result<Option<String>> {
+    let host = get_first(params, "host");
+    // unix socket directories match localhost like in libpq
+    let host = if host.starts_with('/') { "localhost" } else { host };
+    let port = get_first(params, "port").parse::<u16>()
+        .context(format!("invalid port {:?}", get_first(params, "port")))?;
+    let database = get_first(params, "dbname");
+    let user = get_first(params, "user");
+    match params.get("passfile") {
+        Some(passfile) => {
+            let path_buf = PathBuf::from(passfile);
+            if !path_buf.exists() {
+                return Ok(None);
+            }
+            let content = utils::read_file(&path_buf)?;
+            return Ok(secrets::find_pgpass_password_in(&content, host, port, database, user));
+        },
+        None => return secrets::find_pgpass_password(host, port, database, user),
+    }
+}
+
+/* parameters of connection string completed like libpq does: missing ones are taken from service file (service or PGSERVICE),
+then from PG* environment variables, then defaults, password from passfile (.pgpass) */
+pub fn resolve(connection_string: &str) -> anyhow::Result<Params> {
+    let mut result = parse_connection_string(connection_string)?;
+    let service = result.get("service").cloned()
+        .or(env::var("PGSERVICE").ok().filter(|s| !s.is_empty()));
+    if let Some(service) = service {
+        for (keyword, value) in load_service(&service)? {
+            result.entry(keyword).or_insert(value);
+        }
+    }
+    for (keyword, env_var) in PARAMS.iter() {
+        let value = env_var.and_then(|env_var| env::var(env_var).ok()).filter(|v| !v.is_empty());
+        if let Some(value) = value {
+            result.entry(String::from(*keyword)).or_insert(value);
+        }
+    }
+
+    result.entry("host".into()).or_insert("localhost".into());
+    result.entry("port".into()).or_insert("5432".into());
+    if !result.contains_key("user") {
+        let user = env::var("USER").or(env::var("USERNAME"))
+            .context("user is not set in connection string, PGUSER or USER")?;
+        result.insert("user".into(), user);
+    }
+    if !result.contains_key("dbname") {
+        let user = result["user"].clone();
+        result.insert("dbname".into(), user);
+    }
+    if !result.contains_key("password") {
+        if let Some(password) = find_password(&result)? {
+            result.insert("password".into(), password);
+        }
+    }
+    if let Some(password) = result.get("password") {
+        secrets::register(password);
+    }
+    return Ok(result);
+}
+
+/* resolve which is executed once per connection string */
+pub fn get_resolved(connection_string: &str) -> anyhow::Result<Params> {
+    let mut resolved = RESOLVED.lock().map_err(|_| anyhow!("get_resolved error: failed to lock resolved parameters"))?;
+    let resolved = resolved.get_or_insert_with(HashMap::new);
+    if let Some(params) = resolved.get(connection_string) {
+        return Ok(params.clone());
+    }
+    let params = resolve(connection_string)?;
+    resolved.insert(connection_string.into(), params.clone());
+    return Ok(params);
+}
+
+/* resolved parameters of connection string from env_var (WO_CONNECTION_STRING or WO_ADMIN_CONNECTION_STRING) */
+pub fn get_params(env_var: &str) -> anyhow::Result<Params> {
+    let connection_string = utils::read_env_var(env_var)?;
+    let result = get_resolved(&connection_string)
+        .context(format!("invalid connection parameters of {}", env_var))?;
+    return Ok(result);
+}
+
+/* sslrootcert (PGSSLROOTCERT), WO_ROOT_CERT or ~/.postgresql/root.crt if it exists */
+fn get_root_cert_path(params: &Params) -> Option<PathBuf> {
+    if let Some(path) = params.get("sslrootcert") {
+        return Some(PathBuf::from(path));
+    }
+    if let Ok(path) = env::var("WO_ROOT_CERT") {
+        if !path.is_empty() {
+            return Some(PathBuf::from(path));
+        }
+    }
+    let default_path_buf = PathBuf::from(env::var("HOME").ok()?).join(".postgresql").join("root.crt");
+    if default_path_buf.exists() {
+        return Some(default_path_buf);
+    }
+    return None;
+}
+
+/* sslmode, default is verify-full if WO_ROOT_CERT is set, otherwise prefer,
+require with root certificate behaves like verify-ca (like in libpq) */
+pub fn get_ssl_mode(params: &Params) -> anyhow::Result<SslMode> {
+    let has_root_cert = get_root_cert_path(params).is_some();
+    let sslmode = match params.get("sslmode") {
+        Some(sslmode) => sslmode.as_str(),
+        None if env::var("WO_ROOT_CERT").map(|p| !p.is_empty()).unwrap_or(false) => "verify-full",
+        None => "prefer",
+    };
+    let result = match sslmode {
+        "disable" => SslMode::Disable,
+        "prefer" => SslMode::Prefer,
+        "require" if has_root_cert => SslMode::VerifyCa,
+        "require" => SslMode::Require,
+        "verify-ca" => SslMode::VerifyCa,
+        "verify-full" => SslMode::VerifyFull,
+        _ => bail!("unsupported sslmode {:?}, use disable, prefer, require, verify-ca or verify-full", sslmode),
+    };
+    return Ok(result);
+}
+
+/* quoted value of keyword = value connection string */
+fn quote_value(value: &str) -> String {
+    return format!("'{}'", value.replace('\\', "\\\\").replace('\'', "\\'"));
+}
+
+/* postgres config of resolved parameters, tls is configured by connect */
+pub fn get_pg_config(params: &Params) -> anyhow::Result<postgres::Config> {
+    let mut connection_string = String::new();
+    for (keyword, value) in params.iter() {
+        if LIBPQ_PARAMS.contains(&keyword.as_str()) {
+            continue;
+        }
+        connection_string.push_str(&format!("{}={} ", keyword, quote_value(value)));
+    }
+    let mut config: postgres::Config = connection_string.parse()
+        .context("failed to parse connection parameters")?;
+    config.ssl_mode(match get_ssl_mode(params)? {
+        SslMode::Disable => postgres::config::SslMode::Disable,
+        SslMode::Prefer => postgres::config::SslMode::Prefer,
+        _ => postgres::config::SslMode::Require,
+    });
+    return Ok(config);
+}
+
+fn read_root_cert(root_cert_path_buf: &PathBuf) -> anyhow::Result<native_tls::Certificate> {
+    let root_cert_b = fs::read(&root_cert_path_buf)
+        .context(format!("failed to read cert file {:?}", root_cert_path_buf))?;
+
+    match root_cert_path_buf.extension().and_then(OsStr::to_str) {
+        Some("pem") | Some("crt") => {
+            return Ok(native_tls::Certificate::from_pem(&root_cert_b)?);
+        },
+        Some("der") => {
+            return Ok(native_tls::Certificate::from_der(&root_cert_b)?);
+        },
+        _ => {
+            match native_tls::Certificate::from_pem(&root_cert_b) {
+                Ok(pem_cert) => return Ok(pem_cert),
+                Err(pem_err) => {
+                    match native_tls::Certificate::from_der(&root_cert_b) {
+                        Ok(der_cert) => return Ok(der_cert),
+                        Err(der_err) => {
+                            return Err(pem_err)
+                            .context(der_err)
+                            .context(format!("could not parse cert file neither as pem nor der format {:?}", root_cert_path_buf));
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/* connects using tls according to sslmode, verify modes trust only the root certificate if it is provided,
+otherwise system certificates, notices are reported to output */
+pub fn connect(params: &Params) -> anyhow::Result<postgres::Client> {
+    let mut config = get_pg_config(params)?;
+    config.notice_callback(output::notice);
+
+    let ssl_mode = get_ssl_mode(params)?;
+    if ssl_mode == SslMode::Disable {
+        let pg_client = config.connect(postgres::NoTls)
+            .context("failed to connect to db using no TLS")?;
+        return Ok(pg_client);
+    }
+
+    let mut builder = native_tls::TlsConnector::builder();
+    match ssl_mode {
+        SslMode::Prefer | SslMode::Require => {
+            builder.danger_accept_invalid_certs(true);
+            builder.danger_accept_invalid_hostnames(true);
+        },
+        SslMode::VerifyCa | SslMode::VerifyFull => {
+            if ssl_mode == SslMode::VerifyCa {
+                builder.danger_accept_invalid_hostnames(true);
+            }
+            if let Some(root_cert_path_buf) = get_root_cert_path(params) {
+                builder.add_root_certificate(read_root_cert(&root_cert_path_buf)?);
+                builder.disable_built_in_roots(true);
+            }
+        },
+        SslMode::Disable => {},
+    }
+    let connector = builder.build()
+        .context("TLS configuration error")?;
+    let connector = postgres_native_tls::MakeTlsConnector::new(connector);
+
+    let pg_client = config.connect(connector)
+        .context(format!("failed to connect to db using TLS (sslmode {:?})", ssl_mode))?;
+    return Ok(pg_client);
+}
@@ -1,78 +1,71 @@
 
 use std::collections::HashMap;
 use std::collections::HashSet;
-use std::ffi::OsStr;
-use std::fs;
 use std::iter::FromIterator;
 use std::ops::Sub;
-use std::path::PathBuf;
+use std::time::Instant;
 use anyhow;
 use anyhow::Context;
 use postgres;
-use postgres_native_tls;
-use native_tls;
+use postgres::GenericClient;
 use crate::project::DatabaseProject;
 use crate::project::DatabaseObject;
 use crate::project::DatabaseObjectType;
 use crate::project;
 use crate::utils;
+use crate::connection;
+use crate::sql;
+use crate::diff;
+use crate::graph;
+use crate::output;
+use crate::template;
 
+#[cfg(test)]
+mod tests;
 
 
 fn get_pg_client_from_connection_string(connection_string: &str) -> anyhow::Result<postgres::Client> {
-    
-    let root_cert_path = utils::read_env_var("WO_ROOT_CERT")?;
-    if root_cert_path == "" {
-        let pg_client = postgres::Client::connect(&connection_string, postgres::NoTls)
-            .context("failed to connect to db using no TLS")?;
-        return Ok(pg_client);
-    }
+    let params = connection::get_resolved(connection_string)?;
+    let pg_client = connection::connect(&params)?;
+    return Ok(pg_client);
+}
 
+/* batch_execute which echoes the script with its duration (-vv) */
+pub fn batch_execute<C: GenericClient>(
+    pg_client: &mut C,
+    sql: &str
+) -> Result<(), postgres::Error> {
+    let started = Instant::now();
+    let result = pg_client.batch_execute(sql);
+    output::sql(sql, started);
+    return result;
+}
 
-    let root_cert_path_buf = PathBuf::from(root_cert_path);
-    let root_cert_b = fs::read(&root_cert_path_buf)
-        .context(format!("failed to read cert file {:?}", root_cert_path_buf))?;
+/* execute which echoes the statement with its duration (-vv) */
+pub fn execute<C: GenericClient>(
+    pg_client: &mut C,
+    sql: &str,
+    params: &[&(dyn postgres::types::ToSql + Sync)]
+) -> Result<u64, postgres::Error> {
+    let started = Instant::now();
+    let result = pg_client.execute(sql, params);
+    output::sql(sql, started);
+    return result;
+}
 
-    let root_cert;
-    
-    match root_cert_path_buf.extension().and_then(OsStr::to_str) {
-        Some("pem") => {
-            root_cert = native_tls::Certificate::from_pem(&root_cert_b)?;
-        },
-        Some("der") => {
-            root_cert = native_tls::Certificate::from_der(&root_cert_b)?;
-        },
-        _ => {
-            match native_tls::Certificate::from_pem(&root_cert_b) {
-                Ok(pem_cert) => {
-                    root_cert = pem_cert;
-                }
-                Err(pem_err) => {
-                    match native_tls::Certificate::from_der(&root_cert_b) {
-                        Ok(der_cert) => {
-                            root_cert = der_cert;
-                        }
-                        Err(der_err) => {
-                            return Err(pem_err)
-                            .context(der_err)
-                            .context(format!("could not parse cert file neither as pem nor der format {:?}", root_cert_path_buf));
-                        }
-                    }
-                }
-            }
+/* batch_execute of project script (or its part), postgres errors are described with file line, column and snippet */
+fn execute_script<C: GenericClient>(
+    pg_client: &mut C,
+    sql: &str,
+    location: Option<&output::ScriptLocation>
+) -> anyhow::Result<()> {
+    if let Err(e) = batch_execute(pg_client, sql) {
+        if let Some(description) = output::describe_db_error(&e, location) {
+            return Err(anyhow::Error::new(e).context(description));
         }
+        return Err(e.into());
     }
-    
-    let connector = native_tls::TlsConnector::builder()
-        .add_root_certificate(root_cert)
-        .build()
-        .context("TLS configuration error")?;
-
-    let connector = postgres_native_tls::MakeTlsConnector::new(connector);
-    
-    let pg_client = postgres::Client::connect(&connection_string, connector)
-        .context("failed to connect to db using TLS")?;
-    return Ok(pg_client);
+    return Ok(());
 }
 
 fn get_admin_pg_client() -> anyhow::Result<postgres::Client> {
@@ -92,6 +85,25 @@ fn get_pg_client() -> anyhow::Result<postgres::Client> {
 }
 
 
+/* "<schema>.<table>" of tables having at least min_rows estimated rows */
+pub fn select_large_tables(min_rows: i64) -> anyhow::Result<Vec<String>> {
+    let mut pg_client = get_pg_client()?;
+    let sql = "
+        select n.nspname || '.' || c.relname
+        from pg_class c
+        join pg_namespace n on n.oid = c.relnamespace
+        where c.relkind in ('r', 'p', 'm')
+        and c.reltuples::bigint >= $1
+        and n.nspname not in ('pg_catalog', 'information_schema')
+        order by 1;";
+    let mut result = vec![];
+    for row in pg_client.query(sql, &[&min_rows])? {
+        let table: String = row.try_get(0)?;
+        result.push(table.to_lowercase());
+    }
+    return Ok(result);
+}
+
 fn update_wo_object(
     pg_client: &mut postgres::Client,
     object: &DatabaseObject
@@ -103,15 +115,17 @@ fn update_wo_object(
             po_script,
             po_path,
             po_depends_on,
-            po_required_by
+            po_required_by,
+            po_drop_script
         )
-        select $1, $2, $3, $4, $5, $6
+        select $1, $2, $3, $4, $5, $6, $7
         on conflict (po_id) do update set 
             po_md5 = excluded.po_md5,
             po_script = excluded.po_script,
             po_path = excluded.po_path,
             po_depends_on = excluded.po_depends_on,
-            po_required_by = excluded.po_required_by;";
+            po_required_by = excluded.po_required_by,
+            po_drop_script = excluded.po_drop_script;";
 
     let path_str: String = object.path_buf.clone().into_os_string().to_str()
         .ok_or(anyhow!("object_id_from_path error: could not parse filename"))?
@@ -120,13 +134,14 @@ fn update_wo_object(
     let depends_on_vec: Vec<&String> = Vec::from_iter(&object.depends_on);
     let required_by_vec: Vec<&String> = Vec::from_iter(&object.required_by);
 
-    pg_client.execute(sql, &[
+    execute(pg_client, sql, &[
         &object.id,
         &object.md5,
         &object.script,
         &path_str,
         &depends_on_vec,
-        &required_by_vec
+        &required_by_vec,
+        &object.drop_script
     ])?;
     return Ok(());
 }
@@ -136,7 +151,7 @@ fn delete_wo_object(
     object_id: &str
 ) -> anyhow::Result<()> {
     let sql = "delete from wo_objects where lower(po_id) = lower($1)";
-    pg_client.execute(sql, &[&object_id])
+    execute(pg_client, sql, &[&object_id])
         .context(format!("delete_wo_object failed {:?}", object_id))?;
     return Ok(());
 }
@@ -159,13 +174,19 @@ fn exists_object(
                 from pg_views
                 where lower('view' || '.' || schemaname || '.' || viewname) = lower($1)
             )",
-        DatabaseObjectType::Function => "
-            select exists (
-                select 1
-                from pg_proc p
-                join pg_namespace n on n.oid = p.pronamespace
-                where lower('function' || '.' || n.nspname || '.' || p.proname) = lower($1)
-            );",
+        DatabaseObjectType::Function => {
+            if project::get_signature(object_id)?.is_some() {
+                "select to_regprocedure(substr($1, length('function.') + 1)) is not null;"
+            } else {
+                "
+                select exists (
+                    select 1
+                    from pg_proc p
+                    join pg_namespace n on n.oid = p.pronamespace
+                    where lower('function' || '.' || n.nspname || '.' || p.proname) = lower($1)
+                );"
+            }
+        },
         DatabaseObjectType::Constraint => "
             select exists (
                 select 1
@@ -214,6 +235,44 @@ fn exists_object(
                 join pg_namespace n on n.oid = t.typnamespace
                 where lower('type' || '.' || n.nspname || '.' || t.typname) = lower($1)
             );",
+        DatabaseObjectType::EventTrigger => "
+            select exists (
+                select 1
+                from pg_event_trigger
+                where lower('event_trigger' || '.' || evtname) = lower($1)
+            );",
+        DatabaseObjectType::Rule => "
+            select exists (
+                select 1
+                from pg_rules
+                where lower('rule' || '.' || schemaname || '.' || tablename || '.' || rulename) = lower($1)
+            );",
+        DatabaseObjectType::Publication => "
+            select exists (
+                select 1
+                from pg_publication
+                where lower('publication' || '.' || pubname) = lower($1)
+            );",
+        DatabaseObjectType::ForeignServer => "
+            select exists (
+                select 1
+                from pg_foreign_server
+                where lower('foreign_server' || '.' || srvname) = lower($1)
+            );",
+        DatabaseObjectType::UserMapping => "
+            select exists (
+                select 1
+                from pg_user_mappings
+                where lower('user_mapping' || '.' || srvname || '.' || usename) = lower($1)
+            );",
+        DatabaseObjectType::ForeignTable => "
+            select exists (
+                select 1
+                from pg_foreign_table f
+                join pg_class c on c.oid = f.ftrelid
+                join pg_namespace n on n.oid = c.relnamespace
+                where lower('foreign_table' || '.' || n.nspname || '.' || c.relname) = lower($1)
+            );",
     };
 
     let row = pg_client.query_one(sql, &[&object_id])
@@ -226,12 +285,19 @@ fn exists_object(
 
 fn drop_object(
     pg_client: &mut postgres::Client,
-    object_id: &str
+    object_id: &str,
+    drop_script: Option<&str>
 ) -> anyhow::Result<()> {
-    println!("drop if exists {:?}", object_id);
+    let mut action = output::Action::start("drop if exists", object_id);
     let object_type = project::get_object_type(object_id)?;
     let exists = exists_object(pg_client, &object_id)?;
-    if exists {
+    if exists && object_type != DatabaseObjectType::Table && drop_script.is_some() {
+        // user defined drop script is executed instead of generated one
+        output::debug(&format!("execute drop script {:?}", object_id));
+        let script = utils::expand_env_vars(drop_script.unwrap_or(""))?;
+        action.sql(&script);
+        execute_script(pg_client, &script, None)?;
+    } else if exists {
         match object_type {
             DatabaseObjectType::Table => bail!("attempting to drop a table, \
                 tables should be dropped manually or using migration scripts {:?}", object_id),
@@ -239,36 +305,49 @@ fn drop_object(
                 let schema = project::get_schema(object_id)?;
                 let name = project::get_name(object_id)?;
                 let sql = format!("drop view {}.{};", schema, name);
-                pg_client.batch_execute(&sql)?;
+                action.sql(&sql);
+                batch_execute(pg_client, &sql)?;
             },
             DatabaseObjectType::Function => {
-                let sql = format!("
-                    do language plpgsql
-                    $$
-                    begin
-                        if exists (
-                            select 1
+                let schema = project::get_schema(object_id)?;
+                let name = project::get_name(object_id)?;
+                let sql;
+                if let Some(signature) = project::get_signature(object_id)? {
+                    sql = format!("drop function {}.{}({});", schema, name, signature);
+                } else {
+                    // overloads tracked with their own signature ids are left untouched
+                    sql = format!("
+                        do language plpgsql
+                        $$
+                        declare
+                            drop_sql text;
+                        begin
+                            select string_agg(
+                                format('drop function %s;', p.oid::regprocedure),
+                                E'\n'
+                            )
+                            into drop_sql
                             from pg_proc p
                             join pg_namespace n on n.oid = p.pronamespace
                             where lower('function' || '.' || n.nspname || '.' || p.proname) = lower('{}')
-                        ) then
-                            execute(
-                                select string_agg(
-                                    format('drop function %s;', p.oid::regprocedure),
-                                    E'\n'
-                                )
-                                from pg_proc p
-                                join pg_namespace n on n.oid = p.pronamespace
-                                where lower('function' || '.' || n.nspname || '.' || p.proname) = lower('{}')
+                            and p.oid not in (
+                                select to_regprocedure(substr(po_id, length('function.') + 1))::oid
+                                from wo_objects
+                                where po_id like 'function.%(%'
+                                and to_regprocedure(substr(po_id, length('function.') + 1)) is not null
                             );
-                        end if;
-                    end
-                    $$;",
-                    object_id,
-                    object_id,
-                );
 
-                pg_client.batch_execute(&sql)?;
+                            if drop_sql is not null then
+                                execute drop_sql;
+                            end if;
+                        end
+                        $$;",
+                        object_id,
+                    );
+                }
+
+                action.sql(&sql);
+                batch_execute(pg_client, &sql)?;
             },
             DatabaseObjectType::Constraint => {
                 let schema = project::get_schema(object_id)?;
@@ -280,10 +359,11 @@ fn drop_object(
                     name,
                 );
 
-                pg_client.batch_execute(&drop_constraint_sql)?;
+                action.sql(&drop_constraint_sql);
+                batch_execute(pg_client, &drop_constraint_sql)?;
             },
             DatabaseObjectType::Role => {
-                let wo_role = utils::get_role_name()?;
+                let wo_role = template::quote_ident(&utils::get_role_name()?);
                 let drop_role_name = project::get_name(object_id)?;
                 let sql = format!("
                     grant {drop_role_name} to {wo_role};
@@ -294,7 +374,9 @@ fn drop_object(
                     wo_role=wo_role,
                 );
                 
-                pg_client.batch_execute(&sql)?;
+                action.sql(&sql);
+                batch_execute(pg_client, &sql)?;
+                action.finish();
                 return Ok(());
             },
             DatabaseObjectType::Trigger => {
@@ -307,7 +389,8 @@ fn drop_object(
                     table,
                 );
 
-                pg_client.batch_execute(&drop_trigger_sql)?;
+                action.sql(&drop_trigger_sql);
+                batch_execute(pg_client, &drop_trigger_sql)?;
             },
             DatabaseObjectType::Policy => {
                 let schema = project::get_schema(object_id)?;
@@ -319,27 +402,77 @@ fn drop_object(
                     table,
                 );
 
-                pg_client.batch_execute(&drop_policy_sql)?;
+                action.sql(&drop_policy_sql);
+                batch_execute(pg_client, &drop_policy_sql)?;
             },
             DatabaseObjectType::Schema => {
                 let name = project::get_name(object_id)?;
                 let sql = format!("drop schema {};", name);
-                pg_client.batch_execute(&sql)?;
+                action.sql(&sql);
+                batch_execute(pg_client, &sql)?;
             },
             DatabaseObjectType::Extension => {
                 let name = project::get_name(object_id)?;
                 let sql = format!("drop extension {};", name);
-                pg_client.batch_execute(&sql)?;
+                action.sql(&sql);
+                batch_execute(pg_client, &sql)?;
             },
             DatabaseObjectType::Type => {
                 let name = project::get_name(object_id)?;
                 let sql = format!("drop type {};", name);
-                pg_client.batch_execute(&sql)?;
+                action.sql(&sql);
+                batch_execute(pg_client, &sql)?;
+            },
+            DatabaseObjectType::EventTrigger => {
+                let name = project::get_name(object_id)?;
+                let sql = format!("drop event trigger {};", name);
+                action.sql(&sql);
+                batch_execute(pg_client, &sql)?;
+            },
+            DatabaseObjectType::Rule => {
+                let schema = project::get_schema(object_id)?;
+                let table = project::get_table(object_id)?;
+                let name = project::get_name(object_id)?;
+                let drop_rule_sql = format!("drop rule {} on {}.{};",
+                    name,
+                    schema,
+                    table,
+                );
+
+                action.sql(&drop_rule_sql);
+                batch_execute(pg_client, &drop_rule_sql)?;
+            },
+            DatabaseObjectType::Publication => {
+                let name = project::get_name(object_id)?;
+                let sql = format!("drop publication {};", name);
+                action.sql(&sql);
+                batch_execute(pg_client, &sql)?;
+            },
+            DatabaseObjectType::ForeignServer => {
+                let name = project::get_name(object_id)?;
+                let sql = format!("drop server {};", name);
+                action.sql(&sql);
+                batch_execute(pg_client, &sql)?;
+            },
+            DatabaseObjectType::UserMapping => {
+                let server = project::get_server(object_id)?;
+                let role = project::get_name(object_id)?;
+                let sql = format!("drop user mapping for {} server {};", role, server);
+                action.sql(&sql);
+                batch_execute(pg_client, &sql)?;
+            },
+            DatabaseObjectType::ForeignTable => {
+                let schema = project::get_schema(object_id)?;
+                let name = project::get_name(object_id)?;
+                let sql = format!("drop foreign table {}.{};", schema, name);
+                action.sql(&sql);
+                batch_execute(pg_client, &sql)?;
             },
         };
     }
 
     delete_wo_object(pg_client, &object_id)?;
+    action.finish();
     return Ok(());
 }
 
@@ -349,20 +482,25 @@ fn drop_object_with_deps(
     database_project: &DatabaseProject,
     objects: &HashMap<String, DatabaseObject>,
     dropped: &mut HashSet<String>,
-    visited: &mut HashSet<String>,
+    path: &mut Vec<String>,
 ) -> anyhow::Result<()> {
     if dropped.contains(&object.id) {
         return Ok(());
     }
 
-    if visited.contains(&object.id) {
-        bail!("drop_object_with_deps: cycle detected {:?}", object.id);
+    if let Some(i) = path.iter().position(|id| *id == object.id) {
+        // path follows "required by" edges, cycle is reported in "depends on" direction
+        let mut cycle = path[i..].to_vec();
+        cycle.push(object.id.clone());
+        cycle.reverse();
+        bail!("drop_object_with_deps: {}", graph::describe_cycle(&database_project.objects, &cycle)?);
     }
-    visited.insert(object.id.clone());
+    path.push(object.id.clone());
 
     // first attempt to drop the target without dropping dependencies
-    let drop_result = drop_object(pg_client, &object.id);
+    let drop_result = drop_object(pg_client, &object.id, object.drop_script.as_deref());
     if drop_result.is_ok() {
+        path.pop();
         dropped.insert(object.id.clone());
         return Ok(());
     }
@@ -375,7 +513,7 @@ fn drop_object_with_deps(
                 &database_project,
                 &objects,
                 dropped,
-                visited
+                path
             )?;
 
         } else if let Some(dep) = database_project.objects.get(dep_id) {
@@ -385,16 +523,17 @@ fn drop_object_with_deps(
                 &database_project,
                 &objects,
                 dropped,
-                visited
+                path
             )?;
 
         } else {
-            drop_object(pg_client, dep_id)
+            drop_object(pg_client, dep_id, None)
                 .context(format!("undefined dependency could not be dropped {:?} {:?}", object.id, dep_id))?;
         }
     }
 
-    drop_object(pg_client, &object.id)?;
+    drop_object(pg_client, &object.id, object.drop_script.as_deref())?;
+    path.pop();
     dropped.insert(object.id.clone());
     return Ok(());
 }
@@ -414,9 +553,9 @@ fn force_drop_role_if_exists(
         return Ok(());
     }
 
-    println!("force drop role {:?}", object_id);
+    let mut action = output::Action::start("force drop role", object_id);
     let drop_role_name = project::get_name(object_id)?;
-    let role_name = utils::get_role_name()?;
+    let role_name = template::quote_ident(&utils::get_role_name()?);
     
     let sql = format!(
         "
@@ -429,7 +568,9 @@ fn force_drop_role_if_exists(
         role_name=role_name,
     );
 
-    pg_client.batch_execute(&sql)?;
+    action.sql(&sql);
+    batch_execute(pg_client, &sql)?;
+    action.finish();
     return Ok(());
 }
 
@@ -457,35 +598,282 @@ fn create_if_missing(
     if exists {
         let wo_exists = exists_wo_object(pg_client, &object.id)?;
         if !wo_exists {
-            println!("create missing wo_objects record {:?}", object.id);
+            output::debug(&format!("create missing wo_objects record {:?}", object.id));
         }
         // always update because required_by could have changed
         update_wo_object(pg_client, &object)?;
         return Ok(());
     }
-    println!("create {:?}", object.id);
-    pg_client.batch_execute(&object.script)?;
+    let mut action = output::Action::start("create", &object.id);
+    let script = utils::expand_env_vars(&object.script)?;
+    action.sql(&script);
+    let location = output::ScriptLocation {
+        path_buf: &object.path_buf,
+        script: &script,
+        offset: 0,
+        line_offset: object.line_offset,
+    };
+    execute_script(pg_client, &script, Some(&location))?;
     update_wo_object(pg_client, &object)?;
+    action.finish();
     return Ok(());
 }
 
+/* signatures of statements creating given view or function, each with "or replace" flag */
+fn get_created_signatures(
+    object_id: &str,
+    script: &str
+) -> anyhow::Result<Vec<(String, bool)>> {
+    let object_type = project::get_object_type(object_id)?;
+    let schema = project::get_schema(object_id)?;
+    let name = project::get_name(object_id)?;
+    let mut result = vec![];
+    for statement in sql::split_statements(script) {
+        let created;
+        if object_type == DatabaseObjectType::Function {
+            created = sql::parse_create_function(&statement)
+                .map(|f| (f.schema, f.name, f.signature.to_lowercase(), f.or_replace));
+        } else if object_type == DatabaseObjectType::View {
+            created = sql::parse_create_view(&statement)
+                .map(|v| (v.schema, v.name, String::new(), v.or_replace && !v.materialized));
+        } else {
+            bail!("get_created_signatures error: only views and functions can be replaced {:?}", object_id);
+        }
 
-fn prepare_admin_script(template_str: &str) -> anyhow::Result<String> {
-    let database_name = utils::get_database_name()?;
-    let role_name = utils::get_role_name()?;
-    let password = utils::get_password()?;
-    let mut result = template_str.replace("{database_name}", &database_name);
-    result = result.replace("{role_name}", &role_name);
-    if let Some(p) = password {
-        result = result.replace("{password}", &p);
-    } else {
-        if result.contains("{password}") {
-            bail!("admin script expects password parameter to be provided");
+        if let Some((created_schema, created_name, signature, or_replace)) = created {
+            let created_schema = created_schema.unwrap_or(schema.into());
+            if created_schema.to_lowercase() == schema && created_name.to_lowercase() == name {
+                result.push((signature, or_replace));
+            }
         }
     }
+    result.sort();
     return Ok(result);
 }
 
+/* object can be replaced in place if it is created using "or replace" with unchanged signatures */
+fn can_replace_object(
+    db_object: &DatabaseObject,
+    p_object: &DatabaseObject
+) -> anyhow::Result<bool> {
+    let new_signatures = get_created_signatures(&p_object.id, &p_object.script)?;
+    if new_signatures.is_empty() {
+        return Ok(false);
+    }
+    for (_, or_replace) in new_signatures.iter() {
+        if !or_replace {
+            return Ok(false);
+        }
+    }
+
+    // changed signature would create a new overload instead of replacing the old one
+    let old_signatures = get_created_signatures(&db_object.id, &db_object.script)?;
+    let new_signatures: Vec<&String> = new_signatures.iter().map(|(s, _)| s).collect();
+    let old_signatures: Vec<&String> = old_signatures.iter().map(|(s, _)| s).collect();
+    return Ok(new_signatures == old_signatures);
+}
+
+/* executes modified script without dropping the object, postgres rejects unsafe replacements */
+fn replace_object(
+    pg_client: &mut postgres::Client,
+    object: &DatabaseObject,
+) -> anyhow::Result<()> {
+    let mut action = output::Action::start("create or replace", &object.id);
+    let script = utils::expand_env_vars(&object.script)?;
+    action.sql(&script);
+    let location = output::ScriptLocation {
+        path_buf: &object.path_buf,
+        script: &script,
+        offset: 0,
+        line_offset: object.line_offset,
+    };
+    let mut transaction = pg_client.transaction()?;
+    execute_script(&mut transaction, &script, Some(&location))?;
+    transaction.commit()?;
+    update_wo_object(pg_client, &object)?;
+    action.finish();
+    return Ok(());
+}
+
+// default values of publication options, used to reset options removed from the script
+const PUBLICATION_OPTION_DEFAULTS: &[(&str, &str)] = &[
+    ("publish", "'insert, update, delete, truncate'"),
+    ("publish_via_partition_root", "false"),
+];
+
+fn find_create_publication(object: &DatabaseObject) -> Option<sql::CreatePublication> {
+    let name = project::get_name(&object.id).ok()?;
+    return sql::split_statements(&object.script).iter()
+        .filter_map(|statement| sql::parse_create_publication(statement))
+        .find(|p| sql::unquote_ident(&p.name) == name);
+}
+
+/* "<option> = <default>" of options declared in old options but missing in new ones */
+fn get_publication_reset_options(old_options: Option<&str>, new_options: Option<&str>) -> Vec<String> {
+    let declares = |options: Option<&str>, option: &str| options
+        .map(|options| utils::contains_whole_word_ci(options, option))
+        .unwrap_or(false);
+    return PUBLICATION_OPTION_DEFAULTS.iter()
+        .filter(|(option, _)| declares(old_options, option) && !declares(new_options, option))
+        .map(|(option, default)| format!("{} = {}", option, default))
+        .collect();
+}
+
+/* publications are updated by replacing their table list and options instead of dropping them,
+executed after missing objects are created so that new tables can be added */
+fn alter_publication(
+    pg_client: &mut postgres::Client,
+    db_object: &DatabaseObject,
+    object: &DatabaseObject,
+) -> anyhow::Result<()> {
+    let create_publication = find_create_publication(object)
+        .ok_or(anyhow!("alter_publication error: could not find create publication statement {:?}", object.id))?;
+    let old_options = find_create_publication(db_object).and_then(|p| p.options);
+
+    let objects = create_publication.objects
+        .ok_or(anyhow!("alter_publication error: publication without table list can not be altered {:?}", object.id))?;
+    if objects.to_lowercase().starts_with("all ") {
+        bail!("alter_publication error: publication for all tables can not be altered {:?}", object.id);
+    }
+
+    let mut action = output::Action::start("alter publication", &object.id);
+    let mut sql = format!("alter publication {} set {};", create_publication.name, objects);
+    let mut options: Vec<String> = create_publication.options.iter().cloned().collect();
+    options.extend(get_publication_reset_options(old_options.as_deref(), create_publication.options.as_deref()));
+    if !options.is_empty() {
+        sql = format!("{}\nalter publication {} set ({});", sql, create_publication.name, options.join(", "));
+    }
+
+    action.sql(&sql);
+    let mut transaction = pg_client.transaction()?;
+    batch_execute(&mut transaction, &sql)?;
+    transaction.commit()?;
+    update_wo_object(pg_client, &object)?;
+    action.finish();
+    return Ok(());
+}
+
+/* create role statement of the role object */
+fn find_create_role(object_id: &str, script: &str) -> anyhow::Result<Option<sql::CreateRole>> {
+    let name = project::get_name(object_id)?;
+    return Ok(sql::split_statements(script).iter()
+        .filter_map(|statement| sql::parse_create_role(statement))
+        .find(|r| sql::unquote_ident(&r.name).to_lowercase() == name));
+}
+
+/* changed roles are altered instead of dropped, so that privileges granted outside wo are kept */
+fn alter_role(
+    pg_client: &mut postgres::Client,
+    db_object: &DatabaseObject,
+    p_object: &DatabaseObject,
+) -> anyhow::Result<()> {
+    let old_script = template::blank_external(&db_object.script)?;
+    let old_create_role = find_create_role(&db_object.id, &old_script)?
+        .ok_or(anyhow!("alter_role error: could not parse create role statement of previous script {:?}", db_object.id))?;
+
+    let script = utils::expand_env_vars(&p_object.script)?;
+    let new_create_role = find_create_role(&p_object.id, &script)?
+        .ok_or(anyhow!("alter_role error: could not parse create role statement {:?}", p_object.id))?;
+
+    let mut action = output::Action::start("alter role", &p_object.id);
+    let mut transaction = pg_client.transaction()?;
+    for sql in sql::get_alter_role_statements(&old_create_role, &new_create_role) {
+        action.sql(&sql);
+        batch_execute(&mut transaction, &sql)?;
+    }
+    // statements following create role (grants, settings, etc.) are executed again
+    let line_offset = p_object.line_offset;
+    for statement in sql::split_statements(&script) {
+        if sql::parse_create_role(&statement).is_some() {
+            continue;
+        }
+        action.sql(statement.text);
+        let location = output::ScriptLocation {
+            path_buf: &p_object.path_buf,
+            script: &script,
+            offset: statement.offset,
+            line_offset,
+        };
+        execute_script(&mut transaction, statement.text, Some(&location))?;
+    }
+    transaction.commit()?;
+    update_wo_object(pg_client, &p_object)?;
+    action.finish();
+    return Ok(());
+}
+
+/* md5 of the script does not change when env variable or secret used as password is rotated,
+so such passwords are set again on every migration of unchanged roles */
+fn update_role_password(
+    pg_client: &mut postgres::Client,
+    p_object: &DatabaseObject,
+) -> anyhow::Result<()> {
+    // placeholders are not valid sql, so create role statement can be parsed only after substitution
+    let uses_external_values = sql::split_statements(&p_object.script).iter()
+        .filter(|statement| ["role", "user", "group"].iter().any(|kind| statement.starts_with_words(&["create", kind])))
+        .any(|statement| template::has_external_placeholders(statement.text));
+    if !uses_external_values {
+        return Ok(());
+    }
+    let script = utils::expand_env_vars(&p_object.script)?;
+    let create_role = find_create_role(&p_object.id, &script)?
+        .ok_or(anyhow!("update_role_password error: could not parse create role statement {:?}", p_object.id))?;
+    let password = create_role.attributes.iter()
+        .find(|(key, _)| key == "password")
+        .map(|(_, sql)| sql);
+    if let Some(password) = password {
+        output::debug(&format!("setting password from external value {:?}", p_object.id));
+        batch_execute(pg_client, &format!("alter role {} with {};", create_role.name, password))
+            .context(format!("update_role_password error: failed to set password {:?}", p_object.id))?;
+    }
+    return Ok(());
+}
+
+
+/* create and drop scripts additionally get database_name, role_name and password variables from WO_CONNECTION_STRING */
+/* renders create or drop script, admin parameters used unquoted (e.g. {role_name}) are substituted as is
+and have to be alphanumeric, quoted forms ({role_name:ident}, {password:literal}) accept any value */
+fn render_admin_script(
+    template_str: &str,
+    variables: &template::Variables,
+    admin_parameters: &[(&str, Option<String>)]
+) -> anyhow::Result<String> {
+    let mut variables = variables.clone();
+    for (name, value) in admin_parameters.iter() {
+        let unquoted = format!("{{{}}}", name);
+        match value {
+            Some(value) => {
+                if template_str.contains(&unquoted) {
+                    utils::validate_admin_script_param(value)
+                        .context(format!("admin script uses unquoted {}, use {{{}:ident}} or {{{}:literal}} for values with special characters", unquoted, name, name))?;
+                }
+                variables.insert(String::from(*name), value.clone());
+            },
+            None => {
+                if template_str.contains(&unquoted) || template_str.contains(&format!("{{{}:", name)) {
+                    bail!("admin script expects {} parameter to be provided", name);
+                }
+            },
+        }
+    }
+    let result = template::render(template_str, &variables)?;
+    // {env:NAME} and {secret:name} placeholders are kept by render
+    let result = utils::expand_env_vars(&result)?;
+    return Ok(result);
+}
+
+fn prepare_admin_script(
+    template_str: &str,
+    variables: &template::Variables
+) -> anyhow::Result<String> {
+    let admin_parameters = [
+        ("database_name", Some(utils::get_database_name()?)),
+        ("role_name", Some(utils::get_role_name()?)),
+        ("password", utils::get_password()?),
+    ];
+    return render_admin_script(template_str, variables, &admin_parameters);
+}
+
 fn exists_database(
     admin_pg_client: &mut postgres::Client
 ) -> anyhow::Result<bool> {
@@ -504,12 +892,15 @@ fn create_database(
     database_project: &DatabaseProject
 ) -> anyhow::Result<()> {
     for (path_buf, script) in database_project.create_scripts.iter() {
-        println!("create_database: executing {:?}", path_buf);
-        let prepared_script = prepare_admin_script(&script)?;
-        admin_pg_client.batch_execute(&prepared_script)
+        let mut action = output::Action::start("create database", &path_buf.to_string_lossy());
+        let prepared_script = prepare_admin_script(&script, &database_project.variables)?;
+        action.sql(&prepared_script);
+        let location = output::ScriptLocation { path_buf, script: &prepared_script, offset: 0, line_offset: 0 };
+        execute_script(admin_pg_client, &prepared_script, Some(&location))
             .with_context(|| format!("create error: failed to execute script: {:?}", path_buf))?;
+        action.finish();
     }
-    println!("create_database: fresh database created");
+    output::info("create_database: fresh database created");
     return Ok(());
 }
 
@@ -526,15 +917,22 @@ fn create_wo_tables(
             po_required_by text[]
         );";
 
-    pg_client.batch_execute(wo_objects_sql)
+    batch_execute(pg_client, wo_objects_sql)
         .context("failed to create wo_objects table")?;
 
+    // columns added in later versions
+    let wo_objects_columns_sql = "
+        alter table wo_objects add column if not exists po_drop_script text;";
+
+    batch_execute(pg_client, wo_objects_columns_sql)
+        .context("failed to add new columns to wo_objects table")?;
+
     let wo_version_sql = "
         create table if not exists wo_migrations (
             pm_id text primary key
         );";
     
-    pg_client.batch_execute(wo_version_sql)?;
+    batch_execute(pg_client, wo_version_sql)?;
 
     return Ok(());
 }
@@ -555,21 +953,76 @@ fn select_db_objects(
 }
 
 
+#[derive(Debug, Clone, Default)]
+pub struct MigrateOptions {
+    // drop and create changed roles instead of altering them
+    pub recreate_roles: bool,
+    // fail if modified table script does not match the live table
+    pub strict: bool,
+    // fail if object scripts do not create objects declared by their filenames
+    pub strict_scripts: bool,
+    // store dependencies found in pg_depend along with text based dependencies
+    pub catalog_dependencies: bool,
+}
+
+/* modified table scripts must match live tables after migrations were executed */
+fn verify_tables(
+    pg_client: &mut postgres::Client,
+    database_project: &DatabaseProject,
+    db_objects: &HashMap<String, DatabaseObject>
+) -> anyhow::Result<()> {
+    let mut table_ids = Vec::from_iter(database_project.objects.keys());
+    table_ids.sort();
+    let mut errors = vec![];
+    for table_id in table_ids {
+        let p_object = &database_project.objects[table_id];
+        if p_object.object_type()? != DatabaseObjectType::Table {
+            continue;
+        }
+        match db_objects.get(table_id) {
+            Some(db_object) if db_object.md5 != p_object.md5 => {},
+            _ => continue,
+        }
+
+        let shapes = diff::select_table_shapes(pg_client, &p_object)
+            .context(format!("verify_tables error: could not compare table {:?}", table_id))?;
+        if let Some((_, mut live_shape, mut script_shape)) = shapes {
+            let tracked_names = diff::get_tracked_names(&database_project.objects, table_id)?;
+            diff::exclude_tracked(&mut live_shape, &tracked_names);
+            diff::exclude_tracked(&mut script_shape, &tracked_names);
+            let differences = diff::describe_table_shapes_diff(&live_shape, &script_shape);
+            if differences.len() > 0 {
+                errors.push(format!("{:?}\n    {}", table_id, differences.join("\n    ")));
+            }
+        }
+    }
+    if errors.len() > 0 {
+        bail!("verify_tables error: table scripts were modified, but tables were not migrated \
+        (wo migration generate <name> can be used to write a migration draft)\n{}", errors.join("\n"));
+    }
+    return Ok(());
+}
+
 fn update_objects(
     pg_client: &mut postgres::Client,
-    database_project: &DatabaseProject
+    database_project: &DatabaseProject,
+    options: &MigrateOptions
 ) -> anyhow::Result<()> {
 
     let db_objects = select_db_objects(pg_client)?;
+
+    if options.strict {
+        verify_tables(pg_client, database_project, &db_objects)?;
+    }
     
     let mut drop_set: HashSet<String> = HashSet::new();
+    let mut replace_set: HashSet<String> = HashSet::new();
+    let mut alter_publications_set: HashSet<String> = HashSet::new();
     let mut dirty_tables_set: HashSet<String> = HashSet::new();
 
     for (db_object_id, db_object) in db_objects.iter() {
         let object_type = db_object.object_type()?;
-        if object_type == DatabaseObjectType::Role {
-            drop_set.insert(db_object_id.clone());
-        } else if !database_project.objects.contains_key(db_object_id) {
+        if !database_project.objects.contains_key(db_object_id) {
             if object_type == DatabaseObjectType::Table {
                 dirty_tables_set.insert(db_object_id.clone());
             } else {
@@ -583,26 +1036,52 @@ fn update_objects(
                         dirty_tables_set.insert(db_object_id.clone());
                     },
                     DatabaseObjectType::Schema => {
-                        println!("schema script has changed but won't be updated, to modify schema you should use migrations {:?}", db_object_id);
+                        output::info(&format!("schema script has changed but won't be updated, to modify schema you should use migrations {:?}", db_object_id));
                         delete_wo_object(pg_client, &db_object_id)?;
                     },
                     DatabaseObjectType::Extension => {
-                        println!("extension script has changed but won't be updated, to modify extesnion you should use migrations {:?}", db_object_id);
+                        output::info(&format!("extension script has changed but won't be updated, to modify extesnion you should use migrations {:?}", db_object_id));
                         delete_wo_object(pg_client, &db_object_id)?;
                     },
                     DatabaseObjectType::Type => {
-                        println!("type script has changed but won't be updated, to modify type you should use migrations {:?}", db_object_id);
+                        output::info(&format!("type script has changed but won't be updated, to modify type you should use migrations {:?}", db_object_id));
                         delete_wo_object(pg_client, &db_object_id)?;
                     },
-                    DatabaseObjectType::Role => unreachable!(),
+                    DatabaseObjectType::Role => {
+                        if options.recreate_roles {
+                            drop_set.insert(db_object_id.clone());
+                        } else {
+                            let alter_result = alter_role(pg_client, &db_object, &p_object);
+                            if let Err(e) = alter_result {
+                                output::warning(&format!("failed to alter role, it will be dropped and created again {:?} {:?}", db_object_id, e));
+                                drop_set.insert(db_object_id.clone());
+                            }
+                        }
+                    },
+                    DatabaseObjectType::Function |
+                    DatabaseObjectType::View => {
+                        if can_replace_object(&db_object, &p_object)? {
+                            replace_set.insert(db_object_id.clone());
+                        } else {
+                            drop_set.insert(db_object_id.clone());
+                        }
+                    },
+                    DatabaseObjectType::Publication => {
+                        alter_publications_set.insert(db_object_id.clone());
+                    },
                     DatabaseObjectType::Trigger |
                     DatabaseObjectType::Constraint |
-                    DatabaseObjectType::Function |
                     DatabaseObjectType::Policy |
-                    DatabaseObjectType::View => {
+                    DatabaseObjectType::EventTrigger |
+                    DatabaseObjectType::Rule |
+                    DatabaseObjectType::ForeignServer |
+                    DatabaseObjectType::UserMapping |
+                    DatabaseObjectType::ForeignTable => {
                         drop_set.insert(db_object_id.clone());
                     }
                 }
+            } else if object_type == DatabaseObjectType::Role {
+                update_role_password(pg_client, &p_object)?;
             }
         }
     }
@@ -621,13 +1100,13 @@ fn update_objects(
 
         let object_type = p_object.object_type()?;
         if object_type == DatabaseObjectType::Schema {
-            println!("schema is missing in wo_objects but exists in database it will be left as it is {:?}", p_object_id);
+            output::info(&format!("schema is missing in wo_objects but exists in database it will be left as it is {:?}", p_object_id));
         } else if object_type == DatabaseObjectType::Table {
-            println!("table is missing in wo_objects but exists in database it will be left as it is {:?}", p_object_id);
+            output::info(&format!("table is missing in wo_objects but exists in database it will be left as it is {:?}", p_object_id));
         } else if object_type == DatabaseObjectType::Extension {
-            println!("extension is missing in wo_objects but exists in database it will be left as it is {:?}", p_object_id);
+            output::info(&format!("extension is missing in wo_objects but exists in database it will be left as it is {:?}", p_object_id));
         } else if object_type == DatabaseObjectType::Type {
-            println!("type is missing in wo_objects but exists in database it will be left as it is {:?}", p_object_id);
+            output::info(&format!("type is missing in wo_objects but exists in database it will be left as it is {:?}", p_object_id));
         } else {
             drop_set.insert(p_object_id.clone());
         }
@@ -645,10 +1124,10 @@ fn update_objects(
             bail!("table was deleted from project, but it still exists in database, \
             it should be dropped manually or using migrations scripts {:?}", dirty_table_id);
         } else if (!exists) && deleted {
-            println!("deleting wo_objects record for table {:?}", dirty_table_id);
+            output::debug(&format!("deleting wo_objects record for table {:?}", dirty_table_id));
             delete_wo_object(pg_client, dirty_table_id)?;
         } else if exists && (!deleted) {
-            println!("table script was modified, overwriting wo_objects record {:?}", dirty_table_id);
+            output::debug(&format!("table script was modified, overwriting wo_objects record {:?}", dirty_table_id));
             let p_object = &database_project.objects[dirty_table_id];
             update_wo_object(pg_client, &p_object)?;
         }
@@ -656,6 +1135,22 @@ fn update_objects(
         // else table will be created in later step
     }
 
+    let create_order = database_project.get_create_order()
+        .context("update_objects error: could not get create order")?;
+
+    // replace in create order so that dependencies are replaced first
+    for object_id in create_order.iter() {
+        if !replace_set.contains(object_id) {
+            continue;
+        }
+        let object = &database_project.objects[object_id];
+        let replace_result = replace_object(pg_client, &object);
+        if let Err(e) = replace_result {
+            output::warning(&format!("failed to replace, object will be dropped and created again {:?} {:?}", object_id, e));
+            drop_set.insert(object_id.clone());
+        }
+    }
+
     let mut dropped: HashSet<String> = HashSet::new();
     let mut drop_list = Vec::from_iter(drop_set.clone());
     let mut last_error: Option<anyhow::Error> = None;
@@ -664,7 +1159,7 @@ fn update_objects(
         
         drop_list.sort();
         for drop_object_id in drop_list {
-            let mut visited: HashSet<String> = HashSet::new();
+            let mut path: Vec<String> = vec![];
             
             let object;
             if db_objects.contains_key(&drop_object_id) {
@@ -672,7 +1167,7 @@ fn update_objects(
             } else if database_project.objects.contains_key(&drop_object_id) {
                 object = &database_project.objects[&drop_object_id];
             } else {
-                println!("failed to drop, missing wo_objects {:?}", drop_object_id);
+                output::warning(&format!("failed to drop, missing wo_objects {:?}", drop_object_id));
                 last_error = Some(anyhow!("failed to drop, missing wo_objects {:?}", drop_object_id));
                 continue;
             }
@@ -683,11 +1178,11 @@ fn update_objects(
                 &database_project,
                 &db_objects, 
                 &mut dropped,
-                &mut visited
+                &mut path
             );
 
             if drop_result.is_err() {
-                println!("failed to drop {:?}", object.id);
+                output::warning(&format!("failed to drop {:?}", object.id));
                 last_error = drop_result.err();
             }
         }
@@ -703,20 +1198,508 @@ fn update_objects(
             }
         }
         if drop_set.len() > 0 {
-            println!("one more drop iteration will be attempted {:?}", drop_list);
+            output::debug(&format!("one more drop iteration will be attempted {:?}", drop_list));
         }
     }
 
-    let create_order = database_project.get_create_order()
-        .context("update_objects error: could not get create order")?;
-
-
     for object_id in create_order.iter() {
+        if alter_publications_set.contains(object_id) {
+            continue;
+        }
         let object = database_project.objects.get(object_id).unwrap();
         create_if_missing(pg_client, &object)
             .context(format!("update_objects error: could not create {:?}", object.id))?;
     }
 
+    // publications are altered after tables they could add are created
+    for object_id in create_order.iter() {
+        if !alter_publications_set.contains(object_id) {
+            continue;
+        }
+        let db_object = &db_objects[object_id];
+        let object = &database_project.objects[object_id];
+        let alter_result = alter_publication(pg_client, db_object, object);
+        if let Err(e) = alter_result {
+            output::warning(&format!("failed to alter publication, it will be dropped and created again {:?} {:?}", object_id, e));
+            drop_object(pg_client, object_id, db_object.drop_script.as_deref())?;
+            create_if_missing(pg_client, object)
+                .context(format!("update_objects error: could not create {:?}", object_id))?;
+        }
+    }
+
+    if options.catalog_dependencies {
+        update_catalog_dependencies(pg_client, &database_project)
+            .context("update_objects error: could not update catalog dependencies")?;
+    }
+
+    // privileges are lost when objects are recreated, so they are compared on every update
+    update_grants(pg_client, &database_project)
+        .context("update_objects error: could not update grants")?;
+
+    // comments are lost when objects are recreated, so they are compared on every update
+    update_comments(pg_client, &database_project)
+        .context("update_objects error: could not update comments")?;
+
+    return Ok(());
+}
+
+/* (catalog, oid) pairs identifying the object in pg_depend, empty for object types which are not tracked */
+fn select_catalog_keys(
+    pg_client: &mut postgres::Client,
+    object_id: &str
+) -> anyhow::Result<Vec<(String, u32)>> {
+    let object_type = project::get_object_type(object_id)?;
+    let sql = match object_type {
+        DatabaseObjectType::Table |
+        DatabaseObjectType::View |
+        DatabaseObjectType::ForeignTable => "
+            select 'pg_class', c.oid
+            from pg_class c
+            join pg_namespace n on n.oid = c.relnamespace
+            where lower(n.nspname) = lower($1)
+            and lower(c.relname) = lower($2);",
+        DatabaseObjectType::Type => "
+            select 'pg_type', t.oid
+            from pg_type t
+            join pg_namespace n on n.oid = t.typnamespace
+            where lower(n.nspname) = lower($1)
+            and lower(t.typname) = lower($2);",
+        DatabaseObjectType::Function => {
+            if let Some(signature) = project::get_signature(object_id)? {
+                let sql = "select 'pg_proc', to_regprocedure($1)::oid where to_regprocedure($1) is not null;";
+                let target = format!("{}.{}({})", project::get_schema(object_id)?, project::get_name(object_id)?, signature);
+                let mut result = vec![];
+                for row in pg_client.query(sql, &[&target])? {
+                    result.push((row.try_get(0)?, row.try_get(1)?));
+                }
+                return Ok(result);
+            }
+            "
+            select 'pg_proc', p.oid
+            from pg_proc p
+            join pg_namespace n on n.oid = p.pronamespace
+            where lower(n.nspname) = lower($1)
+            and lower(p.proname) = lower($2);"
+        },
+        DatabaseObjectType::Trigger => "
+            select 'pg_trigger', t.oid
+            from pg_trigger t
+            join pg_class c on c.oid = t.tgrelid
+            join pg_namespace n on n.oid = c.relnamespace
+            where lower(n.nspname) = lower($1)
+            and lower(c.relname) = lower($3)
+            and lower(t.tgname) = lower($2);",
+        DatabaseObjectType::Constraint => "
+            select 'pg_constraint', co.oid
+            from pg_constraint co
+            join pg_class c on c.oid = co.conrelid
+            join pg_namespace n on n.oid = c.relnamespace
+            where lower(n.nspname) = lower($1)
+            and lower(c.relname) = lower($3)
+            and lower(co.conname) = lower($2);",
+        DatabaseObjectType::Policy => "
+            select 'pg_policy', p.oid
+            from pg_policy p
+            join pg_class c on c.oid = p.polrelid
+            join pg_namespace n on n.oid = c.relnamespace
+            where lower(n.nspname) = lower($1)
+            and lower(c.relname) = lower($3)
+            and lower(p.polname) = lower($2);",
+        DatabaseObjectType::Rule => "
+            select 'pg_rewrite', r.oid
+            from pg_rewrite r
+            join pg_class c on c.oid = r.ev_class
+            join pg_namespace n on n.oid = c.relnamespace
+            where lower(n.nspname) = lower($1)
+            and lower(c.relname) = lower($3)
+            and lower(r.rulename) = lower($2);",
+        _ => return Ok(vec![]),
+    };
+
+    let schema = project::get_schema(object_id)?;
+    let name = project::get_name(object_id)?;
+    let rows = match object_type {
+        DatabaseObjectType::Trigger |
+        DatabaseObjectType::Constraint |
+        DatabaseObjectType::Policy |
+        DatabaseObjectType::Rule => {
+            let table = project::get_table(object_id)?;
+            pg_client.query(sql, &[&schema, &name, &table])?
+        },
+        _ => pg_client.query(sql, &[&schema, &name])?,
+    };
+    let mut result = vec![];
+    for row in rows {
+        result.push((row.try_get(0)?, row.try_get(1)?));
+    }
+    return Ok(result);
+}
+
+/* learns dependencies between tracked objects from pg_depend and stores them along with text based dependencies,
+so that the next update drops dependent objects which were missed by the text search */
+fn update_catalog_dependencies(
+    pg_client: &mut postgres::Client,
+    database_project: &DatabaseProject
+) -> anyhow::Result<()> {
+    let mut object_ids = Vec::from_iter(database_project.objects.keys());
+    object_ids.sort();
+    let mut keys: HashMap<(String, u32), String> = HashMap::new();
+    for object_id in object_ids {
+        for key in select_catalog_keys(pg_client, object_id)? {
+            keys.insert(key, object_id.clone());
+        }
+    }
+
+    // view dependencies are recorded for its rewrite rule, column default, constraint, trigger and policy dependencies
+    // are mapped to their table if they are not tracked as separate objects
+    let sql = "
+        select
+            d.classid::regclass::text,
+            d.objid,
+            case d.classid
+                when 'pg_rewrite'::regclass then (select ev_class from pg_rewrite where oid = d.objid)
+                when 'pg_attrdef'::regclass then (select adrelid from pg_attrdef where oid = d.objid)
+                when 'pg_constraint'::regclass then (select conrelid from pg_constraint where oid = d.objid)
+                when 'pg_trigger'::regclass then (select tgrelid from pg_trigger where oid = d.objid)
+                when 'pg_policy'::regclass then (select polrelid from pg_policy where oid = d.objid)
+            end,
+            d.refclassid::regclass::text,
+            d.refobjid,
+            case d.refclassid
+                when 'pg_type'::regclass then (select nullif(typrelid, 0) from pg_type where oid = d.refobjid)
+            end
+        from pg_depend d
+        where d.deptype = 'n'
+        and d.objid >= 16384
+        and d.refobjid >= 16384;";
+
+    let mut catalog_required_by: HashMap<String, HashSet<String>> = HashMap::new();
+    for row in pg_client.query(sql, &[])? {
+        let class: String = row.try_get(0)?;
+        let oid: u32 = row.try_get(1)?;
+        let parent_oid: Option<u32> = row.try_get(2)?;
+        let ref_class: String = row.try_get(3)?;
+        let ref_oid: u32 = row.try_get(4)?;
+        let ref_table_oid: Option<u32> = row.try_get(5)?;
+
+        let dependent_id = keys.get(&(class, oid))
+            .or(parent_oid.and_then(|oid| keys.get(&("pg_class".into(), oid))));
+        let referenced_id = keys.get(&(ref_class, ref_oid))
+            .or(ref_table_oid.and_then(|oid| keys.get(&("pg_class".into(), oid))));
+
+        if let (Some(dependent_id), Some(referenced_id)) = (dependent_id, referenced_id) {
+            if dependent_id != referenced_id {
+                catalog_required_by.entry(referenced_id.clone()).or_default().insert(dependent_id.clone());
+            }
+        }
+    }
+
+    let mut catalog_depends_on: HashMap<String, HashSet<String>> = HashMap::new();
+    for (referenced_id, dependent_ids) in catalog_required_by.iter() {
+        for dependent_id in dependent_ids {
+            catalog_depends_on.entry(dependent_id.clone()).or_default().insert(referenced_id.clone());
+        }
+    }
+
+    let mut object_ids = Vec::from_iter(database_project.objects.keys());
+    object_ids.sort();
+    for object_id in object_ids {
+        let object = &database_project.objects[object_id];
+        let mut required_by = object.required_by.clone();
+        let mut depends_on = object.depends_on.clone();
+        if let Some(ids) = catalog_required_by.get(object_id) {
+            required_by.extend(ids.iter().cloned());
+        }
+        if let Some(ids) = catalog_depends_on.get(object_id) {
+            depends_on.extend(ids.iter().cloned());
+        }
+        if required_by.len() == object.required_by.len() && depends_on.len() == object.depends_on.len() {
+            continue;
+        }
+        output::debug(&format!("catalog dependencies found {:?}", object_id));
+        let mut required_by_vec = Vec::from_iter(required_by);
+        let mut depends_on_vec = Vec::from_iter(depends_on);
+        required_by_vec.sort();
+        depends_on_vec.sort();
+        let sql = "update wo_objects set po_required_by = $2, po_depends_on = $3 where po_id = $1;";
+        execute(pg_client, sql, &[&object_id, &required_by_vec, &depends_on_vec])?;
+    }
+    return Ok(());
+}
+
+/* current comment of the target, None if the kind of object is not supported */
+fn select_comment(
+    pg_client: &mut postgres::Client,
+    comment: &sql::CommentOn
+) -> anyhow::Result<Option<Option<String>>> {
+    let row = match comment.kind.as_str() {
+        "table" |
+        "view" |
+        "materialized view" |
+        "foreign table" |
+        "index" |
+        "sequence" => {
+            let sql = "select obj_description($1::text::regclass, 'pg_class');";
+            pg_client.query_one(sql, &[&comment.target])?
+        },
+        "column" => {
+            let tokens = sql::tokenize(&comment.target);
+            let dot = tokens.iter().rposition(|t| t.is_symbol("."))
+                .ok_or(anyhow!("column comment target should be <table>.<column> {:?}", comment.target))?;
+            let relation = &comment.target[..tokens[dot].offset];
+            let column = sql::unquote_ident(comment.target[tokens[dot].end()..].trim());
+            let sql = "
+                select col_description(a.attrelid, a.attnum)
+                from pg_attribute a
+                where a.attrelid = $1::text::regclass
+                and a.attname = $2;";
+            pg_client.query_one(sql, &[&relation, &column])?
+        },
+        "function" |
+        "procedure" => {
+            let target = sql::normalize_function_reference(&comment.target);
+            let sql = if target.contains('(') {
+                "select obj_description($1::text::regprocedure, 'pg_proc');"
+            } else {
+                "select obj_description($1::text::regproc, 'pg_proc');"
+            };
+            pg_client.query_one(sql, &[&target])?
+        },
+        "schema" => {
+            let sql = "select obj_description($1::text::regnamespace, 'pg_namespace');";
+            pg_client.query_one(sql, &[&comment.target])?
+        },
+        "type" => {
+            let sql = "select obj_description($1::text::regtype, 'pg_type');";
+            pg_client.query_one(sql, &[&comment.target])?
+        },
+        _ => return Ok(None),
+    };
+    let result: Option<String> = row.try_get(0)?;
+    return Ok(Some(result));
+}
+
+/* applies comments which differ from the ones found in database, unsupported comments are always applied */
+fn update_comments(
+    pg_client: &mut postgres::Client,
+    database_project: &DatabaseProject
+) -> anyhow::Result<()> {
+    for (path_buf, script) in database_project.comment_scripts.iter() {
+        let script = utils::expand_env_vars(&script)?;
+        for statement in sql::split_statements(&script) {
+            let mut action = None;
+            if let Some(comment) = sql::parse_comment_on(&statement) {
+                let current = select_comment(pg_client, &comment)
+                    .context(format!("update_comments error: failed to select comment {:?} {:?}", path_buf, comment.target))?;
+                // value is null or a string literal, values which can not be unquoted are always applied
+                let desired = match comment.value.eq_ignore_ascii_case("null") {
+                    true => Some(None),
+                    false => sql::unquote_literal(&comment.value).map(Some),
+                };
+                if current.is_some() && current == desired {
+                    continue;
+                }
+                let mut comment_action = output::Action::start("comment on", &format!("{} {}", comment.kind, comment.target));
+                comment_action.sql(statement.text);
+                action = Some(comment_action);
+            }
+            let location = output::ScriptLocation { path_buf, script: &script, offset: statement.offset, line_offset: 0 };
+            execute_script(pg_client, statement.text, Some(&location))
+                .context(format!("update_comments error: failed to execute comment script {:?}", path_buf))?;
+            if let Some(comment_action) = action {
+                comment_action.finish();
+            }
+        }
+    }
+    return Ok(());
+}
+
+/* current privileges of the grantee on given object found in acl (including default acl),
+or privileges of the default acl only (privileges of the owner and public on objects without explicit grants) */
+fn select_privileges(
+    pg_client: &mut postgres::Client,
+    kind: &str,
+    target: &str,
+    grantee_oid: u32,
+    default_only: bool
+) -> anyhow::Result<HashSet<String>> {
+    let (catalog, acl, default_kind, owner, cast) = match kind {
+        "table" |
+        "foreign table" |
+        "sequence" => ("pg_class", "c.relacl", "case when c.relkind = 'S' then 's' else 'r' end::\"char\"", "c.relowner", "regclass"),
+        "schema" => ("pg_namespace", "c.nspacl", "'n'", "c.nspowner", "regnamespace"),
+        "type" |
+        "domain" => ("pg_type", "c.typacl", "'T'", "c.typowner", "regtype"),
+        "function" |
+        "procedure" |
+        "routine" => {
+            let cast = if target.contains('(') { "regprocedure" } else { "regproc" };
+            ("pg_proc", "c.proacl", "'f'", "c.proowner", cast)
+        },
+        _ => bail!("select_privileges error: unsupported object kind {:?}", kind),
+    };
+    let default_acl = format!("acldefault({}, {})", default_kind, owner);
+    let acl = match default_only {
+        true => default_acl,
+        false => format!("coalesce({}, {})", acl, default_acl),
+    };
+    let sql = format!("
+        select distinct lower(a.privilege_type)
+        from {} c
+        cross join lateral aclexplode({}) a
+        where c.oid = $1::text::{}
+        and a.grantee = $2;",
+        catalog,
+        acl,
+        cast,
+    );
+
+    let mut result = HashSet::new();
+    let rows = pg_client.query(sql.as_str(), &[&target, &grantee_oid])?;
+    for row in rows {
+        let privilege: String = row.try_get(0)?;
+        result.insert(privilege);
+    }
+    return Ok(result);
+}
+
+fn select_grantee_oid(
+    pg_client: &mut postgres::Client,
+    grantee: &str
+) -> anyhow::Result<u32> {
+    if grantee == "public" {
+        return Ok(0);
+    }
+    let sql = "select oid from pg_roles where rolname = $1;";
+    let row = pg_client.query_opt(sql, &[&grantee])?
+        .ok_or(anyhow!("select_grantee_oid error: role does not exist {:?}", grantee))?;
+    let oid: u32 = row.try_get(0)?;
+    return Ok(oid);
+}
+
+struct GrantTarget {
+    kind: String,
+    target: String,
+    // unquoted grantee -> privileges declared for this target
+    privileges: HashMap<String, HashSet<String>>,
+}
+
+/* adds privileges of grant statement to targets, grantees maps unquoted grantee to its sql text */
+fn add_grant(
+    grant_targets: &mut Vec<GrantTarget>,
+    grantees: &mut HashMap<String, String>,
+    grant: &sql::Grant
+) {
+    let mut privileges: HashSet<String> = HashSet::new();
+    for privilege in grant.privileges.iter() {
+        if privilege == "all" {
+            privileges.extend(sql::get_all_privileges(&grant.kind).iter().map(|p| String::from(*p)));
+        } else {
+            privileges.insert(privilege.clone());
+        }
+    }
+
+    for target in grant.targets.iter() {
+        let target = match grant.kind.as_str() {
+            "function" | "procedure" => sql::normalize_function_reference(target),
+            _ => target.clone(),
+        };
+        let position = grant_targets.iter()
+            .position(|t| t.kind == grant.kind && t.target.to_lowercase() == target.to_lowercase());
+        let grant_target = match position {
+            Some(i) => &mut grant_targets[i],
+            None => {
+                grant_targets.push(GrantTarget {
+                    kind: grant.kind.clone(),
+                    target,
+                    privileges: HashMap::new(),
+                });
+                grant_targets.last_mut().unwrap()
+            }
+        };
+        for grantee in grant.grantees.iter() {
+            let grantee_name = sql::unquote_ident(grantee);
+            grantees.insert(grantee_name.clone(), grantee.clone());
+            grant_target.privileges.entry(grantee_name)
+                .or_default()
+                .extend(privileges.clone());
+        }
+    }
+}
+
+/* (missing, extra) privileges, default privileges (of owner and public) are never revoked */
+fn get_grant_changes(
+    desired: &HashSet<String>,
+    current: &HashSet<String>,
+    defaults: &HashSet<String>
+) -> (Vec<String>, Vec<String>) {
+    let mut missing = Vec::from_iter(desired.sub(current));
+    let mut extra = Vec::from_iter(current.sub(desired).sub(defaults));
+    missing.sort();
+    extra.sort();
+    return (missing, extra);
+}
+
+/* applies grants and revokes needed to match privileges declared in grants scripts,
+only grantees declared for an object are managed on that object */
+fn update_grants(
+    pg_client: &mut postgres::Client,
+    database_project: &DatabaseProject
+) -> anyhow::Result<()> {
+    let mut grant_targets: Vec<GrantTarget> = vec![];
+    let mut grantees: HashMap<String, String> = HashMap::new();
+
+    for (path_buf, script) in database_project.grant_scripts.iter() {
+        let script = utils::expand_env_vars(&script)?;
+        for statement in sql::split_statements(&script) {
+            match sql::parse_grant(&statement) {
+                Some(grant) => add_grant(&mut grant_targets, &mut grantees, &grant),
+                // grant scripts contain only grant and revoke statements, checked when the project is loaded
+                None => {
+                    let location = output::ScriptLocation { path_buf, script: &script, offset: statement.offset, line_offset: 0 };
+                    execute_script(pg_client, statement.text, Some(&location))
+                        .context(format!("update_grants error: failed to execute grants script {:?}", path_buf))?;
+                }
+            }
+        }
+    }
+
+    let mut grantee_oids: HashMap<String, u32> = HashMap::new();
+    for grant_target in grant_targets.iter() {
+        let mut target_grantees = Vec::from_iter(grant_target.privileges.keys());
+        target_grantees.sort();
+        for grantee in target_grantees {
+            let grantee_oid = match grantee_oids.get(grantee) {
+                Some(oid) => *oid,
+                None => {
+                    let oid = select_grantee_oid(pg_client, grantee)?;
+                    grantee_oids.insert(grantee.clone(), oid);
+                    oid
+                }
+            };
+            let current = select_privileges(pg_client, &grant_target.kind, &grant_target.target, grantee_oid, false)
+                .context(format!("update_grants error: failed to select privileges {} {}", grant_target.kind, grant_target.target))?;
+            let defaults = select_privileges(pg_client, &grant_target.kind, &grant_target.target, grantee_oid, true)
+                .context(format!("update_grants error: failed to select default privileges {} {}", grant_target.kind, grant_target.target))?;
+            let (missing, extra) = get_grant_changes(&grant_target.privileges[grantee], &current, &defaults);
+
+            let grantee_sql = &grantees[grantee];
+            if missing.len() > 0 {
+                let sql = format!("grant {} on {} {} to {};", missing.join(", "), grant_target.kind, grant_target.target, grantee_sql);
+                let mut action = output::Action::start("grant", &format!("{} {}", grant_target.kind, grant_target.target));
+                action.sql(&sql);
+                batch_execute(pg_client, &sql)?;
+                action.finish();
+            }
+            if extra.len() > 0 {
+                let sql = format!("revoke {} on {} {} from {};", extra.join(", "), grant_target.kind, grant_target.target, grantee_sql);
+                let mut action = output::Action::start("revoke", &format!("{} {}", grant_target.kind, grant_target.target));
+                action.sql(&sql);
+                batch_execute(pg_client, &sql)?;
+                action.finish();
+            }
+        }
+    }
     return Ok(());
 }
 
@@ -728,7 +1711,7 @@ fn insert_wo_migration(
         insert into wo_migrations (pm_id)
         select $1
         on conflict (pm_id) do nothing;";
-    pg_client.execute(sql, &[&migration])?;
+    execute(pg_client, sql, &[&migration])?;
     return Ok(());
 }
 
@@ -741,14 +1724,23 @@ fn get_db_last_migration(pg_client: &mut postgres::Client) -> anyhow::Result<Opt
 }
 
 
-pub fn migrate(database_project: DatabaseProject) -> anyhow::Result<()> {
+pub fn migrate(database_project: DatabaseProject, options: &MigrateOptions) -> anyhow::Result<()> {
+
+    let script_problems = database_project.validate_scripts()
+        .context("migrate error: could not validate object scripts")?;
+    if options.strict_scripts && script_problems.len() > 0 {
+        bail!("migrate error: object scripts do not match their filenames\n{}", script_problems.join("\n"));
+    }
+    for problem in script_problems.iter() {
+        output::warning(problem);
+    }
 
     let project_last_migration_opt = database_project.migration_scripts.last();
     let pg_client_result = get_pg_client();
     
     match pg_client_result {
         Err(_) => {
-            println!("database was not found, will attempt to create a fresh one and create all database objects");
+            output::info("database was not found, will attempt to create a fresh one and create all database objects");
             
             let mut admin_pg_client = get_admin_pg_client()
                 .context("migrate error: could not connect to database neither using WO_CONNECTION_STRING nor WO_ADMIN_CONNECTION_STRING")?;
@@ -766,7 +1758,7 @@ pub fn migrate(database_project: DatabaseProject) -> anyhow::Result<()> {
             create_wo_tables(&mut pg_client)
                 .context("migrate error: could not create wo tables in new database")?;
 
-            update_objects(&mut pg_client, &database_project)
+            update_objects(&mut pg_client, &database_project, options)
                 .context("migrate error: failed to create database objects in new database")?;
 
             if let Some((project_last_migration, _)) = project_last_migration_opt {
@@ -791,13 +1783,24 @@ pub fn migrate(database_project: DatabaseProject) -> anyhow::Result<()> {
                         if let Some((next_migration_id, next_migration_script)) 
                             = database_project.get_next_migration(&db_last_migration_current) 
                         {
-                            println!("execute migration script {:?}", next_migration_id);
-                            pg_client.batch_execute(&next_migration_script)
+                            let mut action = output::Action::start("execute migration script", &next_migration_id);
+                            let script = utils::expand_env_vars(&next_migration_script)
+                                .context(format!("migrate error: failed to substitute migration script {:?}", next_migration_id))?;
+                            action.sql(&script);
+                            let path_buf = database_project.project_dirpath.join("migrations").join(&next_migration_id);
+                            let location = output::ScriptLocation {
+                                path_buf: &path_buf,
+                                script: &script,
+                                offset: 0,
+                                line_offset: 0,
+                            };
+                            execute_script(&mut pg_client, &script, Some(&location))
                                 .context(format!("migrate error: failed to execute migration script {:?}", next_migration_id))?;
                             
                             insert_wo_migration(&mut pg_client, &next_migration_id)
                                 .context(format!("migrate error: failed to mark migration as executed, you should insert \
                                     migration into wo_migrations manually to fix possible issues {:?}", next_migration_id))?;
+                            action.finish();
 
                             db_last_migration_current = get_db_last_migration(&mut pg_client)?
                                 .ok_or(anyhow!("migrate error: failed to select latest migration after executing migration script {:?}", next_migration_id))?;
@@ -806,12 +1809,12 @@ pub fn migrate(database_project: DatabaseProject) -> anyhow::Result<()> {
                             break;
                         }
                     }
-                    update_objects(&mut pg_client, &database_project)
+                    update_objects(&mut pg_client, &database_project, options)
                         .context("migrate error: failed to update database objects")?;
                 },
                 None => {
-                    println!("database has no initial migration, last migration found in wo project will be marked as executed.");
-                    update_objects(&mut pg_client, &database_project)
+                    output::info("database has no initial migration, last migration found in wo project will be marked as executed.");
+                    update_objects(&mut pg_client, &database_project, options)
                         .context("migrate error: failed to update database objects after no initial migration was found")?;
 
                     if let Some((project_last_migration, _)) = project_last_migration_opt {
@@ -829,6 +1832,54 @@ pub fn migrate(database_project: DatabaseProject) -> anyhow::Result<()> {
 }
 
 
+/* writes alter statements for tables whose scripts differ from the live tables into a new migration script */
+pub fn generate_migration(database_project: DatabaseProject, name: &str) -> anyhow::Result<()> {
+    if name.len() == 0 || !name.chars().all(|c| c.is_alphanumeric() || c == '_' || c == '-') {
+        bail!("generate_migration error: migration name can only have alphanumeric characters, underscores and dashes {:?}", name);
+    }
+
+    let mut pg_client = get_pg_client()
+        .context("generate_migration error: could not connect to database using WO_CONNECTION_STRING")?;
+    let create_order = database_project.get_create_order()
+        .context("generate_migration error: could not get create order")?;
+
+    let mut script = String::new();
+    for object_id in create_order.iter() {
+        let object = &database_project.objects[object_id];
+        // all tables are compared, because wo_objects record is overwritten by migrate even if table was not altered
+        if object.object_type()? != DatabaseObjectType::Table {
+            continue;
+        }
+
+        let shapes = diff::select_table_shapes(&mut pg_client, &object)
+            .context(format!("generate_migration error: could not compare table {:?}", object_id))?;
+        if let Some((table, mut live_shape, mut script_shape)) = shapes {
+            let tracked_names = diff::get_tracked_names(&database_project.objects, object_id)?;
+            diff::exclude_tracked(&mut live_shape, &tracked_names);
+            diff::exclude_tracked(&mut script_shape, &tracked_names);
+            let statements = diff::diff_table_shapes(&table, &live_shape, &script_shape);
+            if statements.len() > 0 {
+                output::info(&format!("table differs from script {:?}", object_id));
+                script.push_str(&format!("-- {}\n{}\n\n", object_id, statements.join("\n")));
+            }
+        }
+    }
+
+    if script.len() == 0 {
+        output::info("no table changes found, migration was not generated");
+        return Ok(());
+    }
+
+    let filename = format!("{}_{}.sql", utils::get_timestamp()?, name);
+    let path_buf = database_project.project_dirpath.join("migrations").join(filename);
+    let script = format!("-- draft generated by wo migration generate, review before applying\n\n{}", script);
+    utils::write_file(&path_buf, &script)?;
+    output::info(&format!("migration draft was written to {:?}", path_buf));
+    output::change();
+    return Ok(());
+}
+
+
 pub fn drop(database_project: DatabaseProject) -> anyhow::Result<()> {
 
     let mut pg_admin_client = get_admin_pg_client()
@@ -869,11 +1920,14 @@ pub fn drop(database_project: DatabaseProject) -> anyhow::Result<()> {
     }
 
     for (path_buf, script) in database_project.drop_scripts {
-        println!("drop database: executing {:?}", path_buf);
-        let prepared_script = prepare_admin_script(&script)
+        let mut action = output::Action::start("drop database", &path_buf.to_string_lossy());
+        let prepared_script = prepare_admin_script(&script, &database_project.variables)
             .context(format!("drop error: failed to prepare drop script {:?}", path_buf))?;
-        pg_admin_client.batch_execute(&prepared_script)
+        action.sql(&prepared_script);
+        let location = output::ScriptLocation { path_buf: &path_buf, script: &prepared_script, offset: 0, line_offset: 0 };
+        execute_script(&mut pg_admin_client, &prepared_script, Some(&location))
             .context(format!("drop error: failed to execute drop script: {:?}", path_buf))?;
+        action.finish();
     }
 
 
@@ -884,7 +1938,7 @@ pub fn drop(database_project: DatabaseProject) -> anyhow::Result<()> {
             let role_exists = exists_object(&mut pg_admin_client, p_object_id)
                 .context(format!("drop error: failed to check if role exists {:?}", p_object_id))?;
             if role_exists {
-                println!("role still exists after executing all drop scripts, drop it manually or remove it from the project {:?}", p_object_id);
+                output::warning(&format!("role still exists after executing all drop scripts, drop it manually or remove it from the project {:?}", p_object_id));
             }
         }
     }
@@ -0,0 +1,93 @@
+use anyhow;
+use super::*;
+use crate::secrets;
+
+
+#[test]
+fn test_render_admin_script() -> anyhow::Result<()> {
+    let variables = template::Variables::new();
+    let admin_parameters = [
+        ("database_name", Some(String::from("my-db"))),
+        ("role_name", Some(String::from("Owner"))),
+        ("password", Some(String::from("p@ss'word\\"))),
+    ];
+
+    let script = "create role {role_name:ident} login password {password:literal};";
+    assert_eq!(render_admin_script(script, &variables, &admin_parameters)?,
+        "create role \"Owner\" login password E'p@ss''word\\\\';");
+    let script = "create database {database_name:ident} owner = {role_name:ident};";
+    assert_eq!(render_admin_script(script, &variables, &admin_parameters)?,
+        "create database \"my-db\" owner = \"Owner\";");
+
+    // unquoted parameters are validated
+    assert_eq!(render_admin_script("create database \"{database_name}\";", &variables, &admin_parameters).is_err(), true);
+    assert_eq!(render_admin_script("create role r password '{password}';", &variables, &admin_parameters).is_err(), true);
+    assert_eq!(render_admin_script("create role \"{role_name}\";", &variables, &admin_parameters)?, "create role \"Owner\";");
+
+    let admin_parameters = [("password", None)];
+    assert_eq!(render_admin_script("create role r password {password:literal};", &variables, &admin_parameters).is_err(), true);
+    assert_eq!(render_admin_script("create role r;", &variables, &admin_parameters)?, "create role r;");
+
+    // secrets and env variables are substituted too
+    std::env::set_var("WO_TEST_ADMIN_PASSWORD", "s3cr'et");
+    std::env::set_var("WO_TEST_ADMIN_OWNER", "app owner");
+    let mut sources = HashMap::new();
+    sources.insert(String::from("admin_password"), secrets::Source::Env("WO_TEST_ADMIN_PASSWORD".into()));
+    secrets::set_sources(sources);
+    let script = "create role {env:WO_TEST_ADMIN_OWNER:ident} login password {secret:admin_password:literal};";
+    assert_eq!(render_admin_script(script, &variables, &admin_parameters)?,
+        "create role \"app owner\" login password 's3cr''et';");
+    return Ok(());
+}
+
+#[test]
+fn test_get_publication_reset_options() -> anyhow::Result<()> {
+    let old_options = Some("publish = 'insert', publish_via_partition_root = true");
+    assert_eq!(get_publication_reset_options(old_options, None), vec![
+        String::from("publish = 'insert, update, delete, truncate'"),
+        String::from("publish_via_partition_root = false"),
+    ]);
+    assert_eq!(get_publication_reset_options(old_options, Some("publish = 'insert, update'")), vec![
+        String::from("publish_via_partition_root = false"),
+    ]);
+    assert_eq!(get_publication_reset_options(None, Some("publish = 'insert'")).len(), 0);
+    assert_eq!(get_publication_reset_options(None, None).len(), 0);
+    return Ok(());
+}
+
+#[test]
+fn test_update_grants_plan() -> anyhow::Result<()> {
+    let script = "
+        grant select on table_a to reader;
+        grant select, insert on table_b to writer;
+        grant all on table_a to \"Owner\";
+    ";
+    let mut grant_targets: Vec<GrantTarget> = vec![];
+    let mut grantees: HashMap<String, String> = HashMap::new();
+    for statement in sql::split_statements(script) {
+        add_grant(&mut grant_targets, &mut grantees, &sql::parse_grant(&statement).unwrap());
+    }
+    assert_eq!(grant_targets.len(), 2);
+    assert_eq!(grantees["Owner"], "\"Owner\"");
+
+    // reader and owner are declared only on table_a, so their privileges on table_b (e.g. owner defaults) are not managed
+    let table_a = grant_targets.iter().find(|t| t.target == "table_a").unwrap();
+    let table_b = grant_targets.iter().find(|t| t.target == "table_b").unwrap();
+    let mut table_a_grantees = Vec::from_iter(table_a.privileges.keys().cloned());
+    table_a_grantees.sort();
+    assert_eq!(table_a_grantees, vec![String::from("Owner"), String::from("reader")]);
+    assert_eq!(Vec::from_iter(table_b.privileges.keys().cloned()), vec![String::from("writer")]);
+    assert_eq!(table_a.privileges["Owner"].len(), 7);
+
+    // default privileges of the owner are kept even if they are not declared
+    let owner_defaults: HashSet<String> = sql::get_all_privileges("table").iter().map(|p| String::from(*p)).collect();
+    let desired: HashSet<String> = vec![String::from("select")].into_iter().collect();
+    let (missing, extra) = get_grant_changes(&desired, &owner_defaults, &owner_defaults);
+    assert_eq!((missing.len(), extra.len()), (0, 0));
+
+    let current: HashSet<String> = vec![String::from("insert"), String::from("update")].into_iter().collect();
+    let (missing, extra) = get_grant_changes(&desired, &current, &HashSet::new());
+    assert_eq!(missing, vec![String::from("select")]);
+    assert_eq!(extra, vec![String::from("insert"), String::from("update")]);
+    return Ok(());
+}
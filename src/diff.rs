@@ -0,0 +1,319 @@
+use std::collections::HashMap;
+use anyhow;
+use anyhow::Context;
+use postgres;
+use postgres::GenericClient;
+use crate::project::DatabaseObject;
+use crate::project::DatabaseObjectType;
+use crate::project;
+use crate::database;
+use crate::utils;
+use crate::sql;
+
+#[cfg(test)]
+mod tests;
+
+
+// temporary schema for building table scripts, it is always rolled back
+const SCRATCH_SCHEMA: &str = "wo_scratch";
+
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Column {
+    pub name: String,
+    pub data_type: String,
+    pub default: Option<String>,
+    pub not_null: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct TableShape {
+    pub columns: Vec<Column>,
+    // (name, definition) pairs of check, primary key, unique and exclusion constraints
+    pub constraints: Vec<(String, String)>,
+    // (name, definition) pairs of indexes not backing constraints
+    pub indexes: Vec<(String, String)>,
+}
+
+fn select_table_oid(
+    client: &mut impl GenericClient,
+    schema: &str,
+    name: &str
+) -> anyhow::Result<Option<(u32, String, String)>> {
+    let sql = "
+        select c.oid, quote_ident(n.nspname), c.oid::regclass::text
+        from pg_class c
+        join pg_namespace n on n.oid = c.relnamespace
+        where lower(n.nspname) = lower($1)
+        and lower(c.relname) = lower($2)
+        and c.relkind in ('r', 'p');";
+    let row = client.query_opt(sql, &[&schema, &name])?;
+    if let Some(row) = row {
+        return Ok(Some((row.try_get(0)?, row.try_get(1)?, row.try_get(2)?)));
+    }
+    return Ok(None);
+}
+
+/* columns, constraints and indexes of the table, expects search_path to be pg_catalog so that all names are qualified */
+fn select_table_shape(
+    client: &mut impl GenericClient,
+    oid: u32
+) -> anyhow::Result<TableShape> {
+    let mut result = TableShape::default();
+
+    let sql = "
+        select quote_ident(a.attname), format_type(a.atttypid, a.atttypmod), pg_get_expr(d.adbin, d.adrelid), a.attnotnull
+        from pg_attribute a
+        left join pg_attrdef d on d.adrelid = a.attrelid and d.adnum = a.attnum
+        where a.attrelid = $1
+        and a.attnum > 0
+        and not a.attisdropped
+        order by a.attnum;";
+    for row in client.query(sql, &[&oid])? {
+        result.columns.push(Column {
+            name: row.try_get(0)?,
+            data_type: row.try_get(1)?,
+            default: row.try_get(2)?,
+            not_null: row.try_get(3)?,
+        });
+    }
+
+    let sql = "
+        select quote_ident(conname), pg_get_constraintdef(oid)
+        from pg_constraint
+        where conrelid = $1
+        and contype in ('c', 'p', 'u', 'x')
+        order by conname;";
+    for row in client.query(sql, &[&oid])? {
+        result.constraints.push((row.try_get(0)?, row.try_get(1)?));
+    }
+
+    let sql = "
+        select quote_ident(c.relname), pg_get_indexdef(i.indexrelid)
+        from pg_index i
+        join pg_class c on c.oid = i.indexrelid
+        where i.indrelid = $1
+        and not exists (select from pg_constraint where conindid = i.indexrelid)
+        order by c.relname;";
+    for row in client.query(sql, &[&oid])? {
+        result.indexes.push((row.try_get(0)?, row.try_get(1)?));
+    }
+    return Ok(result);
+}
+
+/* points qualified references of the table to the scratch schema */
+fn rewrite_table_script(script: &str, schema: &str, name: &str) -> String {
+    let tokens = sql::tokenize(script);
+    let mut result = String::new();
+    let mut copied = 0;
+    for window in tokens.windows(3) {
+        let is_reference = window[0].is_identifier()
+            && window[1].is_symbol(".")
+            && window[2].is_identifier()
+            && sql::unquote_ident(window[0].text).to_lowercase() == schema
+            && sql::unquote_ident(window[2].text).to_lowercase() == name;
+        if is_reference {
+            result.push_str(&script[copied..window[0].offset]);
+            result.push_str(SCRATCH_SCHEMA);
+            copied = window[0].end();
+        }
+    }
+    result.push_str(&script[copied..]);
+    return result;
+}
+
+fn replace_scratch_schema(text: &str, schema: &str) -> String {
+    return text.replace(&format!("{}.", SCRATCH_SCHEMA), &format!("{}.", schema));
+}
+
+/* live table shape and the shape of the table built from the script, None if the table does not exist */
+pub fn select_table_shapes(
+    pg_client: &mut postgres::Client,
+    object: &DatabaseObject
+) -> anyhow::Result<Option<(String, TableShape, TableShape)>> {
+    let schema = project::get_schema(&object.id)?;
+    let name = project::get_name(&object.id)?;
+    let script = utils::expand_env_vars(&object.script)?;
+    let script = rewrite_table_script(&script, schema, name);
+
+    let mut transaction = pg_client.transaction()?;
+    let search_path: String = transaction.query_one("select current_setting('search_path');", &[])?.try_get(0)?;
+    database::batch_execute(&mut transaction, "set local search_path to pg_catalog;")?;
+
+    let (live_oid, live_schema, table) = match select_table_oid(&mut transaction, schema, name)? {
+        Some(t) => t,
+        None => return Ok(None),
+    };
+    let live_shape = select_table_shape(&mut transaction, live_oid)?;
+
+    database::batch_execute(&mut transaction, &format!("create schema {0}; set local search_path to {0}, {1};", SCRATCH_SCHEMA, search_path))?;
+    database::batch_execute(&mut transaction, &script)
+        .context(format!("select_table_shapes error: failed to build table script in scratch schema {:?}", object.id))?;
+    database::batch_execute(&mut transaction, "set local search_path to pg_catalog;")?;
+
+    let (scratch_oid, _, _) = select_table_oid(&mut transaction, SCRATCH_SCHEMA, name)?
+        .ok_or(anyhow!("select_table_shapes error: table script did not create table {:?}", object.id))?;
+    let mut script_shape = select_table_shape(&mut transaction, scratch_oid)?;
+    transaction.rollback()?;
+
+    for column in script_shape.columns.iter_mut() {
+        column.default = column.default.as_ref().map(|d| replace_scratch_schema(d, &live_schema));
+    }
+    for (_, definition) in script_shape.constraints.iter_mut().chain(script_shape.indexes.iter_mut()) {
+        *definition = replace_scratch_schema(definition, &live_schema);
+    }
+    return Ok(Some((table, live_shape, script_shape)));
+}
+
+/* names of constraints of the table declared as separate constraint objects (lowercase, unquoted) */
+pub fn get_tracked_names(objects: &HashMap<String, DatabaseObject>, table_id: &str) -> anyhow::Result<Vec<String>> {
+    let schema = project::get_schema(table_id)?;
+    let table = project::get_name(table_id)?;
+    let mut result = vec![];
+    for object_id in objects.keys() {
+        if project::get_object_type(object_id)? != DatabaseObjectType::Constraint {
+            continue;
+        }
+        if project::get_schema(object_id)? == schema && project::get_table(object_id)? == table {
+            result.push(String::from(project::get_name(object_id)?));
+        }
+    }
+    return Ok(result);
+}
+
+/* constraints and indexes managed by their own project objects are not part of the table script */
+pub fn exclude_tracked(shape: &mut TableShape, tracked_names: &[String]) {
+    let is_untracked = |(name, _): &(String, String)| !tracked_names.contains(&sql::unquote_ident(name).to_lowercase());
+    shape.constraints.retain(is_untracked);
+    shape.indexes.retain(is_untracked);
+}
+
+fn find_definition<'a>(definitions: &'a [(String, String)], name: &str) -> Option<&'a String> {
+    return definitions.iter().find(|(n, _)| n == name).map(|(_, d)| d);
+}
+
+/* alter statements which change the live table into the table defined by the script */
+pub fn diff_table_shapes(table: &str, live: &TableShape, script: &TableShape) -> Vec<String> {
+    let mut result = vec![];
+    let schema_prefix = match table.rfind('.') {
+        Some(i) => &table[..i + 1],
+        None => "",
+    };
+
+    for (name, definition) in live.constraints.iter() {
+        if find_definition(&script.constraints, name) != Some(definition) {
+            result.push(format!("alter table {} drop constraint {};", table, name));
+        }
+    }
+    for (name, definition) in live.indexes.iter() {
+        if find_definition(&script.indexes, name) != Some(definition) {
+            result.push(format!("drop index {}{};", schema_prefix, name));
+        }
+    }
+
+    for column in script.columns.iter() {
+        let live_column = live.columns.iter().find(|c| c.name == column.name);
+        let live_column = match live_column {
+            Some(c) => c,
+            None => {
+                let mut sql = format!("alter table {} add column {} {}", table, column.name, column.data_type);
+                if let Some(default) = &column.default {
+                    sql = format!("{} default {}", sql, default);
+                }
+                if column.not_null {
+                    sql = format!("{} not null", sql);
+                }
+                result.push(format!("{};", sql));
+                continue;
+            }
+        };
+        if live_column.data_type != column.data_type {
+            result.push(format!(
+                "alter table {0} alter column {1} type {2} using {1}::{2};",
+                table, column.name, column.data_type
+            ));
+        }
+        if live_column.default != column.default {
+            match &column.default {
+                Some(default) => result.push(format!("alter table {} alter column {} set default {};", table, column.name, default)),
+                None => result.push(format!("alter table {} alter column {} drop default;", table, column.name)),
+            }
+        }
+        if live_column.not_null != column.not_null {
+            if column.not_null {
+                result.push(format!("alter table {} alter column {} set not null;", table, column.name));
+            } else {
+                result.push(format!("alter table {} alter column {} drop not null;", table, column.name));
+            }
+        }
+    }
+    for column in live.columns.iter() {
+        if !script.columns.iter().any(|c| c.name == column.name) {
+            result.push(format!("alter table {} drop column {};", table, column.name));
+        }
+    }
+
+    for (name, definition) in script.constraints.iter() {
+        if find_definition(&live.constraints, name) != Some(definition) {
+            result.push(format!("alter table {} add constraint {} {};", table, name, definition));
+        }
+    }
+    for (name, definition) in script.indexes.iter() {
+        if find_definition(&live.indexes, name) != Some(definition) {
+            result.push(format!("{};", definition));
+        }
+    }
+    return result;
+}
+
+fn describe_definitions(kind: &str, live: &[(String, String)], script: &[(String, String)], result: &mut Vec<String>) {
+    for (name, definition) in script.iter() {
+        match find_definition(live, name) {
+            None => result.push(format!("{} {}: missing in database", kind, name)),
+            Some(live_definition) if live_definition != definition => {
+                result.push(format!("{} {}: {:?} in database, {:?} in script", kind, name, live_definition, definition));
+            },
+            _ => {},
+        }
+    }
+    for (name, _) in live.iter() {
+        if find_definition(script, name).is_none() {
+            result.push(format!("{} {}: missing in script", kind, name));
+        }
+    }
+}
+
+/* human readable differences between the live table and the table defined by the script */
+pub fn describe_table_shapes_diff(live: &TableShape, script: &TableShape) -> Vec<String> {
+    let mut result = vec![];
+    let nullability = |not_null: bool| if not_null { "not null" } else { "nullable" };
+    let default = |default: &Option<String>| default.clone().unwrap_or("no default".into());
+
+    for column in script.columns.iter() {
+        let live_column = match live.columns.iter().find(|c| c.name == column.name) {
+            Some(c) => c,
+            None => {
+                result.push(format!("column {}: missing in database", column.name));
+                continue;
+            }
+        };
+        if live_column.data_type != column.data_type {
+            result.push(format!("column {}: type {} in database, {} in script", column.name, live_column.data_type, column.data_type));
+        }
+        if live_column.default != column.default {
+            result.push(format!("column {}: {} in database, {} in script", column.name, default(&live_column.default), default(&column.default)));
+        }
+        if live_column.not_null != column.not_null {
+            result.push(format!("column {}: {} in database, {} in script", column.name, nullability(live_column.not_null), nullability(column.not_null)));
+        }
+    }
+    for column in live.columns.iter() {
+        if !script.columns.iter().any(|c| c.name == column.name) {
+            result.push(format!("column {}: missing in script", column.name));
+        }
+    }
+
+    describe_definitions("constraint", &live.constraints, &script.constraints, &mut result);
+    describe_definitions("index", &live.indexes, &script.indexes, &mut result);
+    return result;
+}
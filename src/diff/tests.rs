@@ -0,0 +1,117 @@
+use anyhow;
+use super::*;
+
+
+fn column(name: &str, data_type: &str, default: Option<&str>, not_null: bool) -> Column {
+    return Column {
+        name: name.into(),
+        data_type: data_type.into(),
+        default: default.map(|d| d.into()),
+        not_null,
+    };
+}
+
+
+#[test]
+fn test_rewrite_table_script() -> anyhow::Result<()> {
+    let script = "create table public.t0 (id int);\ncreate index t0_idx on \"public\".\"t0\" (id);\ncomment on table t0 is 'public.t0';";
+    assert_eq!(
+        rewrite_table_script(script, "public", "t0"),
+        "create table wo_scratch.t0 (id int);\ncreate index t0_idx on wo_scratch.\"t0\" (id);\ncomment on table t0 is 'public.t0';"
+    );
+    assert_eq!(rewrite_table_script("create table s.t01 (id int);", "s", "t0"), "create table s.t01 (id int);");
+    return Ok(());
+}
+
+#[test]
+fn test_diff_table_shapes() -> anyhow::Result<()> {
+    let live = TableShape {
+        columns: vec![
+            column("id", "integer", Some("nextval('public.t0_id_seq'::regclass)"), true),
+            column("name", "text", None, false),
+            column("old", "text", None, false),
+        ],
+        constraints: vec![
+            ("t0_pkey".into(), "PRIMARY KEY (id)".into()),
+            ("t0_name_check".into(), "CHECK ((length(name) > 0))".into()),
+        ],
+        indexes: vec![
+            ("t0_name_idx".into(), "CREATE INDEX t0_name_idx ON public.t0 USING btree (name)".into()),
+        ],
+    };
+    let script = TableShape {
+        columns: vec![
+            column("id", "bigint", Some("nextval('public.t0_id_seq'::regclass)"), true),
+            column("name", "text", Some("''::text"), true),
+            column("created", "timestamp with time zone", Some("now()"), true),
+        ],
+        constraints: vec![
+            ("t0_pkey".into(), "PRIMARY KEY (id)".into()),
+        ],
+        indexes: vec![
+            ("t0_name_idx".into(), "CREATE UNIQUE INDEX t0_name_idx ON public.t0 USING btree (name)".into()),
+        ],
+    };
+
+    assert_eq!(diff_table_shapes("public.t0", &live, &script), vec![
+        "alter table public.t0 drop constraint t0_name_check;",
+        "drop index public.t0_name_idx;",
+        "alter table public.t0 alter column id type bigint using id::bigint;",
+        "alter table public.t0 alter column name set default ''::text;",
+        "alter table public.t0 alter column name set not null;",
+        "alter table public.t0 add column created timestamp with time zone default now() not null;",
+        "alter table public.t0 drop column old;",
+        "CREATE UNIQUE INDEX t0_name_idx ON public.t0 USING btree (name);",
+    ]);
+    assert_eq!(diff_table_shapes("public.t0", &live, &live).len(), 0);
+
+    // table script omits the constraint owned by a constraint object
+    let script = live.clone();
+    let mut live = live.clone();
+    live.constraints.push(("t0_created_check".into(), "CHECK ((created > '2000-01-01'::date))".into()));
+    assert_eq!(diff_table_shapes("public.t0", &live, &script), vec!["alter table public.t0 drop constraint t0_created_check;"]);
+    exclude_tracked(&mut live, &[String::from("t0_created_check")]);
+    assert_eq!(diff_table_shapes("public.t0", &live, &script).len(), 0);
+    return Ok(());
+}
+
+#[test]
+fn test_describe_table_shapes_diff() -> anyhow::Result<()> {
+    let live = TableShape {
+        columns: vec![
+            column("id", "integer", None, true),
+            column("old", "text", None, false),
+        ],
+        constraints: vec![("t0_pkey".into(), "PRIMARY KEY (id)".into())],
+        indexes: vec![],
+    };
+    let script = TableShape {
+        columns: vec![
+            column("id", "bigint", Some("0"), false),
+            column("name", "text", None, false),
+        ],
+        constraints: vec![],
+        indexes: vec![("t0_name_idx".into(), "CREATE INDEX t0_name_idx ON public.t0 USING btree (name)".into())],
+    };
+
+    assert_eq!(describe_table_shapes_diff(&live, &script), vec![
+        "column id: type integer in database, bigint in script",
+        "column id: no default in database, 0 in script",
+        "column id: not null in database, nullable in script",
+        "column name: missing in database",
+        "column old: missing in script",
+        "constraint t0_pkey: missing in script",
+        "index t0_name_idx: missing in database",
+    ]);
+    assert_eq!(describe_table_shapes_diff(&live, &live).len(), 0);
+
+
+    // constraint declared as a constraint object is missing in the table script
+    let script = live.clone();
+    let mut live = live.clone();
+    live.constraints.push(("\"T0_Check\"".into(), "CHECK (true)".into()));
+    assert_eq!(describe_table_shapes_diff(&live, &script), vec!["constraint \"T0_Check\": missing in script"]);
+    exclude_tracked(&mut live, &[String::from("t0_check")]);
+    assert_eq!(describe_table_shapes_diff(&live, &script).len(), 0);
+    return Ok(());
+}
@@ -0,0 +1,358 @@
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::collections::VecDeque;
+use std::str::FromStr;
+use anyhow;
+use serde_json;
+use crate::project::DatabaseProject;
+use crate::project::DatabaseObject;
+use crate::project::DatabaseObjectType;
+use crate::project;
+
+#[cfg(test)]
+mod tests;
+
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Edge {
+    pub from: String,
+    pub to: String,
+    pub reasons: Vec<String>,
+}
+
+/* node ids matching type and schema filters, empty filter matches everything */
+fn filter_nodes(
+    objects: &HashMap<String, DatabaseObject>,
+    types: &[DatabaseObjectType],
+    schemas: &[String]
+) -> anyhow::Result<Vec<String>> {
+    let mut result = vec![];
+    for (object_id, object) in objects.iter() {
+        let object_type = object.object_type()?;
+        if types.len() > 0 && !types.contains(&object_type) {
+            continue;
+        }
+        if schemas.len() > 0 {
+            // objects without schema (roles, extensions, etc.) are excluded by schema filter
+            let schema = match object_type {
+                DatabaseObjectType::Schema => Some(project::get_name(object_id)?),
+                _ => project::get_schema(object_id).ok(),
+            };
+            match schema {
+                Some(schema) if schemas.iter().any(|s| s.to_lowercase() == schema) => {},
+                _ => continue,
+            }
+        }
+        result.push(object_id.clone());
+    }
+    result.sort();
+    return Ok(result);
+}
+
+/* "depends on" edges between given nodes */
+fn get_edges(objects: &HashMap<String, DatabaseObject>, nodes: &[String]) -> Vec<Edge> {
+    let node_set: HashSet<&String> = nodes.iter().collect();
+    let mut result = vec![];
+    for node in nodes {
+        let mut depends_on: Vec<&String> = objects[node].depends_on.iter()
+            .filter(|id| node_set.contains(id))
+            .collect();
+        depends_on.sort();
+        for dependency in depends_on {
+            result.push(Edge {
+                from: node.clone(),
+                to: dependency.clone(),
+                reasons: vec![],
+            });
+        }
+    }
+    return result;
+}
+
+struct Tarjan<'a> {
+    objects: &'a HashMap<String, DatabaseObject>,
+    index: usize,
+    indexes: HashMap<&'a String, usize>,
+    low_links: HashMap<&'a String, usize>,
+    stack: Vec<&'a String>,
+    on_stack: HashSet<&'a String>,
+    components: Vec<Vec<String>>,
+}
+
+impl<'a> Tarjan<'a> {
+    fn visit(&mut self, node: &'a String) {
+        self.indexes.insert(node, self.index);
+        self.low_links.insert(node, self.index);
+        self.index += 1;
+        self.stack.push(node);
+        self.on_stack.insert(node);
+
+        let mut depends_on: Vec<&String> = self.objects[node].depends_on.iter()
+            .filter(|id| self.objects.contains_key(*id))
+            .collect();
+        depends_on.sort();
+        for dependency in depends_on {
+            let dependency = self.objects.get_key_value(dependency).unwrap().0;
+            if !self.indexes.contains_key(dependency) {
+                self.visit(dependency);
+                let low_link = self.low_links[node].min(self.low_links[dependency]);
+                self.low_links.insert(node, low_link);
+            } else if self.on_stack.contains(dependency) {
+                let low_link = self.low_links[node].min(self.indexes[dependency]);
+                self.low_links.insert(node, low_link);
+            }
+        }
+
+        if self.low_links[node] == self.indexes[node] {
+            let mut component = vec![];
+            while let Some(member) = self.stack.pop() {
+                self.on_stack.remove(member);
+                component.push(member.clone());
+                if member == node {
+                    break;
+                }
+            }
+            component.sort();
+            self.components.push(component);
+        }
+    }
+}
+
+/* strongly connected components having more than one object or a self reference, i.e. dependency cycles */
+pub fn find_cycles(objects: &HashMap<String, DatabaseObject>) -> Vec<Vec<String>> {
+    let mut tarjan = Tarjan {
+        objects,
+        index: 0,
+        indexes: HashMap::new(),
+        low_links: HashMap::new(),
+        stack: vec![],
+        on_stack: HashSet::new(),
+        components: vec![],
+    };
+    let mut nodes: Vec<&String> = objects.keys().collect();
+    nodes.sort();
+    for node in nodes {
+        if !tarjan.indexes.contains_key(node) {
+            tarjan.visit(node);
+        }
+    }
+
+    let mut result: Vec<Vec<String>> = tarjan.components.into_iter()
+        .filter(|c| c.len() > 1 || objects[&c[0]].depends_on.contains(&c[0]))
+        .collect();
+    result.sort();
+    return result;
+}
+
+fn to_dot(nodes: &[String], edges: &[Edge]) -> String {
+    let mut result = String::from("digraph wo {\n    rankdir=LR;\n");
+    for node in nodes {
+        result.push_str(&format!("    {:?};\n", node));
+    }
+    for edge in edges {
+        result.push_str(&format!("    {:?} -> {:?};\n", edge.from, edge.to));
+    }
+    result.push_str("}\n");
+    return result;
+}
+
+fn to_mermaid(nodes: &[String], edges: &[Edge]) -> String {
+    let mut result = String::from("graph LR\n");
+    let node_keys: HashMap<&String, String> = nodes.iter().enumerate()
+        .map(|(i, node)| (node, format!("n{}", i)))
+        .collect();
+    for node in nodes {
+        result.push_str(&format!("    {}[\"{}\"]\n", node_keys[node], node));
+    }
+    for edge in edges {
+        result.push_str(&format!("    {} --> {}\n", node_keys[&edge.from], node_keys[&edge.to]));
+    }
+    return result;
+}
+
+fn edges_to_json(edges: &[Edge]) -> serde_json::Value {
+    let edges: Vec<serde_json::Value> = edges.iter()
+        .map(|e| {
+            if e.reasons.is_empty() {
+                serde_json::json!({ "from": e.from, "to": e.to })
+            } else {
+                serde_json::json!({ "from": e.from, "to": e.to, "reasons": e.reasons })
+            }
+        })
+        .collect();
+    return serde_json::Value::Array(edges);
+}
+
+fn to_json(nodes: &[String], edges: &[Edge]) -> String {
+    let value = serde_json::json!({
+        "nodes": nodes,
+        "edges": edges_to_json(edges),
+    });
+    return format!("{}\n", serde_json::to_string_pretty(&value).unwrap_or_default());
+}
+
+/* edges inside the cycle, each with script lines which produced it */
+pub fn get_cycle_edges(database_project: &DatabaseProject, cycle: &[String]) -> anyhow::Result<Vec<Edge>> {
+    let mut edges = get_edges(&database_project.objects, cycle);
+    for edge in edges.iter_mut() {
+        edge.reasons = project::get_dependency_reasons(&database_project.objects, &edge.from, &edge.to)?;
+    }
+    return Ok(edges);
+}
+
+/* shortest path from the first object of the component back to it, in "depends on" direction without the repeated object */
+fn find_cycle_path(objects: &HashMap<String, DatabaseObject>, component: &[String]) -> Vec<String> {
+    let start = &component[0];
+    let mut previous: HashMap<&String, &String> = HashMap::new();
+    let mut queue = VecDeque::from(vec![start]);
+    while let Some(node) = queue.pop_front() {
+        let mut depends_on: Vec<&String> = objects[node].depends_on.iter()
+            .filter(|id| component.contains(id))
+            .collect();
+        depends_on.sort();
+        for dependency in depends_on {
+            if dependency == start {
+                let mut result = vec![node.clone()];
+                let mut current = node;
+                while current != start {
+                    current = previous[current];
+                    result.push(current.clone());
+                }
+                result.reverse();
+                return result;
+            }
+            if !previous.contains_key(dependency) {
+                previous.insert(dependency, node);
+                queue.push_back(dependency);
+            }
+        }
+    }
+    return component.to_vec();
+}
+
+/* suggestion how to break the cycle, tailored to types of objects in the cycle given in "depends on" order */
+pub fn get_cycle_hint(cycle: &[String]) -> String {
+    let of_type = |object_type: DatabaseObjectType| -> Vec<&String> {
+        return cycle.iter()
+            .filter(|id| project::get_object_type(id).ok() == Some(object_type))
+            .collect();
+    };
+    let tables = of_type(DatabaseObjectType::Table);
+    let functions = of_type(DatabaseObjectType::Function);
+    let views = of_type(DatabaseObjectType::View);
+
+    if tables.len() > 1 {
+        return format!(
+            "move foreign keys between tables {} out of table scripts into ./constraints/<schema>.<table>.<name>.sql scripts, \
+            constraints are created after all tables",
+            tables.iter().map(|id| format!("{:?}", id)).collect::<Vec<String>>().join(", ")
+        );
+    }
+    if let (Some(table), Some(function)) = (tables.first(), functions.first()) {
+        return format!(
+            "if function {:?} uses table {:?} only in its body, add \"-- wo:ignore-dependency {}\" to the function script, \
+            otherwise move column defaults or checks calling the function into ./constraints/ scripts",
+            function, table, table
+        );
+    }
+    if functions.len() > 1 && functions.len() == cycle.len() {
+        return format!(
+            "function bodies are resolved at run time, add \"-- wo:ignore-dependency {}\" to {:?} script to create it first",
+            cycle[1], cycle[0]
+        );
+    }
+    if views.len() > 0 {
+        return String::from(
+            "views can not depend on each other in a cycle, one of the edges is likely a name match in a comment or string literal, \
+            remove it or add \"-- wo:ignore-dependency <id>\" to the script"
+        );
+    }
+    return String::from(
+        "if one of the edges is a false name match add \"-- wo:ignore-dependency <id>\" to the script, \
+        see wo graph --cycles for all cycles"
+    );
+}
+
+/* cycle error message, path is in "depends on" direction and ends with its first object */
+pub fn describe_cycle(objects: &HashMap<String, DatabaseObject>, path: &[String]) -> anyhow::Result<String> {
+    let mut result = format!("cycle detected: {}", path.join(" -> "));
+    for edge in path.windows(2) {
+        result.push_str(&format!("\n    {} -> {}", edge[0], edge[1]));
+        let reasons = project::get_dependency_reasons(objects, &edge[0], &edge[1])?;
+        if reasons.is_empty() {
+            result.push_str(&format!("\n        {:?} is not in the project, dependency is stored in wo_objects", edge[0]));
+        }
+        for reason in reasons {
+            result.push_str(&format!("\n        {}", reason));
+        }
+    }
+    result.push_str(&format!("\nhint: {}", get_cycle_hint(&path[..path.len() - 1])));
+    return Ok(result);
+}
+
+fn cycles_to_text(database_project: &DatabaseProject, cycles: &[Vec<String>]) -> anyhow::Result<String> {
+    if cycles.is_empty() {
+        return Ok("no dependency cycles found\n".into());
+    }
+    let mut result = String::new();
+    for (i, cycle) in cycles.iter().enumerate() {
+        result.push_str(&format!("cycle {}: {}\n", i + 1, cycle.join(", ")));
+        for edge in get_cycle_edges(database_project, cycle)? {
+            result.push_str(&format!("    {} -> {}\n", edge.from, edge.to));
+            for reason in edge.reasons {
+                result.push_str(&format!("        {}\n", reason));
+            }
+        }
+        let path = find_cycle_path(&database_project.objects, cycle);
+        result.push_str(&format!("    hint: {}\n", get_cycle_hint(&path)));
+    }
+    return Ok(result);
+}
+
+fn cycles_to_json(database_project: &DatabaseProject, cycles: &[Vec<String>]) -> anyhow::Result<String> {
+    let mut values = vec![];
+    for cycle in cycles {
+        let edges = get_cycle_edges(database_project, cycle)?;
+        values.push(serde_json::json!({
+            "nodes": cycle,
+            "edges": edges_to_json(&edges),
+            "hint": get_cycle_hint(&find_cycle_path(&database_project.objects, cycle)),
+        }));
+    }
+    let value = serde_json::json!({ "cycles": values });
+    return Ok(format!("{}\n", serde_json::to_string_pretty(&value)?));
+}
+
+/* dependency graph of project objects, edges point from object to its dependency */
+pub fn graph(
+    database_project: &DatabaseProject,
+    format: &str,
+    types: &[String],
+    schemas: &[String],
+    cycles: bool
+) -> anyhow::Result<String> {
+    let mut object_types = vec![];
+    for t in types {
+        object_types.push(DatabaseObjectType::from_str(&t.to_lowercase())?);
+    }
+    let nodes = filter_nodes(&database_project.objects, &object_types, schemas)?;
+
+    if cycles {
+        let node_set: HashSet<&String> = nodes.iter().collect();
+        let cycles: Vec<Vec<String>> = find_cycles(&database_project.objects).into_iter()
+            .filter(|c| c.iter().any(|id| node_set.contains(id)))
+            .collect();
+        return match format {
+            "json" => cycles_to_json(database_project, &cycles),
+            _ => cycles_to_text(database_project, &cycles),
+        };
+    }
+
+    let edges = get_edges(&database_project.objects, &nodes);
+    match format {
+        "dot" => return Ok(to_dot(&nodes, &edges)),
+        "mermaid" => return Ok(to_mermaid(&nodes, &edges)),
+        "json" => return Ok(to_json(&nodes, &edges)),
+        _ => bail!("graph error: unknown format {:?}, expected dot, mermaid or json", format),
+    }
+}
@@ -0,0 +1,89 @@
+use anyhow;
+use super::*;
+
+
+fn object(id: &str, depends_on: Vec<&str>) -> DatabaseObject {
+    return DatabaseObject {
+        id: id.into(),
+        path_buf: format!("{}.sql", id).into(),
+        script: "".into(),
+        md5: "asd".into(),
+        depends_on: depends_on.iter().map(|d| String::from(*d)).collect(),
+        required_by: HashSet::new(),
+        drop_script: None,
+        line_offset: 0,
+    };
+}
+
+fn objects(list: Vec<DatabaseObject>) -> HashMap<String, DatabaseObject> {
+    return list.into_iter().map(|o| (o.id.clone(), o)).collect();
+}
+
+
+#[test]
+fn test_find_cycles() -> anyhow::Result<()> {
+    let objects = objects(vec![
+        object("table.s.a", vec!["table.s.b"]),
+        object("table.s.b", vec!["table.s.c"]),
+        object("table.s.c", vec!["table.s.a", "schema.s"]),
+        object("view.s.d", vec!["view.s.d", "table.s.a"]),
+        object("view.s.e", vec!["table.s.a"]),
+        object("schema.s", vec![]),
+    ]);
+    assert_eq!(find_cycles(&objects), vec![
+        vec!["table.s.a", "table.s.b", "table.s.c"],
+        vec!["view.s.d"],
+    ]);
+    assert_eq!(find_cycle_path(&objects, &find_cycles(&objects)[0]), vec!["table.s.a", "table.s.b", "table.s.c"]);
+    assert_eq!(find_cycle_path(&objects, &find_cycles(&objects)[1]), vec!["view.s.d"]);
+    return Ok(());
+}
+
+#[test]
+fn test_graph_formats() -> anyhow::Result<()> {
+    let objects = objects(vec![
+        object("table.s.a", vec!["schema.s"]),
+        object("view.s.v", vec!["table.s.a", "schema.s"]),
+        object("schema.s", vec![]),
+        object("role.r", vec![]),
+    ]);
+
+    let nodes = filter_nodes(&objects, &[DatabaseObjectType::Table, DatabaseObjectType::View], &[])?;
+    assert_eq!(nodes, vec!["table.s.a", "view.s.v"]);
+    let nodes = filter_nodes(&objects, &[], &["S".into()])?;
+    assert_eq!(nodes, vec!["schema.s", "table.s.a", "view.s.v"]);
+
+    let nodes = filter_nodes(&objects, &[DatabaseObjectType::Table, DatabaseObjectType::View], &[])?;
+    let edges = get_edges(&objects, &nodes);
+    assert_eq!(
+        to_dot(&nodes, &edges),
+        "digraph wo {\n    rankdir=LR;\n    \"table.s.a\";\n    \"view.s.v\";\n    \"view.s.v\" -> \"table.s.a\";\n}\n"
+    );
+    assert_eq!(
+        to_mermaid(&nodes, &edges),
+        "graph LR\n    n0[\"table.s.a\"]\n    n1[\"view.s.v\"]\n    n1 --> n0\n"
+    );
+    assert_eq!(to_json(&nodes, &edges).contains("\"from\": \"view.s.v\""), true);
+    return Ok(());
+}
+
+#[test]
+fn test_get_cycle_hint() -> anyhow::Result<()> {
+    let cycle = |ids: Vec<&str>| ids.iter().map(|id| String::from(*id)).collect::<Vec<String>>();
+
+    let hint = get_cycle_hint(&cycle(vec!["table.s.a", "table.s.b"]));
+    assert_eq!(hint.starts_with("move foreign keys between tables \"table.s.a\", \"table.s.b\""), true);
+
+    let hint = get_cycle_hint(&cycle(vec!["function.s.f", "table.s.a"]));
+    assert_eq!(hint.contains("-- wo:ignore-dependency table.s.a"), true);
+
+    let hint = get_cycle_hint(&cycle(vec!["function.s.f", "function.s.g"]));
+    assert_eq!(hint.contains("add \"-- wo:ignore-dependency function.s.g\" to \"function.s.f\" script"), true);
+
+    let hint = get_cycle_hint(&cycle(vec!["view.s.a", "view.s.b"]));
+    assert_eq!(hint.starts_with("views can not depend on each other"), true);
+
+    let hint = get_cycle_hint(&cycle(vec!["role.a", "role.b"]));
+    assert_eq!(hint.contains("wo graph --cycles"), true);
+    return Ok(());
+}
@@ -0,0 +1,490 @@
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::path::PathBuf;
+use std::str::FromStr;
+use anyhow;
+use anyhow::Context;
+use serde_json;
+use toml;
+use crate::project::DatabaseProject;
+use crate::project::DatabaseObjectType;
+use crate::database;
+use crate::sql;
+use crate::sql::Statement;
+use crate::sql::Token;
+use crate::utils;
+
+#[cfg(test)]
+mod tests;
+
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+    Off,
+}
+
+impl FromStr for Severity {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "error" => Ok(Severity::Error),
+            "warning" => Ok(Severity::Warning),
+            "off" => Ok(Severity::Off),
+            _ => bail!("unknown lint severity {:?}, expected error, warning or off", s),
+        }
+    }
+}
+
+impl From<&Severity> for String {
+    fn from(s: &Severity) -> Self {
+        match s {
+            Severity::Error => "error".into(),
+            Severity::Warning => "warning".into(),
+            Severity::Off => "off".into(),
+        }
+    }
+}
+
+// (name, default severity) of lint rules
+const RULES: [(&str, Severity); 6] = [
+    ("view-or-replace", Severity::Warning),
+    ("function-revoke-public", Severity::Warning),
+    ("role-prefix", Severity::Error),
+    ("policy-role", Severity::Warning),
+    ("migration-idempotent", Severity::Warning),
+    ("access-exclusive-lock", Severity::Warning),
+];
+
+const DEFAULT_LARGE_TABLE_ROWS: i64 = 1000000;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Problem {
+    pub rule: String,
+    pub severity: Severity,
+    pub path: PathBuf,
+    pub line: usize,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LintConfig {
+    pub severities: HashMap<String, Severity>,
+    // "<schema>.<table>" of tables considered large, None if unknown (every table is considered large)
+    pub large_tables: Option<HashSet<String>>,
+    pub large_table_rows: i64,
+}
+
+/* lint configuration from [lint] section of wo.toml */
+pub fn load_lint_config(config: &toml::Value) -> anyhow::Result<LintConfig> {
+    let mut result = LintConfig {
+        severities: RULES.iter().map(|(name, severity)| (String::from(*name), *severity)).collect(),
+        large_tables: None,
+        large_table_rows: DEFAULT_LARGE_TABLE_ROWS,
+    };
+    let lint = match config.get("lint") {
+        Some(lint) => lint,
+        None => return Ok(result),
+    };
+
+    if let Some(rules) = lint.get("rules") {
+        let rules = rules.as_table()
+            .ok_or(anyhow!("load_lint_config error: lint.rules has to be a table"))?;
+        for (name, value) in rules.iter() {
+            if !result.severities.contains_key(name) {
+                bail!("load_lint_config error: unknown lint rule {:?}", name);
+            }
+            let severity = value.as_str()
+                .ok_or(anyhow!("load_lint_config error: severity of lint rule {:?} has to be a string", name))?;
+            let severity = Severity::from_str(severity)
+                .context(format!("load_lint_config error: invalid severity of lint rule {:?}", name))?;
+            result.severities.insert(name.clone(), severity);
+        }
+    }
+
+    if let Some(large_tables) = lint.get("large_tables") {
+        let large_tables = large_tables.as_array()
+            .ok_or(anyhow!("load_lint_config error: lint.large_tables has to be an array"))?;
+        let mut tables = HashSet::new();
+        for table in large_tables {
+            let table = table.as_str()
+                .ok_or(anyhow!("load_lint_config error: lint.large_tables has to contain strings"))?;
+            tables.insert(get_table_key(&table.split('.').map(String::from).collect::<Vec<String>>()));
+        }
+        result.large_tables = Some(tables);
+    }
+
+    if let Some(large_table_rows) = lint.get("large_table_rows") {
+        result.large_table_rows = large_table_rows.as_integer()
+            .ok_or(anyhow!("load_lint_config error: lint.large_table_rows has to be an integer"))?;
+    }
+    return Ok(result);
+}
+
+/* "<schema>.<table>" of table name parts, unqualified tables are assumed to be in public schema */
+fn get_table_key(parts: &[String]) -> String {
+    let parts: Vec<String> = parts.iter().map(|p| p.to_lowercase()).collect();
+    match parts.len() {
+        1 => format!("public.{}", parts[0]),
+        _ => parts[parts.len() - 2..].join("."),
+    }
+}
+
+fn get_statement_line(script: &str, statement: &Statement) -> usize {
+    return script[..statement.offset].matches('\n').count() + 1;
+}
+
+fn has_words(tokens: &[Token], words: &[&str]) -> bool {
+    return tokens.windows(words.len())
+        .any(|window| window.iter().zip(words).all(|(token, word)| token.is_word(word)));
+}
+
+/* (line, message) of views created without "or replace" */
+pub fn check_view_or_replace(script: &str) -> Vec<(usize, String)> {
+    let mut result = vec![];
+    for statement in sql::split_statements(script) {
+        if let Some(view) = sql::parse_create_view(&statement) {
+            if !view.or_replace && !view.materialized {
+                result.push((
+                    get_statement_line(script, &statement),
+                    format!("view {} is created without \"or replace\", it will be dropped and created again along with dependent objects on each change", view.name),
+                ));
+            }
+        }
+    }
+    return result;
+}
+
+fn is_trigger_function(statement: &Statement) -> bool {
+    let tokens = &statement.tokens;
+    return match sql::find_word(tokens, "returns", 0) {
+        Some(i) => tokens.get(i + 1).map(|t| t.is_word("trigger") || t.is_word("event_trigger")) == Some(true),
+        None => false,
+    };
+}
+
+fn is_revoke_execute_from_public(statement: &Statement) -> bool {
+    let tokens = &statement.tokens;
+    let revoke = match sql::find_word(tokens, "revoke", 0) {
+        Some(i) => i,
+        None => return false,
+    };
+    if sql::find_word(tokens, "execute", revoke).is_none() {
+        return false;
+    }
+    return match sql::find_word(tokens, "from", revoke) {
+        Some(i) => tokens[i + 1..].iter().any(|t| t.is_word("public")),
+        None => false,
+    };
+}
+
+/* (line, message) of functions which are not revoked from public */
+pub fn check_function_revoke_public(script: &str) -> Vec<(usize, String)> {
+    let mut function_line = None;
+    let mut revoked = false;
+    for statement in sql::split_statements(script) {
+        if sql::parse_create_function(&statement).is_some() && !is_trigger_function(&statement) && function_line.is_none() {
+            function_line = Some(get_statement_line(script, &statement));
+        }
+        if is_revoke_execute_from_public(&statement) {
+            revoked = true;
+        }
+    }
+    return match function_line {
+        Some(line) if !revoked => vec![(
+            line,
+            "function script does not revoke execute from public, functions are executable by every role by default".into(),
+        )],
+        _ => vec![],
+    };
+}
+
+/* (line, message) of roles created without {wo_role_prefix}, expects script before the prefix is substituted */
+pub fn check_role_prefix(script: &str) -> Vec<(usize, String)> {
+    let mut result = vec![];
+    for statement in sql::split_statements(script) {
+        let is_create_role = statement.starts_with_words(&["create", "role"])
+            || statement.starts_with_words(&["create", "group"])
+            || (statement.starts_with_words(&["create", "user"]) && !statement.starts_with_words(&["create", "user", "mapping"]));
+        if is_create_role && !statement.text.contains("{wo_role_prefix}") {
+            result.push((
+                get_statement_line(script, &statement),
+                "role is created without {wo_role_prefix}, role names have to be unique per database".into(),
+            ));
+        }
+    }
+    return result;
+}
+
+/* (line, message) of policies assigned to roles other than public */
+pub fn check_policy_role(script: &str) -> Vec<(usize, String)> {
+    let mut result = vec![];
+    for statement in sql::split_statements(script) {
+        if !statement.starts_with_words(&["create", "policy"]) {
+            continue;
+        }
+        let tokens = &statement.tokens;
+        let on = match sql::find_word(tokens, "on", 2) {
+            Some(i) => i,
+            None => continue,
+        };
+        let to = match sql::find_word(tokens, "to", on) {
+            Some(i) => i,
+            None => continue,
+        };
+        let roles: Vec<&str> = tokens[to + 1..].iter()
+            .take_while(|t| !t.is_word("using") && !t.is_word("with"))
+            .filter(|t| t.is_identifier() && !t.is_word("public"))
+            .map(|t| t.text)
+            .collect();
+        if roles.len() > 0 {
+            result.push((
+                get_statement_line(script, &statement),
+                format!("policy targets roles {} directly, assign roles in role scripts using alter policy", roles.join(", ")),
+            ));
+        }
+    }
+    return result;
+}
+
+/* message for "alter table" action which fails when executed again */
+fn check_alter_table_action(action: &[Token]) -> Option<String> {
+    let first = action.first()?;
+    if !first.is_word("add") && !first.is_word("drop") {
+        return None;
+    }
+    let second = action.get(1)?;
+    let constraint_words = ["constraint", "primary", "unique", "check", "foreign", "exclude"];
+    if first.is_word("add") && constraint_words.iter().any(|w| second.is_word(w)) {
+        return None;
+    }
+    let mut i = 1;
+    if second.is_word("column") || (first.is_word("drop") && second.is_word("constraint")) {
+        i += 1;
+    }
+    if action.get(i).map(|t| t.is_word("if")) == Some(true) {
+        return None;
+    }
+    if first.is_word("add") {
+        return Some("add column without \"if not exists\"".into());
+    }
+    return Some(format!("drop {} without \"if exists\"", if second.is_word("constraint") { "constraint" } else { "column" }));
+}
+
+/* (line, message) of migration statements which fail when the migration is executed again */
+pub fn check_migration_idempotent(script: &str) -> Vec<(usize, String)> {
+    let mut result = vec![];
+    for statement in sql::split_statements(script) {
+        let tokens = &statement.tokens;
+        let mut problems = vec![];
+        if tokens[0].is_word("create") {
+            if let Some(created) = sql::parse_create_object(&statement) {
+                let materialized = has_words(&tokens[..tokens.len().min(4)], &["materialized"]);
+                let kind = if materialized { String::from("materialized view") } else { created.kind.clone() };
+                let if_not_exists_kinds = ["table", "index", "sequence", "schema", "extension", "foreign table", "server", "materialized view"];
+                let or_replace_kinds = ["view", "function", "procedure", "trigger", "rule"];
+                if if_not_exists_kinds.contains(&kind.as_str()) && !has_words(tokens, &["if", "not", "exists"]) {
+                    problems.push(format!("create {} without \"if not exists\"", kind));
+                }
+                if or_replace_kinds.contains(&kind.as_str()) && !statement.starts_with_words(&["create", "or", "replace"]) {
+                    problems.push(format!("create {} without \"or replace\"", kind));
+                }
+            }
+        } else if tokens[0].is_word("drop") {
+            if !has_words(tokens, &["if", "exists"]) {
+                problems.push(format!("drop {} without \"if exists\"", tokens.get(1).map(|t| t.text.to_lowercase()).unwrap_or_default()));
+            }
+        } else if let Some((_, i)) = sql::parse_alter_table(&statement) {
+            for action in sql::split_by_commas(&tokens[i..]) {
+                problems.extend(check_alter_table_action(action));
+            }
+        }
+        for problem in problems {
+            result.push((
+                get_statement_line(script, &statement),
+                format!("{} fails when the migration is executed again", problem),
+            ));
+        }
+    }
+    return result;
+}
+
+/* whether "alter table" action takes a lock weaker than access exclusive */
+fn is_weak_lock_action(action: &[Token]) -> bool {
+    let starts_with = |words: &[&str]| action.len() >= words.len() && action.iter().zip(words).all(|(t, w)| t.is_word(w));
+    return starts_with(&["validate", "constraint"])
+        || starts_with(&["enable", "trigger"])
+        || starts_with(&["disable", "trigger"])
+        || starts_with(&["cluster", "on"])
+        || starts_with(&["set", "without", "cluster"])
+        || starts_with(&["attach", "partition"])
+        || (starts_with(&["detach", "partition"]) && has_words(action, &["concurrently"]))
+        || (starts_with(&["add"]) && has_words(action, &["foreign", "key"]))
+        || (starts_with(&["alter"]) && has_words(action, &["set", "statistics"]));
+}
+
+/* table name parts of tables locked in access exclusive mode by the statement */
+fn get_access_exclusive_tables(statement: &Statement) -> Vec<Vec<String>> {
+    let tokens = &statement.tokens;
+    let mut result = vec![];
+    let mut parse_tables = |start: usize| {
+        let mut i = start;
+        while let Some(token) = tokens.get(i) {
+            if token.is_word("only") || token.is_word("table") || token.is_symbol(",") {
+                i += 1;
+                continue;
+            }
+            match sql::parse_qualified_name(tokens, i) {
+                Some((parts, next)) => {
+                    result.push(parts);
+                    i = next;
+                },
+                None => break,
+            }
+        }
+    };
+
+    if let Some((table, i)) = sql::parse_alter_table(statement) {
+        if !sql::split_by_commas(&tokens[i..]).iter().all(|action| is_weak_lock_action(action)) {
+            result.push(table);
+        }
+    } else if statement.starts_with_words(&["drop", "table"]) {
+        let i = if has_words(&tokens[..tokens.len().min(4)], &["if", "exists"]) { 4 } else { 2 };
+        parse_tables(i);
+    } else if statement.starts_with_words(&["truncate"]) || statement.starts_with_words(&["cluster"]) {
+        parse_tables(1);
+    } else if statement.starts_with_words(&["vacuum", "full"]) {
+        parse_tables(2);
+    } else if statement.starts_with_words(&["refresh", "materialized", "view"]) && !has_words(tokens, &["concurrently"]) {
+        parse_tables(3);
+    } else if statement.starts_with_words(&["lock"]) {
+        let mode = sql::find_word(tokens, "in", 1);
+        if mode.is_none() || has_words(tokens, &["access", "exclusive"]) {
+            parse_tables(1);
+        }
+    }
+    return result;
+}
+
+/* (line, message) of statements taking access exclusive lock on large tables */
+pub fn check_access_exclusive_lock(script: &str, large_tables: &Option<HashSet<String>>) -> Vec<(usize, String)> {
+    let mut result = vec![];
+    for statement in sql::split_statements(script) {
+        for table in get_access_exclusive_tables(&statement) {
+            let key = get_table_key(&table);
+            let is_large = match large_tables {
+                Some(large_tables) => large_tables.contains(&key),
+                None => true,
+            };
+            if is_large {
+                result.push((
+                    get_statement_line(script, &statement),
+                    format!("statement takes access exclusive lock on table {}, all reads and writes are blocked until the migration commits", key),
+                ));
+            }
+        }
+    }
+    return result;
+}
+
+/* problems of all project objects and migrations, sorted by location */
+pub fn lint_project(database_project: &DatabaseProject, config: &LintConfig) -> anyhow::Result<Vec<Problem>> {
+    let mut result = vec![];
+    let mut add_problems = |rule: &str, path: &PathBuf, line_offset: usize, problems: Vec<(usize, String)>| {
+        let severity = config.severities[rule];
+        if severity == Severity::Off {
+            return;
+        }
+        for (line, message) in problems {
+            result.push(Problem {
+                rule: rule.into(),
+                severity,
+                path: path.clone(),
+                line: line_offset + line,
+                message,
+            });
+        }
+    };
+
+    for object in database_project.objects.values() {
+        let line_offset = object.line_offset;
+        match object.object_type()? {
+            DatabaseObjectType::View => {
+                add_problems("view-or-replace", &object.path_buf, line_offset, check_view_or_replace(&object.script));
+            },
+            DatabaseObjectType::Function => {
+                add_problems("function-revoke-public", &object.path_buf, line_offset, check_function_revoke_public(&object.script));
+            },
+            DatabaseObjectType::Role => {
+                // prefix is already substituted in object script
+                let script = utils::read_file(&object.path_buf)?;
+                add_problems("role-prefix", &object.path_buf, 0, check_role_prefix(&script));
+            },
+            DatabaseObjectType::Policy => {
+                add_problems("policy-role", &object.path_buf, line_offset, check_policy_role(&object.script));
+            },
+            _ => {},
+        }
+    }
+
+    for (migration_id, script) in database_project.migration_scripts.iter() {
+        let path_buf = database_project.project_dirpath.join("migrations").join(migration_id);
+        add_problems("migration-idempotent", &path_buf, 0, check_migration_idempotent(script));
+        add_problems("access-exclusive-lock", &path_buf, 0, check_access_exclusive_lock(script, &config.large_tables));
+    }
+
+    result.sort_by(|a, b| (&a.path, a.line, &a.rule).cmp(&(&b.path, b.line, &b.rule)));
+    return Ok(result);
+}
+
+fn problems_to_text(problems: &[Problem]) -> String {
+    let mut result = String::new();
+    for problem in problems {
+        result.push_str(&format!(
+            "{}:{}: {}: {} [{}]\n",
+            problem.path.display(), problem.line, String::from(&problem.severity), problem.message, problem.rule
+        ));
+    }
+    let errors = problems.iter().filter(|p| p.severity == Severity::Error).count();
+    result.push_str(&format!("{} errors, {} warnings\n", errors, problems.len() - errors));
+    return result;
+}
+
+fn problems_to_json(problems: &[Problem]) -> anyhow::Result<String> {
+    let values: Vec<serde_json::Value> = problems.iter()
+        .map(|p| serde_json::json!({
+            "rule": p.rule,
+            "severity": String::from(&p.severity),
+            "path": p.path.display().to_string(),
+            "line": p.line,
+            "message": p.message,
+        }))
+        .collect();
+    let errors = problems.iter().filter(|p| p.severity == Severity::Error).count();
+    let value = serde_json::json!({
+        "problems": values,
+        "errors": errors,
+        "warnings": problems.len() - errors,
+    });
+    return Ok(format!("{}\n", serde_json::to_string_pretty(&value)?));
+}
+
+/* lint report in given format and number of problems with error severity */
+pub fn lint(database_project: &DatabaseProject, format: &str, use_database: bool) -> anyhow::Result<(String, usize)> {
+    let mut config = load_lint_config(&database_project.config)?;
+    if use_database {
+        let tables = database::select_large_tables(config.large_table_rows)
+            .context("lint error: could not select large tables")?;
+        config.large_tables.get_or_insert(HashSet::new()).extend(tables);
+    }
+
+    let problems = lint_project(database_project, &config)?;
+    let errors = problems.iter().filter(|p| p.severity == Severity::Error).count();
+    let output = match format {
+        "json" => problems_to_json(&problems)?,
+        _ => problems_to_text(&problems),
+    };
+    return Ok((output, errors));
+}
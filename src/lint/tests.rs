@@ -0,0 +1,97 @@
+use anyhow;
+use super::*;
+
+
+fn lines(problems: Vec<(usize, String)>) -> Vec<usize> {
+    return problems.into_iter().map(|(line, _)| line).collect();
+}
+
+#[test]
+fn test_load_lint_config() -> anyhow::Result<()> {
+    let config: toml::Value = "
+        [lint]
+        large_tables = [\"events\", \"app.Logs\"]
+        large_table_rows = 5000
+
+        [lint.rules]
+        view-or-replace = \"error\"
+        function-revoke-public = \"off\"
+    ".parse()?;
+    let lint_config = load_lint_config(&config)?;
+    assert_eq!(lint_config.severities["view-or-replace"], Severity::Error);
+    assert_eq!(lint_config.severities["function-revoke-public"], Severity::Off);
+    assert_eq!(lint_config.severities["role-prefix"], Severity::Error);
+    assert_eq!(lint_config.large_tables, Some(vec!["public.events".into(), "app.logs".into()].into_iter().collect()));
+    assert_eq!(lint_config.large_table_rows, 5000);
+
+    let config: toml::Value = "[lint.rules]\nno-such-rule = \"error\"".parse()?;
+    assert_eq!(load_lint_config(&config).is_err(), true);
+    let config: toml::Value = "[lint.rules]\nview-or-replace = \"fatal\"".parse()?;
+    assert_eq!(load_lint_config(&config).is_err(), true);
+
+    let lint_config = load_lint_config(&"".parse()?)?;
+    assert_eq!(lint_config.large_tables, None);
+    return Ok(());
+}
+
+#[test]
+fn test_object_rules() -> anyhow::Result<()> {
+    let script = "create view v0 as select 1;\ncreate or replace view v1 as select 1;\ncreate materialized view v2 as select 1;";
+    assert_eq!(lines(check_view_or_replace(script)), vec![1]);
+
+    let script = "create function f() returns int as $$ select 1 $$ language sql;";
+    assert_eq!(lines(check_function_revoke_public(script)), vec![1]);
+    let script = "create function f() returns int as $$ select 1 $$ language sql;\nrevoke execute on function f from public;";
+    assert_eq!(check_function_revoke_public(script).len(), 0);
+    let script = "create function t() returns trigger as $$ begin return new; end $$ language plpgsql;";
+    assert_eq!(check_function_revoke_public(script).len(), 0);
+
+    let script = "create role {wo_role_prefix}r0;\ncreate user r1;\ncreate user mapping for r1 server s;";
+    assert_eq!(lines(check_role_prefix(script)), vec![2]);
+
+    let script = "create policy p0 on t0 to public using (true);\ncreate policy p1 on t0 for select to r0, r1 using (true);";
+    assert_eq!(check_policy_role(script), vec![
+        (2, "policy targets roles r0, r1 directly, assign roles in role scripts using alter policy".into()),
+    ]);
+    return Ok(());
+}
+
+#[test]
+fn test_check_migration_idempotent() -> anyhow::Result<()> {
+    let script = "
+create table t0 (id int);
+create table if not exists t1 (id int);
+create index on t0 (id);
+create or replace view v0 as select 1;
+create view v1 as select 1;
+drop table t2;
+drop index if exists i0;
+alter table t0 add column c0 int, add if not exists c1 int, add constraint c check (c0 > 0);
+alter table t0 drop column c0, drop constraint if exists c;
+insert into t0 values (1);
+";
+    assert_eq!(lines(check_migration_idempotent(script)), vec![2, 4, 6, 7, 9, 10]);
+    assert_eq!(check_migration_idempotent(script)[4].1, "add column without \"if not exists\" fails when the migration is executed again");
+    return Ok(());
+}
+
+#[test]
+fn test_check_access_exclusive_lock() -> anyhow::Result<()> {
+    let script = "
+alter table events add column c0 int;
+alter table app.logs validate constraint c, alter column c set statistics 100;
+alter table app.logs add constraint fk foreign key (c) references t (id);
+truncate only events, app.logs;
+lock table events in share mode;
+refresh materialized view concurrently mv;
+vacuum full small;
+";
+    assert_eq!(lines(check_access_exclusive_lock(script, &None)), vec![2, 5, 5, 8]);
+
+    let large_tables = Some(vec!["public.events".into()].into_iter().collect());
+    assert_eq!(check_access_exclusive_lock(script, &large_tables), vec![
+        (2, "statement takes access exclusive lock on table public.events, all reads and writes are blocked until the migration commits".into()),
+        (5, "statement takes access exclusive lock on table public.events, all reads and writes are blocked until the migration commits".into()),
+    ]);
+    return Ok(());
+}
@@ -1,12 +1,29 @@
 #[macro_use] extern crate anyhow;
 
 use clap;
+use std::time::Instant;
 pub mod project;
 pub mod database;
 pub mod utils;
+pub mod sql;
+pub mod diff;
+pub mod graph;
+pub mod lint;
+pub mod output;
+pub mod template;
+pub mod secrets;
+pub mod connection;
 
 
-fn main() -> anyhow::Result<()> {
+fn main() {
+    if let Err(e) = run() {
+        // error messages could contain substituted secrets
+        eprintln!("Error: {}", secrets::mask(&format!("{:?}", e)));
+        std::process::exit(output::EXIT_FAILED);
+    }
+}
+
+fn run() -> anyhow::Result<()> {
 
     let about = format!("{}
 
@@ -14,8 +31,10 @@ ENVIRONMENT VARIABLES:
     WO_DIR                      project location
     WO_CONNECTION_STRING        connection string for target database
     WO_ADMIN_CONNECTION_STRING  connection string for admin database
-    WO_ROOT_CERT                path to root certificate to verify server's certificate
+    WO_ROOT_CERT                optional path to root certificate to verify server's certificate
     WO_ROLE_PREFIX              role prefix to make them unique per environment
+    WO_PROFILE                  optional profile of variables declared in wo.toml
+    PGHOST, PGPORT, PGUSER, ... parameters missing in connection strings like in libpq (also PGSERVICE and .pgpass)
 ",
         clap::crate_description!(),        
     );
@@ -26,30 +45,141 @@ ENVIRONMENT VARIABLES:
     .version(clap::crate_version!())
     .author(clap::crate_authors!("\n"))
     .about(about_str)
+    .arg(clap::Arg::new("detailed-exit-code")
+        .long("detailed-exit-code")
+        .global(true)
+        .about("exit with code 2 if command succeeded and changed database or project files"))
+    .arg(clap::Arg::new("quiet")
+        .short('q')
+        .long("quiet")
+        .global(true)
+        .about("print only warnings and errors"))
+    .arg(clap::Arg::new("verbose")
+        .short('v')
+        .long("verbose")
+        .multiple_occurrences(true)
+        .global(true)
+        .about("print action durations and internal messages, -vv also prints every executed sql statement with its duration"))
     .subcommand(clap::App::new("init")
-        .about("initialize new wo project"))
+        .about("initialize new wo project")
+        .arg(format_arg()))
     .subcommand(clap::App::new("migrate")
-        .about("update database"))
+        .about("update database")
+        .arg(format_arg())
+        .arg(clap::Arg::new("recreate-roles")
+            .long("recreate-roles")
+            .about("drop and create changed roles instead of altering them"))
+        .arg(clap::Arg::new("strict")
+            .long("strict")
+            .about("fail if modified table scripts do not match tables after migrations"))
+        .arg(clap::Arg::new("strict-scripts")
+            .long("strict-scripts")
+            .about("fail if object scripts do not create only the objects declared by their filenames"))
+        .arg(clap::Arg::new("catalog-dependencies")
+            .long("catalog-dependencies")
+            .about("store dependencies found in postgres catalog to drop dependent objects on the next update")))
+    .subcommand(clap::App::new("new")
+        .about("create a new object or migration script from template")
+        .arg(format_arg())
+        .arg(clap::Arg::new("type")
+            .required(true)
+            .about("object type (table, view, function, trigger, ...) or migration"))
+        .arg(clap::Arg::new("name")
+            .required(true)
+            .about("object name as in filename, e.g. public.users or public.users.set_updated_at")))
+    .subcommand(clap::App::new("graph")
+        .about("print dependency graph of project objects")
+        .arg(clap::Arg::new("format")
+            .long("format")
+            .takes_value(true)
+            .possible_values(&["dot", "mermaid", "json"])
+            .default_value("dot")
+            .about("output format"))
+        .arg(clap::Arg::new("type")
+            .long("type")
+            .takes_value(true)
+            .multiple_occurrences(true)
+            .about("include only objects of given type, can be repeated"))
+        .arg(clap::Arg::new("schema")
+            .long("schema")
+            .takes_value(true)
+            .multiple_occurrences(true)
+            .about("include only objects of given schema, can be repeated"))
+        .arg(clap::Arg::new("cycles")
+            .long("cycles")
+            .about("print dependency cycles with script lines which produced each edge")))
+    .subcommand(clap::App::new("lint")
+        .about("check project scripts and migrations for common mistakes")
+        .arg(clap::Arg::new("format")
+            .long("format")
+            .takes_value(true)
+            .possible_values(&["text", "json"])
+            .default_value("text")
+            .about("output format"))
+        .arg(clap::Arg::new("database")
+            .long("database")
+            .about("select large tables from target database for access-exclusive-lock rule")))
+    .subcommand(clap::App::new("migration")
+        .about("manage migration scripts")
+        .subcommand(clap::App::new("generate")
+            .about("generate migration draft from modified table scripts")
+            .arg(format_arg())
+            .arg(clap::Arg::new("name")
+                .required(true)
+                .about("migration name"))))
     .subcommand(clap::App::new("drop")
         .about("drop entire database")
+        .arg(format_arg())
         .arg(clap::Arg::new("no-joke") // wtf
             .long("no-joke")
             .about("confirmation")));
 
-    let matches = clap.clone().get_matches();
-    
+    let started = Instant::now();
+    let matches = match clap.clone().try_get_matches() {
+        Ok(matches) => matches,
+        Err(e) if is_json_requested() && e.use_stderr() => {
+            // invalid arguments are reported as error and summary events too
+            output::set_json(true);
+            let result: anyhow::Result<()> = Err(anyhow!("{}", e.to_string().trim()));
+            output::finish(&result, started);
+            std::process::exit(output::EXIT_FAILED);
+        },
+        Err(e) => e.exit(),
+    };
+
     match matches.subcommand() {
         Some(("init", subcommand_matches)) => {
-            utils::validate_environment()?;
-            subcommand_init(subcommand_matches)?;
+            set_output(subcommand_matches);
+            let result = utils::validate_environment().and_then(|_| subcommand_init(subcommand_matches));
+            finish_command(result, started, subcommand_matches)?;
         },
         Some(("migrate", subcommand_matches)) => {
+            set_output(subcommand_matches);
+            let result = utils::validate_environment().and_then(|_| subcommand_migrate(subcommand_matches));
+            finish_command(result, started, subcommand_matches)?;
+        },
+        Some(("new", subcommand_matches)) => {
+            set_output(subcommand_matches);
+            let result = utils::validate_environment().and_then(|_| subcommand_new(subcommand_matches));
+            finish_command(result, started, subcommand_matches)?;
+        },
+        Some(("graph", subcommand_matches)) => {
+            output::set_level(get_level(subcommand_matches));
             utils::validate_environment()?;
-            subcommand_migrate(subcommand_matches)?;
+            subcommand_graph(subcommand_matches)?;
         },
-        Some(("drop", subcommand_matches)) => {
+        Some(("lint", subcommand_matches)) => {
+            output::set_level(get_level(subcommand_matches));
             utils::validate_environment()?;
-            subcommand_drop(subcommand_matches)?;
+            subcommand_lint(subcommand_matches)?;
+        },
+        Some(("migration", subcommand_matches)) => {
+            subcommand_migration(subcommand_matches, started)?;
+        },
+        Some(("drop", subcommand_matches)) => {
+            set_output(subcommand_matches);
+            let result = utils::validate_environment().and_then(|_| subcommand_drop(subcommand_matches));
+            finish_command(result, started, subcommand_matches)?;
         },
         _ => {
             clap.print_help()?
@@ -59,21 +189,124 @@ ENVIRONMENT VARIABLES:
     return Ok(());
 }
 
+fn format_arg<'a>() -> clap::Arg<'a> {
+    return clap::Arg::new("format")
+        .long("format")
+        .takes_value(true)
+        .possible_values(&["text", "json"])
+        .default_value("text")
+        .about("output format, json prints one event per line followed by a summary");
+}
+
+fn get_level(matches: &clap::ArgMatches) -> output::Level {
+    return output::Level::from_flags(matches.is_present("quiet"), matches.occurrences_of("verbose"));
+}
+
+/* output level and format are set before anything else, so that all failures are reported in json mode */
+fn set_output(matches: &clap::ArgMatches) {
+    output::set_level(get_level(matches));
+    output::set_json(matches.value_of("format") == Some("json"));
+}
+
+/* "--format json" or "--format=json" in arguments which could not be parsed */
+fn is_json_requested() -> bool {
+    let args: Vec<String> = std::env::args().collect();
+    return args.iter().enumerate().any(|(i, arg)| {
+        arg == "--format=json" || (arg == "--format" && args.get(i + 1).map(|a| a.as_str()) == Some("json"))
+    });
+}
+
+/* prints summary in json mode and exits with 2 if changes were applied and --detailed-exit-code is provided */
+fn finish_command(
+    result: anyhow::Result<()>,
+    started: Instant,
+    matches: &clap::ArgMatches
+) -> anyhow::Result<()> {
+    let status = output::finish(&result, started);
+    result?;
+    if matches.is_present("detailed-exit-code") && status == output::Status::ChangesApplied {
+        std::process::exit(status.exit_code());
+    }
+    return Ok(());
+}
+
 
 fn subcommand_init(_matches: &clap::ArgMatches) -> anyhow::Result<()> {
     project::init()?;
     return Ok(());
 }
 
-fn subcommand_migrate(_matches: &clap::ArgMatches) -> anyhow::Result<()> {
+fn subcommand_migrate(matches: &clap::ArgMatches) -> anyhow::Result<()> {
+    let options = database::MigrateOptions {
+        recreate_roles: matches.is_present("recreate-roles"),
+        strict: matches.is_present("strict"),
+        strict_scripts: matches.is_present("strict-scripts"),
+        catalog_dependencies: matches.is_present("catalog-dependencies"),
+    };
+    let database_project = project::load()?;
+    database::migrate(database_project, &options)?;
+    return Ok(());
+}
+
+fn subcommand_new(matches: &clap::ArgMatches) -> anyhow::Result<()> {
+    let object_type = matches.value_of("type")
+        .ok_or(anyhow!("object type is required"))?;
+    let name = matches.value_of("name")
+        .ok_or(anyhow!("object name is required"))?;
+    project::new_object(object_type, name)?;
+    return Ok(());
+}
+
+fn subcommand_graph(matches: &clap::ArgMatches) -> anyhow::Result<()> {
+    let format = matches.value_of("format").unwrap_or("dot");
+    let types: Vec<String> = matches.values_of("type")
+        .map(|values| values.map(String::from).collect())
+        .unwrap_or_default();
+    let schemas: Vec<String> = matches.values_of("schema")
+        .map(|values| values.map(String::from).collect())
+        .unwrap_or_default();
+    let database_project = project::load()?;
+    let output = graph::graph(&database_project, format, &types, &schemas, matches.is_present("cycles"))?;
+    print!("{}", output);
+    return Ok(());
+}
+
+fn subcommand_lint(matches: &clap::ArgMatches) -> anyhow::Result<()> {
+    let format = matches.value_of("format").unwrap_or("text");
+    let database_project = project::load()?;
+    let (output, errors) = lint::lint(&database_project, format, matches.is_present("database"))?;
+    print!("{}", output);
+    if errors > 0 {
+        bail!("lint error: {} problems with error severity found", errors);
+    }
+    return Ok(());
+}
+
+fn subcommand_migration(matches: &clap::ArgMatches, started: Instant) -> anyhow::Result<()> {
+    match matches.subcommand() {
+        Some(("generate", subcommand_matches)) => {
+            set_output(subcommand_matches);
+            let result = utils::validate_environment().and_then(|_| subcommand_migration_generate(subcommand_matches));
+            finish_command(result, started, subcommand_matches)?;
+        },
+        _ => {
+            println!("Unknown migration command, try wo migration generate <name>");
+        }
+    }
+    return Ok(());
+}
+
+fn subcommand_migration_generate(matches: &clap::ArgMatches) -> anyhow::Result<()> {
+    let name = matches.value_of("name")
+        .ok_or(anyhow!("migration name is required"))?;
     let database_project = project::load()?;
-    database::migrate(database_project)?;
+    database::generate_migration(database_project, name)?;
     return Ok(());
 }
 
 fn subcommand_drop(matches: &clap::ArgMatches) -> anyhow::Result<()> {
     if !matches.is_present("no-joke") {
-        println!("Are you sure? Try with --no-joke argument");
+        output::info("Are you sure? Try with --no-joke argument");
     } else {
         let database_project = project::load()?;
         database::drop(database_project)?;
@@ -0,0 +1,404 @@
+use std::error::Error;
+use std::path::Path;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+use std::sync::Mutex;
+use std::time::Instant;
+use postgres;
+use serde_json;
+use crate::secrets;
+
+#[cfg(test)]
+mod tests;
+
+
+static JSON: AtomicBool = AtomicBool::new(false);
+static LEVEL: AtomicUsize = AtomicUsize::new(Level::Normal as usize);
+// object of the running action, used to attribute sql and notices
+static CURRENT_OBJECT: Mutex<Option<String>> = Mutex::new(None);
+// actions which executed sql successfully
+static CHANGES: AtomicUsize = AtomicUsize::new(0);
+static FAILED_ACTIONS: AtomicUsize = AtomicUsize::new(0);
+
+// exit code of successful command which did not change anything (when --detailed-exit-code is provided)
+pub const EXIT_NOTHING_TO_DO: i32 = 0;
+// exit code of failed command
+pub const EXIT_FAILED: i32 = 1;
+// exit code of successful command which changed database or project (when --detailed-exit-code is provided)
+pub const EXIT_CHANGES_APPLIED: i32 = 2;
+
+
+/* verbosity selected by -q, -v and -vv */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level {
+    // warnings and errors only
+    Quiet = 0,
+    Normal = 1,
+    // action durations and internal messages
+    Verbose = 2,
+    // every executed sql statement with its duration
+    Trace = 3,
+}
+
+impl Level {
+    /* level from number of -v flags, -q wins */
+    pub fn from_flags(quiet: bool, verbose: u64) -> Level {
+        if quiet {
+            return Level::Quiet;
+        }
+        match verbose {
+            0 => Level::Normal,
+            1 => Level::Verbose,
+            _ => Level::Trace,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+    NothingToDo,
+    ChangesApplied,
+    Failed,
+}
+
+impl From<&Status> for String {
+    fn from(s: &Status) -> Self {
+        match s {
+            Status::NothingToDo => "nothing_to_do".into(),
+            Status::ChangesApplied => "changes_applied".into(),
+            Status::Failed => "failed".into(),
+        }
+    }
+}
+
+impl Status {
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Status::NothingToDo => EXIT_NOTHING_TO_DO,
+            Status::ChangesApplied => EXIT_CHANGES_APPLIED,
+            Status::Failed => EXIT_FAILED,
+        }
+    }
+}
+
+pub fn set_json(json: bool) {
+    JSON.store(json, Ordering::SeqCst);
+}
+
+pub fn is_json() -> bool {
+    return JSON.load(Ordering::SeqCst);
+}
+
+pub fn set_level(level: Level) {
+    LEVEL.store(level as usize, Ordering::SeqCst);
+}
+
+pub fn is_enabled(level: Level) -> bool {
+    return LEVEL.load(Ordering::SeqCst) >= level as usize;
+}
+
+fn get_current_object() -> Option<String> {
+    return CURRENT_OBJECT.lock().map(|o| o.clone()).unwrap_or(None);
+}
+
+fn set_current_object(object_id: Option<String>) {
+    if let Ok(mut current) = CURRENT_OBJECT.lock() {
+        *current = object_id;
+    }
+}
+
+/* all output goes through here so that secret values are masked */
+fn print(text: &str) {
+    println!("{}", secrets::mask(text));
+}
+
+fn print_event(value: serde_json::Value) {
+    print(&value.to_string());
+}
+
+/* informational message, printed as is in text mode */
+pub fn info(message: &str) {
+    if !is_enabled(Level::Normal) {
+        return;
+    }
+    if is_json() {
+        print_event(serde_json::json!({ "type": "message", "level": "info", "message": message }));
+    } else {
+        print(message);
+    }
+}
+
+/* message printed only with -v */
+pub fn debug(message: &str) {
+    if !is_enabled(Level::Verbose) {
+        return;
+    }
+    if is_json() {
+        print_event(serde_json::json!({ "type": "message", "level": "debug", "message": message }));
+    } else {
+        print(message);
+    }
+}
+
+/* warning message, printed with "warning: " prefix in text mode */
+pub fn warning(message: &str) {
+    if is_json() {
+        print_event(serde_json::json!({ "type": "message", "level": "warning", "message": message }));
+    } else {
+        print(&format!("warning: {}", message));
+    }
+}
+
+/* action performed on object (create, drop, migration, etc.), reported as failed unless finish is called */
+pub struct Action {
+    action: String,
+    object_id: String,
+    sql: Vec<String>,
+    started: Instant,
+    succeeded: bool,
+}
+
+impl Action {
+    /* prints "<action> <object id>" in text mode */
+    pub fn start(action: &str, object_id: &str) -> Action {
+        if !is_json() && is_enabled(Level::Normal) {
+            print(&format!("{} {:?}", action, object_id));
+        }
+        set_current_object(Some(object_id.into()));
+        return Action {
+            action: action.into(),
+            object_id: object_id.into(),
+            sql: vec![],
+            started: Instant::now(),
+            succeeded: false,
+        };
+    }
+
+    /* records sql which is about to be executed */
+    pub fn sql(&mut self, sql: &str) {
+        self.sql.push(sql.trim().into());
+    }
+
+    pub fn finish(mut self) {
+        self.succeeded = true;
+    }
+
+    fn to_json(&self) -> serde_json::Value {
+        return serde_json::json!({
+            "type": "action",
+            "action": self.action,
+            "object_id": self.object_id,
+            "sql": self.sql,
+            "duration_ms": self.started.elapsed().as_secs_f64() * 1000.0,
+            "outcome": if self.succeeded { "ok" } else { "failed" },
+        });
+    }
+}
+
+impl Drop for Action {
+    fn drop(&mut self) {
+        if !self.succeeded {
+            FAILED_ACTIONS.fetch_add(1, Ordering::SeqCst);
+        } else if !self.sql.is_empty() {
+            CHANGES.fetch_add(1, Ordering::SeqCst);
+        }
+        set_current_object(None);
+        if is_json() {
+            print_event(self.to_json());
+        } else if is_enabled(Level::Verbose) {
+            print(&format!("{} {:?} {} ({:.3} ms)",
+                self.action,
+                self.object_id,
+                if self.succeeded { "ok" } else { "failed" },
+                self.started.elapsed().as_secs_f64() * 1000.0,
+            ));
+        }
+    }
+}
+
+/* echoes executed sql statement with its duration (-vv) */
+pub fn sql(sql: &str, started: Instant) {
+    if !is_enabled(Level::Trace) {
+        return;
+    }
+    let duration_ms = started.elapsed().as_secs_f64() * 1000.0;
+    if is_json() {
+        print_event(serde_json::json!({
+            "type": "sql",
+            "object_id": get_current_object(),
+            "sql": sql.trim(),
+            "duration_ms": duration_ms,
+        }));
+    } else {
+        print(&format!("{}\n-- {:.3} ms", sql.trim(), duration_ms));
+    }
+}
+
+/* notice callback of postgres connections, notices raised outside of object actions (e.g. while creating wo tables) are printed only with -v */
+pub fn notice(notice: postgres::error::DbError) {
+    let object_id = get_current_object();
+    let is_warning = notice.parsed_severity() == Some(postgres::error::Severity::Warning);
+    let level = if is_warning {
+        Level::Quiet
+    } else if object_id.is_some() {
+        Level::Normal
+    } else {
+        Level::Verbose
+    };
+    if !is_enabled(level) {
+        return;
+    }
+    if is_json() {
+        print_event(serde_json::json!({
+            "type": "notice",
+            "severity": notice.severity(),
+            "object_id": object_id,
+            "message": notice.message(),
+            "detail": notice.detail(),
+            "hint": notice.hint(),
+        }));
+    } else {
+        let mut text = match object_id {
+            Some(object_id) => format!("{} {:?}: {}", notice.severity().to_lowercase(), object_id, notice.message()),
+            None => format!("{}: {}", notice.severity().to_lowercase(), notice.message()),
+        };
+        if let Some(detail) = notice.detail() {
+            text = format!("{}\n    detail: {}", text, detail);
+        }
+        if let Some(hint) = notice.hint() {
+            text = format!("{}\n    hint: {}", text, hint);
+        }
+        print(&text);
+    }
+}
+
+/* marks command as changing something without executing sql (e.g. writing a file) */
+pub fn change() {
+    CHANGES.fetch_add(1, Ordering::SeqCst);
+}
+
+/* status of the finished command, prints error and summary events in json mode */
+pub fn finish<T>(result: &anyhow::Result<T>, started: Instant) -> Status {
+    let changes = CHANGES.load(Ordering::SeqCst);
+    let status = match result {
+        Err(_) => Status::Failed,
+        Ok(_) if changes > 0 => Status::ChangesApplied,
+        Ok(_) => Status::NothingToDo,
+    };
+    if is_json() {
+        if let Err(e) = result {
+            print_event(serde_json::json!({ "type": "error", "message": format!("{:#}", e) }));
+        }
+        print_event(serde_json::json!({
+            "type": "summary",
+            "status": String::from(&status),
+            "changes": changes,
+            "failed_actions": FAILED_ACTIONS.load(Ordering::SeqCst),
+            "duration_ms": started.elapsed().as_secs_f64() * 1000.0,
+        }));
+    }
+    return status;
+}
+
+
+/* project script executed as script[offset..], line_offset is the line of the script start in the file */
+pub struct ScriptLocation<'t> {
+    pub path_buf: &'t Path,
+    pub script: &'t str,
+    pub offset: usize,
+    pub line_offset: usize,
+}
+
+/* 1-based line and column of the character position (1-based, as reported by postgres) in text */
+pub fn get_line_column(text: &str, position: usize) -> (usize, usize) {
+    let mut line = 1;
+    let mut column = 1;
+    for c in text.chars().take(position.saturating_sub(1)) {
+        if c == '\n' {
+            line += 1;
+            column = 1;
+        } else {
+            column += 1;
+        }
+    }
+    return (line, column);
+}
+
+/* source line with a caret under the column */
+fn get_snippet(line_number: usize, line: &str, column: usize) -> String {
+    let gutter = line_number.to_string().len();
+    let caret_offset: String = line.chars().take(column - 1)
+        .map(|c| if c == '\t' { '\t' } else { ' ' })
+        .collect();
+    return format!("{:w$} |\n{} | {}\n{:w$} | {}^",
+        "",
+        line_number,
+        line.trim_end(),
+        "",
+        caret_offset,
+        w = gutter,
+    );
+}
+
+/* location lines of the error position in executed script, file line and column are reported only when the line
+was executed as written in the file, otherwise substituted placeholders could shift the column */
+fn get_position_lines(location: &ScriptLocation, position: usize, file_text: Option<&str>) -> Vec<String> {
+    let prefix = &location.script[..location.offset];
+    let (line, column) = get_line_column(&location.script[location.offset..], position);
+    // executed text could start in the middle of a line
+    let line_start = prefix.rfind('\n').map(|i| i + 1).unwrap_or(0);
+    let column = if line == 1 { prefix[line_start..].chars().count() + column } else { column };
+    let line = prefix.matches('\n').count() + line;
+    let script_line = location.script.lines().nth(line - 1).unwrap_or("");
+    let file_line = file_text.and_then(|text| text.lines().nth(location.line_offset + line - 1));
+    if file_line == Some(script_line) {
+        return vec![
+            format!("  --> {}:{}:{}", location.path_buf.display(), location.line_offset + line, column),
+            get_snippet(location.line_offset + line, script_line, column),
+        ];
+    }
+    return vec![
+        format!("  --> {}", location.path_buf.display()),
+        "  = rendered sql:".into(),
+        get_snippet(line, script_line, column),
+    ];
+}
+
+/* compiler-like description of postgres error (sqlstate, message, location with snippet, detail, hint, where),
+none if error was not raised by the server */
+pub fn describe_db_error(error: &postgres::Error, location: Option<&ScriptLocation>) -> Option<String> {
+    let db_error = error.source()?.downcast_ref::<postgres::error::DbError>()?;
+    let mut lines = vec![format!("{}[{}]: {}", db_error.severity().to_lowercase(), db_error.code().code(), db_error.message())];
+
+    match (db_error.position(), location) {
+        (Some(postgres::error::ErrorPosition::Original(position)), Some(location)) => {
+            let file_text = std::fs::read_to_string(location.path_buf).ok();
+            lines.extend(get_position_lines(location, *position as usize, file_text.as_deref()));
+        },
+        (Some(postgres::error::ErrorPosition::Internal { position, query }), location) => {
+            if let Some(location) = location {
+                lines.push(format!("  --> {}", location.path_buf.display()));
+            }
+            let (line, column) = get_line_column(query, *position as usize);
+            let query_line = query.lines().nth(line - 1).unwrap_or("");
+            lines.push("  = internal query:".into());
+            lines.push(get_snippet(line, query_line, column));
+        },
+        (_, Some(location)) => {
+            lines.push(format!("  --> {}", location.path_buf.display()));
+        },
+        (_, None) => {},
+    }
+
+    if let Some(detail) = db_error.detail() {
+        lines.push(format!("  = detail: {}", detail));
+    }
+    if let Some(hint) = db_error.hint() {
+        lines.push(format!("  = hint: {}", hint));
+    }
+    if let Some(where_) = db_error.where_() {
+        lines.push(format!("  = where: {}", where_.replace('\n', "\n           ")));
+    }
+    return Some(lines.join("\n"));
+}
@@ -0,0 +1,80 @@
+use anyhow;
+use super::*;
+
+
+#[test]
+fn test_status() -> anyhow::Result<()> {
+    assert_eq!(Status::NothingToDo.exit_code(), 0);
+    assert_eq!(Status::Failed.exit_code(), 1);
+    assert_eq!(Status::ChangesApplied.exit_code(), 2);
+    assert_eq!(String::from(&Status::ChangesApplied), "changes_applied");
+    assert_eq!(String::from(&Status::NothingToDo), "nothing_to_do");
+    return Ok(());
+}
+
+#[test]
+fn test_action_to_json() -> anyhow::Result<()> {
+    let mut action = Action::start("create", "view.public.users_view");
+    action.sql("  create view public.users_view as select 1;\n");
+    let value = action.to_json();
+    assert_eq!(value["type"], "action");
+    assert_eq!(value["action"], "create");
+    assert_eq!(value["object_id"], "view.public.users_view");
+    assert_eq!(value["sql"], serde_json::json!(["create view public.users_view as select 1;"]));
+    assert_eq!(value["outcome"], "failed");
+    assert_eq!(value["duration_ms"].is_f64(), true);
+    action.finish();
+    return Ok(());
+}
+
+#[test]
+fn test_level_from_flags() -> anyhow::Result<()> {
+    assert_eq!(Level::from_flags(false, 0), Level::Normal);
+    assert_eq!(Level::from_flags(false, 1), Level::Verbose);
+    assert_eq!(Level::from_flags(false, 2), Level::Trace);
+    assert_eq!(Level::from_flags(false, 5), Level::Trace);
+    assert_eq!(Level::from_flags(true, 2), Level::Quiet);
+    assert_eq!(Level::Quiet < Level::Normal, true);
+    return Ok(());
+}
+
+#[test]
+fn test_get_line_column() -> anyhow::Result<()> {
+    let text = "create view v1 as\n    select id, nam from t0;";
+    assert_eq!(get_line_column(text, 1), (1, 1));
+    assert_eq!(get_line_column(text, 8), (1, 8));
+    assert_eq!(get_line_column(text, 34), (2, 16));
+    assert_eq!(get_line_column("select 'ё', nam;", 13), (1, 13));
+    return Ok(());
+}
+
+#[test]
+fn test_get_snippet() -> anyhow::Result<()> {
+    assert_eq!(get_snippet(3, "    select id, nam from t0;", 16), "  |\n3 |     select id, nam from t0;\n  |                ^");
+    assert_eq!(get_snippet(12, "\tselect nam;", 9), "   |\n12 | \tselect nam;\n   | \t       ^");
+    return Ok(());
+}
+
+#[test]
+fn test_get_position_lines() -> anyhow::Result<()> {
+    let path_buf = std::path::PathBuf::from("views/public.v1.sql");
+    let file_text = "-- wo:ignore-dependency table.public.t0\ncreate view v1 as\n    select id, nam from t0;\n";
+    let script = "create view v1 as\n    select id, nam from t0;\n";
+    let location = ScriptLocation { path_buf: &path_buf, script, offset: 0, line_offset: 1 };
+    assert_eq!(get_position_lines(&location, 34, Some(file_text)), vec![
+        "  --> views/public.v1.sql:3:16".to_string(),
+        "  |\n3 |     select id, nam from t0;\n  |                ^".to_string(),
+    ]);
+
+    // placeholder substituted earlier on the line shifts the column
+    let file_text = "create view v1 as\n    select {app_id}, nam from t0;\n";
+    let script = "create view v1 as\n    select id, nam from t0;\n";
+    let location = ScriptLocation { path_buf: &path_buf, script, offset: 0, line_offset: 0 };
+    assert_eq!(get_position_lines(&location, 34, Some(file_text)), vec![
+        "  --> views/public.v1.sql".to_string(),
+        "  = rendered sql:".to_string(),
+        "  |\n2 |     select id, nam from t0;\n  |                ^".to_string(),
+    ]);
+    assert_eq!(get_position_lines(&location, 34, None)[0], "  --> views/public.v1.sql");
+    return Ok(());
+}
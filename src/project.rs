@@ -12,8 +12,14 @@ use anyhow::Context;
 use md5::Md5;
 use md5::Digest;
 use hex;
+use toml;
 use postgres;
 use crate::utils;
+use crate::sql;
+use crate::graph;
+use crate::output;
+use crate::template;
+use crate::secrets;
 
 
 #[cfg(test)]
@@ -31,6 +37,17 @@ fn get_role_prefix() -> anyhow::Result<String> {
     return Ok(role_prefix);
 }
 
+/* optional profile selecting per-environment variables of the project config */
+fn get_profile() -> Option<String> {
+    return utils::read_env_var("WO_PROFILE").ok().filter(|p| !p.is_empty());
+}
+
+/* template variables of the project config for the current profile */
+fn load_variables(config: &toml::Value) -> anyhow::Result<template::Variables> {
+    let variables = template::load_variables(config, get_profile().as_deref(), &get_role_prefix()?)?;
+    return Ok(variables);
+}
+
 fn get_create_script_00() -> (String, String) {
     let filename = String::from("00-create-role.sql");
     let content = "
@@ -39,10 +56,11 @@ fn get_create_script_00() -> (String, String) {
 -- -- role_name
 -- -- password
 -- parameters are taken from WO_CONNECTION_STRING env variable
--- parameters are validated to contain only alphanum cahracters and underscores
-CREATE ROLE \"{role_name}\"
+-- use {param:ident} for identifiers and {param:literal} for strings,
+-- unquoted {param} is validated to contain only alphanum characters and underscores
+CREATE ROLE {role_name:ident}
 WITH
-LOGIN PASSWORD '{password}'
+LOGIN PASSWORD {password:literal}
 SUPERUSER;
 ".into();
     return (filename, content);
@@ -56,10 +74,11 @@ fn get_create_script_01() -> (String, String) {
 -- -- role_name
 -- -- password
 -- parameters are taken from WO_CONNECTION_STRING env variable
--- parameters are validated to contain only alphanum cahracters and underscores
-CREATE DATABASE \"{database_name}\"
+-- use {param:ident} for identifiers and {param:literal} for strings,
+-- unquoted {param} is validated to contain only alphanum characters and underscores
+CREATE DATABASE {database_name:ident}
 WITH
-OWNER = \"{role_name}\"
+OWNER = {role_name:ident}
 TEMPLATE = template0
 ENCODING = 'UTF8'
 LC_COLLATE = 'en_US.UTF-8'
@@ -78,8 +97,9 @@ fn get_drop_script_00() -> (String, String) {
 -- -- role_name
 -- -- password
 -- parameters are taken from WO_CONNECTION_STRING env variable
--- parameters are validated to contain only alphanum cahracters and underscores
-DROP DATABASE IF EXISTS \"{database_name}\" WITH (FORCE);
+-- use {param:ident} for identifiers and {param:literal} for strings,
+-- unquoted {param} is validated to contain only alphanum characters and underscores
+DROP DATABASE IF EXISTS {database_name:ident} WITH (FORCE);
 ".into();
     return (filename, content);
 }
@@ -92,8 +112,9 @@ fn get_drop_script_01() -> (String, String) {
 -- -- role_name
 -- -- password
 -- parameters are taken from WO_CONNECTION_STRING env variable
--- parameters are validated to contain only alphanum cahracters and underscores
-DROP ROLE IF EXISTS \"{role_name}\";
+-- use {param:ident} for identifiers and {param:literal} for strings,
+-- unquoted {param} is validated to contain only alphanum characters and underscores
+DROP ROLE IF EXISTS {role_name:ident};
 ".into();
     return (filename, content);
 }
@@ -110,7 +131,7 @@ pub fn init() -> anyhow::Result<()> {
         .context("init error: failed to get project path")?;
     
     if project_path.exists() {
-        println!("project directory already exists at {:?}", project_path);
+        output::info(&format!("project directory already exists at {:?}", project_path));
         return Ok(());
     }
 
@@ -128,6 +149,14 @@ pub fn init() -> anyhow::Result<()> {
     fs::create_dir(project_path.join("policies"))?;
     fs::create_dir(project_path.join("extensions"))?;
     fs::create_dir(project_path.join("types"))?;
+    fs::create_dir(project_path.join("event_triggers"))?;
+    fs::create_dir(project_path.join("rules"))?;
+    fs::create_dir(project_path.join("publications"))?;
+    fs::create_dir(project_path.join("foreign_servers"))?;
+    fs::create_dir(project_path.join("user_mappings"))?;
+    fs::create_dir(project_path.join("foreign_tables"))?;
+    fs::create_dir(project_path.join("comments"))?;
+    fs::create_dir(project_path.join("grants"))?;
 
 
     {
@@ -161,78 +190,341 @@ pub fn init() -> anyhow::Result<()> {
     }
 
     
+    output::info(&format!("created {:?}", project_path));
+    output::change();
     return Ok(());
 }
 
+fn get_object_dir(object_type: &DatabaseObjectType) -> &'static str {
+    match object_type {
+        DatabaseObjectType::Table => "tables",
+        DatabaseObjectType::View => "views",
+        DatabaseObjectType::Function => "functions",
+        DatabaseObjectType::Constraint => "constraints",
+        DatabaseObjectType::Role => "roles",
+        DatabaseObjectType::Trigger => "triggers",
+        DatabaseObjectType::Schema => "schemas",
+        DatabaseObjectType::Policy => "policies",
+        DatabaseObjectType::Extension => "extensions",
+        DatabaseObjectType::Type => "types",
+        DatabaseObjectType::EventTrigger => "event_triggers",
+        DatabaseObjectType::Rule => "rules",
+        DatabaseObjectType::Publication => "publications",
+        DatabaseObjectType::ForeignServer => "foreign_servers",
+        DatabaseObjectType::UserMapping => "user_mappings",
+        DatabaseObjectType::ForeignTable => "foreign_tables",
+    }
+}
+
+/* object id of a new object file, the same validation as for existing files is applied */
+fn new_object_id(object_type: &DatabaseObjectType, name: &str) -> anyhow::Result<String> {
+    if name.len() == 0 || name.contains('/') || name.contains('\\') {
+        bail!("object name should not be empty or contain path separators {:?}", name);
+    }
+    let object_id = format!("{}.{}", String::from(object_type), normalize_function_signature(&name.to_lowercase())?);
+    validate_object_id(&object_id)?;
+    if split_id(&object_id).iter().any(|part| part.len() == 0) {
+        bail!("object name parts should not be empty {:?}", name);
+    }
+    return Ok(object_id);
+}
+
+/* template script of a new object, names are taken as they were typed */
+fn get_object_template(object_type: &DatabaseObjectType, name: &str) -> String {
+    let parts: Vec<&str> = split_id(name);
+    let last = parts[parts.len() - 1];
+    let function_name = match last.find('(') {
+        Some(i) => &last[..i],
+        None => last,
+    };
+    match object_type {
+        DatabaseObjectType::Table => format!("create table {} (\n    id bigserial primary key\n);\n", name),
+        DatabaseObjectType::View => format!("create or replace view {} as\nselect 1 as id;\n", name),
+        DatabaseObjectType::Function => {
+            let arguments = match last.find('(') {
+                Some(i) => &last[i..],
+                None => "()",
+            };
+            format!(
+                "create or replace function {}.{}{} returns void as $$\nbegin\nend;\n$$ language plpgsql;\n",
+                parts[0], function_name, arguments
+            )
+        },
+        DatabaseObjectType::Constraint => format!(
+            "alter table {}.{} add constraint {} check (true);\n",
+            parts[0], parts[1], last
+        ),
+        DatabaseObjectType::Trigger => format!(
+            "create trigger {} before update on {}.{}\nfor each row execute function {}.{}();\n",
+            last, parts[0], parts[1], parts[0], last
+        ),
+        DatabaseObjectType::Policy => format!(
+            "create policy {} on {}.{}\nfor select\nusing (true);\n",
+            last, parts[0], parts[1]
+        ),
+        DatabaseObjectType::Role => format!("create role {{wo_role_prefix}}{};\n", name),
+        DatabaseObjectType::Schema => format!("create schema {};\n", name),
+        DatabaseObjectType::Extension => format!("create extension {};\n", name),
+        DatabaseObjectType::Type => format!("create type {} as enum ();\n", name),
+        DatabaseObjectType::EventTrigger => format!(
+            "create event trigger {} on ddl_command_end\nexecute function public.{}();\n",
+            name, name
+        ),
+        DatabaseObjectType::Rule => format!(
+            "create rule {} as on insert to {}.{}\ndo instead nothing;\n",
+            last, parts[0], parts[1]
+        ),
+        DatabaseObjectType::Publication => format!("create publication {};\n", name),
+        DatabaseObjectType::ForeignServer => format!(
+            "create server {} foreign data wrapper postgres_fdw\noptions (host 'localhost', dbname 'postgres');\n",
+            name
+        ),
+        DatabaseObjectType::UserMapping => format!(
+            "create user mapping for {} server {}\noptions (user {{env:{}_USER:literal}}, password {{env:{}_PASSWORD:literal}});\n",
+            last, parts[0], parts[0].to_uppercase(), parts[0].to_uppercase()
+        ),
+        DatabaseObjectType::ForeignTable => format!(
+            "create foreign table {} (\n    id bigint\n) server server_name;\n",
+            name
+        ),
+    }
+}
+
+/* creates a new object or migration file with a template script */
+pub fn new_object(object_type_str: &str, name: &str) -> anyhow::Result<PathBuf> {
+    let project_path = get_project_path()
+        .context("new_object error: failed to get project path")?;
+    if !project_path.exists() {
+        bail!("new_object error: project does not exist, run wo init first {:?}", project_path);
+    }
+
+    if object_type_str == "migration" {
+        if name.len() == 0 || !name.chars().all(|c| c.is_alphanumeric() || c == '_' || c == '-') {
+            bail!("new_object error: migration name can only have alphanumeric characters, underscores and dashes {:?}", name);
+        }
+        let migration_id = format!("{}_{}.sql", utils::get_timestamp()?, name);
+        let path_buf = project_path.join("migrations");
+        for p in utils::list_files(&path_buf)? {
+            let existing_id = migration_id_from_path(&p)?;
+            if existing_id >= migration_id {
+                bail!("new_object error: existing migration would be executed after the new one {:?}", existing_id);
+            }
+        }
+        let path_buf = path_buf.join(&migration_id);
+        utils::write_file(&path_buf, &format!("-- migration {}\n", name))?;
+        output::info(&format!("created {:?}", path_buf));
+        output::change();
+        return Ok(path_buf);
+    }
+
+    let object_type = DatabaseObjectType::from_str(object_type_str)
+        .context("new_object error: unknown object type, expected one of: table, view, function, constraint, role, \
+        trigger, schema, policy, extension, type, event_trigger, rule, publication, foreign_server, user_mapping, \
+        foreign_table, migration")?;
+    let object_id = new_object_id(&object_type, name)
+        .context(format!("new_object error: invalid {} name {:?}", object_type_str, name))?;
+
+    let path_buf = project_path.join(get_object_dir(&object_type));
+    fs::create_dir_all(&path_buf)?;
+    for p in utils::list_files(&path_buf)? {
+        let existing_id = object_id_from_path(&p, &object_type);
+        if let Ok(existing_id) = existing_id {
+            let existing_id = existing_id.replacen(&format!("role.{}", get_role_prefix()?), "role.", 1);
+            if existing_id == object_id {
+                bail!("new_object error: object already exists {:?}", p);
+            }
+        }
+    }
+
+    let path_buf = path_buf.join(format!("{}.sql", name));
+    utils::write_file(&path_buf, &get_object_template(&object_type, name))?;
+    output::info(&format!("created {:?}", path_buf));
+    output::change();
+    return Ok(path_buf);
+}
+
+/* function ids may end with a signature which can contain dots itself */
+fn split_id(id: &str) -> Vec<&str> {
+    match id.find('(') {
+        Some(i) => {
+            let mut result: Vec<&str> = id[..i].split('.').collect();
+            let last_len = result.pop().unwrap_or("").len();
+            result.push(&id[i - last_len..]);
+            return result;
+        },
+        None => return id.split('.').collect(),
+    }
+}
+
 fn validate_object_id(id: &str) -> anyhow::Result<()> {
     let object_type = get_object_type(id)
         .context(format!("could not parse type from object id {:?}", id))?;
+
+    if id.contains('(') {
+        if object_type != DatabaseObjectType::Function {
+            bail!("only function ids can contain argument signature {:?}", id);
+        }
+        if !id.ends_with(')') {
+            bail!("function signature should be enclosed in parentheses {:?}", id);
+        }
+    }
     
     match object_type {
         DatabaseObjectType::Constraint => {
-            let id_parts: Vec<&str> = id.split('.').collect();
+            let id_parts: Vec<&str> = split_id(id);
             if id_parts.len() != 4 {
                 bail!("constraint filename format shoud be <schema>.<table>.<name> {:?}", id);
             }
         },
         DatabaseObjectType::Trigger => {
-            let id_parts: Vec<&str> = id.split('.').collect();
+            let id_parts: Vec<&str> = split_id(id);
             if id_parts.len() != 4 {
                 bail!("trigger filename format shoud be <schema>.<table>.<name> {:?}", id);
             }
         },
         DatabaseObjectType::Policy => {
-            let id_parts: Vec<&str> = id.split('.').collect();
+            let id_parts: Vec<&str> = split_id(id);
             if id_parts.len() != 4 {
                 bail!("policy filename format shoud be <schema>.<table>.<name> {:?}", id);
             }
         },
         DatabaseObjectType::Table => {
-            let id_parts: Vec<&str> = id.split('.').collect();
+            let id_parts: Vec<&str> = split_id(id);
             if id_parts.len() != 3 {
                 bail!("table filename format shoud be <schema>.<name> {:?}", id);
             }
         },
         DatabaseObjectType::View => {
-            let id_parts: Vec<&str> = id.split('.').collect();
+            let id_parts: Vec<&str> = split_id(id);
             if id_parts.len() != 3 {
                 bail!("view filename format shoud be <schema>.<name> {:?}", id);
             }
         },
         DatabaseObjectType::Function => {
-            let id_parts: Vec<&str> = id.split('.').collect();
+            let id_parts: Vec<&str> = split_id(id);
             if id_parts.len() != 3 {
-                bail!("function filename format shoud be <schema>.<name> {:?}", id);
+                bail!("function filename format shoud be <schema>.<name> or <schema>.<name>(<argument types>) {:?}", id);
             }
         },
         DatabaseObjectType::Role => {
-            let id_parts: Vec<&str> = id.split('.').collect();
+            let id_parts: Vec<&str> = split_id(id);
             if id_parts.len() != 2 {
                 bail!("role filename should not contain dots {:?}", id);
             }
         },
         DatabaseObjectType::Schema => {
-            let id_parts: Vec<&str> = id.split('.').collect();
+            let id_parts: Vec<&str> = split_id(id);
             if id_parts.len() != 2 {
                 bail!("schema filename should not contain dots {:?}", id);
             }
         },
         DatabaseObjectType::Extension => {
-            let id_parts: Vec<&str> = id.split('.').collect();
+            let id_parts: Vec<&str> = split_id(id);
             if id_parts.len() != 2 {
                 bail!("extension filename should not contain dots {:?}", id);
             }
         },
         DatabaseObjectType::Type => {
-            let id_parts: Vec<&str> = id.split('.').collect();
+            let id_parts: Vec<&str> = split_id(id);
             if id_parts.len() != 3 {
                 bail!("type filename format shoud be <schema>.<name> {:?}", id);
             }
         },
+        DatabaseObjectType::EventTrigger => {
+            let id_parts: Vec<&str> = split_id(id);
+            if id_parts.len() != 2 {
+                bail!("event trigger filename should not contain dots {:?}", id);
+            }
+        },
+        DatabaseObjectType::Rule => {
+            let id_parts: Vec<&str> = split_id(id);
+            if id_parts.len() != 4 {
+                bail!("rule filename format shoud be <schema>.<table>.<name> {:?}", id);
+            }
+        },
+        DatabaseObjectType::Publication => {
+            let id_parts: Vec<&str> = split_id(id);
+            if id_parts.len() != 2 {
+                bail!("publication filename should not contain dots {:?}", id);
+            }
+        },
+        DatabaseObjectType::ForeignServer => {
+            let id_parts: Vec<&str> = split_id(id);
+            if id_parts.len() != 2 {
+                bail!("foreign server filename should not contain dots {:?}", id);
+            }
+        },
+        DatabaseObjectType::UserMapping => {
+            let id_parts: Vec<&str> = split_id(id);
+            if id_parts.len() != 3 {
+                bail!("user mapping filename format shoud be <server>.<role> {:?}", id);
+            }
+        },
+        DatabaseObjectType::ForeignTable => {
+            let id_parts: Vec<&str> = split_id(id);
+            if id_parts.len() != 3 {
+                bail!("foreign table filename format shoud be <schema>.<name> {:?}", id);
+            }
+        },
     }
     return Ok(());
 }
 
+/* rewrites "<schema>.<name>(a int, b  int)" into "<schema>.<name>(int,int)" */
+fn normalize_function_signature(name: &str) -> anyhow::Result<String> {
+    let i = match name.find('(') {
+        Some(i) => i,
+        None => return Ok(name.into()),
+    };
+    if !name.ends_with(')') {
+        bail!("function signature should be enclosed in parentheses {:?}", name);
+    }
+    let tokens = sql::tokenize(&name[i + 1..name.len() - 1]);
+    let signature = sql::normalize_signature(&tokens);
+    return Ok(format!("{}({})", &name[..i], signature));
+}
+
+/* splits function script declaring several overloads into separate objects, one per signature */
+fn split_function_script(
+    object_id: &str,
+    script: &str
+) -> anyhow::Result<Vec<(String, String)>> {
+    if get_signature(object_id)?.is_some() {
+        return Ok(vec![(object_id.into(), script.into())]);
+    }
+
+    let schema = get_schema(object_id)?;
+    let name = get_name(object_id)?;
+    let mut overloads: Vec<(usize, String)> = vec![];
+    for statement in sql::split_statements(script) {
+        if let Some(create_function) = sql::parse_create_function(&statement) {
+            let function_schema = create_function.schema.unwrap_or(schema.into());
+            if create_function.name.to_lowercase() == name && function_schema.to_lowercase() == schema {
+                overloads.push((statement.offset, create_function.signature));
+            }
+        }
+    }
+
+    if overloads.len() < 2 {
+        return Ok(vec![(object_id.into(), script.into())]);
+    }
+
+    let mut result: Vec<(String, String)> = vec![];
+    for (i, (offset, signature)) in overloads.iter().enumerate() {
+        let start = if i == 0 { 0 } else { *offset };
+        let end = match overloads.get(i + 1) {
+            Some((next_offset, _)) => *next_offset,
+            None => script.len(),
+        };
+        let overload_id = format!("{}({})", object_id, signature.to_lowercase());
+        if result.iter().any(|(id, _)| *id == overload_id) {
+            bail!("function overload is declared more than once {:?}", overload_id);
+        }
+        result.push((overload_id, script[start..end].into()));
+    }
+    return Ok(result);
+}
+
 fn object_id_from_path(
     path_buf: &PathBuf,
     object_type: &DatabaseObjectType
@@ -250,6 +542,11 @@ fn object_id_from_path(
             bail!("filename for role objects should not be separated by dot, role prefix is specified via env variable");
         }
         object_id = format!("{}{}", role_prefix, filestem_str).to_lowercase();
+    } else if *object_type == DatabaseObjectType::Function {
+        object_id = normalize_function_signature(&filestem_str.to_lowercase())?;
+    } else if *object_type == DatabaseObjectType::UserMapping {
+        let role_prefix = get_role_prefix()?;
+        object_id = filestem_str.replace("{wo_role_prefix}", &role_prefix).to_lowercase();
     } else {
         object_id = filestem_str.to_lowercase();
     }
@@ -261,6 +558,17 @@ fn object_id_from_path(
     return Ok(object_id);
 }
 
+/* project configuration, empty table if the file does not exist */
+fn load_config(path_buf: &PathBuf) -> anyhow::Result<toml::Value> {
+    if !path_buf.exists() {
+        return Ok(toml::Value::Table(toml::value::Table::new()));
+    }
+    let content = utils::read_file(path_buf)?;
+    let config = content.parse::<toml::Value>()
+        .context(format!("load_config error: could not parse {:?}", path_buf))?;
+    return Ok(config);
+}
+
 fn migration_id_from_path(path_buf: &PathBuf) -> anyhow::Result<String> {
     let filename = path_buf.file_name()
         .ok_or(anyhow!("migration_id_from_path error: could not parse filename {:?}", path_buf))?;
@@ -270,69 +578,336 @@ fn migration_id_from_path(path_buf: &PathBuf) -> anyhow::Result<String> {
     return Ok(filename_str.into());
 }
 
-fn prepare_script(
-    script: &str,
-    role_prefix: &str
-) -> String {
-    let result = script.replace("{wo_role_prefix}", role_prefix);
-    return result;
+const DROP_SECTION_MARKER: &str = "-- wo:drop";
+
+/* splits script into create part and optional drop part following "-- wo:drop" line */
+fn split_drop_section(script: &str) -> (String, Option<String>) {
+    let mut offset = 0;
+    for line in script.split_inclusive('\n') {
+        if line.trim().to_lowercase() == DROP_SECTION_MARKER {
+            let drop_script = &script[offset + line.len()..];
+            return (script[..offset].into(), Some(drop_script.into()));
+        }
+        offset += line.len();
+    }
+    return (script.into(), None);
+}
+
+/* "<object>.drop.sql" file is a drop script only if "<object>.sql" exists next to it,
+otherwise it is a script of object named "drop" (e.g. views/public.drop.sql) */
+fn is_drop_script_path(path_buf: &PathBuf) -> bool {
+    let filename = match path_buf.file_name().and_then(|f| f.to_str()) {
+        Some(filename) => filename,
+        None => return false,
+    };
+    if !filename.to_lowercase().ends_with(".drop.sql") {
+        return false;
+    }
+    let object_filename = format!("{}.sql", &filename[..filename.len() - ".drop.sql".len()]);
+    return path_buf.with_file_name(object_filename).exists();
+}
+
+/* drop script of the object from "<object>.drop.sql" file or "-- wo:drop" section */
+fn load_drop_script(
+    path_buf: &PathBuf,
+    script: String,
+    variables: &template::Variables
+) -> anyhow::Result<(String, Option<String>)> {
+    let (script, drop_section) = split_drop_section(&script);
+    let filestem = path_buf.file_stem()
+        .and_then(|f| f.to_str())
+        .ok_or(anyhow!("load_drop_script error: could not parse filename {:?}", path_buf))?;
+    let drop_path_buf = path_buf.with_file_name(format!("{}.drop.sql", filestem));
+    if !drop_path_buf.exists() {
+        return Ok((script, drop_section));
+    }
+    if drop_section.is_some() {
+        bail!("load_drop_script error: drop script is defined both in a separate file and in {} section {:?}", DROP_SECTION_MARKER, path_buf);
+    }
+    let drop_script = utils::read_file(&drop_path_buf)?;
+    return Ok((script, Some(template::render(&drop_script, variables)?)));
+}
+
+/* objects restricted to some environments (profiles) by "-- wo:environments" directive or [environments] of project config */
+struct Environments {
+    profile: Option<String>,
+    // names of profiles declared in project config
+    profiles: HashSet<String>,
+    // object id (function id without signature applies to all overloads) -> environments
+    config: HashMap<String, Vec<String>>,
+    // ids of objects excluded from the current environment
+    excluded: HashSet<String>,
+}
+
+impl Environments {
+    fn load(config: &toml::Value, variables: &template::Variables) -> anyhow::Result<Environments> {
+        let mut result = Environments {
+            profile: get_profile().map(|p| p.to_lowercase()),
+            profiles: HashSet::new(),
+            config: HashMap::new(),
+            excluded: HashSet::new(),
+        };
+        if let Some(profiles) = config.get("profiles") {
+            let profiles = profiles.as_table()
+                .ok_or(anyhow!("load_environments error: profiles has to be a table"))?;
+            result.profiles.extend(profiles.keys().map(|p| p.to_lowercase()));
+        }
+        let environments = match config.get("environments") {
+            Some(environments) => environments.as_table()
+                .ok_or(anyhow!("load_environments error: environments has to be a table"))?,
+            None => return Ok(result),
+        };
+        for (object_id, value) in environments.iter() {
+            let values = value.as_array()
+                .ok_or(anyhow!("load_environments error: environments of {:?} has to be an array", object_id))?;
+            let mut object_environments = vec![];
+            for value in values.iter() {
+                let environment = value.as_str()
+                    .ok_or(anyhow!("load_environments error: environments of {:?} has to be an array of strings", object_id))?;
+                object_environments.push(environment.to_lowercase());
+            }
+            // role ids in config can use {wo_role_prefix} as filenames do
+            let object_id = template::render(object_id, variables)?.to_lowercase();
+            result.validate_names(&object_id, &object_environments)?;
+            result.config.insert(object_id, object_environments);
+        }
+        return Ok(result);
+    }
+
+    /* misspelled environment would exclude the object everywhere, so environments have to be declared profiles */
+    fn validate_names(&self, object_id: &str, environments: &[String]) -> anyhow::Result<()> {
+        for environment in environments.iter() {
+            if !self.profiles.contains(environment) {
+                bail!("load_environments error: environment {:?} of {:?} is not declared as [profiles.{}] in project config",
+                    environment, object_id, environment);
+            }
+        }
+        return Ok(());
+    }
+
+    /* object is declared for the current environment, objects without restrictions are declared for all of them */
+    fn is_included(&self, object_id: &str, script: &str) -> anyhow::Result<bool> {
+        let mut restrictions = vec![];
+        let directive_values = get_directive_values(script, "environments");
+        if !directive_values.is_empty() {
+            self.validate_names(object_id, &directive_values)?;
+            restrictions.push(directive_values);
+        }
+        for (config_id, environments) in self.config.iter() {
+            if object_id == config_id || object_id.starts_with(&format!("{}(", config_id)) {
+                restrictions.push(environments.clone());
+            }
+        }
+        if restrictions.is_empty() {
+            return Ok(true);
+        }
+        // these objects are never dropped when deleted from the project, they are changed by migrations only
+        match get_object_type(object_id)? {
+            DatabaseObjectType::Table |
+            DatabaseObjectType::Schema |
+            DatabaseObjectType::Extension |
+            DatabaseObjectType::Type => {
+                bail!("load_environments error: tables, schemas, extensions and types can not be restricted to environments {:?}", object_id);
+            },
+            _ => {},
+        }
+        // excluding restricted objects without a profile would drop them from any database
+        let profile = self.profile.as_ref()
+            .ok_or(anyhow!("load_environments error: object is restricted to environments, WO_PROFILE has to be set {:?}", object_id))?;
+        return Ok(restrictions.iter().all(|environments| environments.contains(profile)));
+    }
+
+    fn filter(&mut self, object_id: &str, script: &str) -> anyhow::Result<bool> {
+        if self.is_included(object_id, script)? {
+            return Ok(true);
+        }
+        output::debug(&format!("object is excluded from environment {:?} {:?}", self.profile.as_deref().unwrap_or(""), object_id));
+        self.excluded.insert(object_id.into());
+        return Ok(false);
+    }
+
+    /* config entries have to reference existing objects */
+    fn validate(&self, objects_info: &ObjectsInfo) -> anyhow::Result<()> {
+        for config_id in self.config.keys() {
+            let exists = objects_info.keys().chain(self.excluded.iter())
+                .any(|id| id == config_id || id.starts_with(&format!("{}(", config_id)));
+            if !exists {
+                bail!("load_environments error: environments config references unknown object {:?}", config_id);
+            }
+        }
+        return Ok(());
+    }
 }
 
 fn load_objects_info_by_type(
     result: &mut HashMap<String, (DatabaseObjectType, PathBuf, String)>, 
+    drop_scripts: &mut HashMap<String, String>,
+    line_offsets: &mut LineOffsets,
     path_buf: &PathBuf,
-    object_type: &DatabaseObjectType
+    object_type: &DatabaseObjectType,
+    variables: &template::Variables,
+    environments: &mut Environments
 ) -> anyhow::Result<()> {
-    let role_prefix = get_role_prefix()?;
+    // directories of object types added in later versions might be missing in older projects
+    if !path_buf.exists() {
+        return Ok(());
+    }
     let ls_paths = utils::list_files(&path_buf)
         .context(format!("load_objects_info error: failed to list files at {:?}", path_buf))?;
     for ls_path in ls_paths {
+        if is_drop_script_path(&ls_path) {
+            continue;
+        }
         let object_id = object_id_from_path(&ls_path, &object_type)
             .context(format!("load_objects_info error: failed to parse object_id {:?}", ls_path))?;
         let script = utils::read_file(&ls_path)
             .context(format!("load_objects_info error: failed to read file {:?}", ls_path))?;
         
-        let script = prepare_script(&script, &role_prefix);
+        let script = template::render(&script, variables)
+            .context(format!("load_objects_info error: failed to substitute variables {:?}", ls_path))?;
+        let (script, drop_script) = load_drop_script(&ls_path, script, variables)
+            .context(format!("load_objects_info error: failed to load drop script {:?}", ls_path))?;
+        if *object_type == DatabaseObjectType::Function {
+            let overloads = split_function_script(&object_id, &script)?;
+            if overloads.len() > 1 && drop_script.is_some() {
+                bail!("load_objects_info error: drop script is not supported for files declaring several overloads {:?}", ls_path);
+            }
+            for (overload_id, overload_script) in overloads {
+                if result.contains_key(&overload_id) {
+                    bail!("load_objects_info error: function is declared in several files {:?}", overload_id);
+                }
+                // directive applies to all overloads declared in the file
+                if !environments.filter(&overload_id, &script)? {
+                    continue;
+                }
+                if let Some(drop_script) = &drop_script {
+                    drop_scripts.insert(overload_id.clone(), drop_script.clone());
+                }
+                // overloads split from a single file start in the middle of it
+                if let Some(i) = script.find(&overload_script) {
+                    line_offsets.insert(overload_id.clone(), script[..i].matches('\n').count());
+                }
+                result.insert(overload_id, (*object_type, ls_path.clone(), overload_script));
+            }
+            continue;
+        }
+        if !environments.filter(&object_id, &script)? {
+            continue;
+        }
+        if let Some(drop_script) = drop_script {
+            drop_scripts.insert(object_id.clone(), drop_script);
+        }
         result.insert(object_id, (object_type.clone(), ls_path, script));
     }
     return Ok(());
 }
 
 
-fn load_objects_info(project_path: &PathBuf) -> anyhow::Result<HashMap<String, (DatabaseObjectType, PathBuf, String)>> {
+/* scripts from directories which are not required to exist, e.g. comments */
+fn load_optional_scripts(
+    path_buf: &PathBuf,
+    variables: &template::Variables
+) -> anyhow::Result<Vec<(PathBuf, String)>> {
+    let mut result = vec![];
+    if !path_buf.exists() {
+        return Ok(result);
+    }
+    let script_paths = utils::list_files(&path_buf)?;
+    for p in script_paths {
+        let script = utils::read_file(&p)?;
+        let script = template::render(&script, variables)
+            .context(format!("load error: failed to substitute variables {:?}", p))?;
+        result.push((p, script));
+    }
+    return Ok(result);
+}
+
+/* optional scripts are executed on every migration, so they can only contain statements starting with given words */
+fn validate_optional_scripts(
+    scripts: &[(PathBuf, String)],
+    allowed_starts: &[&[&str]],
+    kind: &str
+) -> anyhow::Result<()> {
+    let allowed = allowed_starts.iter().map(|words| words.join(" ")).collect::<Vec<String>>().join(" or ");
+    for (path_buf, script) in scripts.iter() {
+        for statement in sql::split_statements(script) {
+            if allowed_starts.iter().any(|words| statement.starts_with_words(words)) {
+                continue;
+            }
+            let offset = statement.tokens.first().map(|t| t.offset).unwrap_or(statement.offset);
+            let line = script[..offset].matches('\n').count() + 1;
+            bail!("load error: {} scripts can only contain {} statements {:?} line {}", kind, allowed, path_buf, line);
+        }
+    }
+    return Ok(());
+}
+
+// object id -> (type, path, script)
+type ObjectsInfo = HashMap<String, (DatabaseObjectType, PathBuf, String)>;
+// object id -> number of file lines preceding the script
+type LineOffsets = HashMap<String, usize>;
+
+// returns objects info, drop scripts and line offsets of scripts in their files by object id
+fn load_objects_info(
+    project_path: &PathBuf,
+    config: &toml::Value,
+    variables: &template::Variables
+) -> anyhow::Result<(ObjectsInfo, HashMap<String, String>, LineOffsets)> {
     let mut result = HashMap::new();
+    let mut drop_scripts = HashMap::new();
+    let mut line_offsets = HashMap::new();
+    let mut environments = Environments::load(config, variables)?;
 
     let path_buf = project_path.join("tables");
-    load_objects_info_by_type(&mut result, &path_buf, &DatabaseObjectType::Table)?;
+    load_objects_info_by_type(&mut result, &mut drop_scripts, &mut line_offsets, &path_buf, &DatabaseObjectType::Table, variables, &mut environments)?;
 
     let path_buf = project_path.join("views");
-    load_objects_info_by_type(&mut result, &path_buf, &DatabaseObjectType::View)?;
+    load_objects_info_by_type(&mut result, &mut drop_scripts, &mut line_offsets, &path_buf, &DatabaseObjectType::View, variables, &mut environments)?;
 
     let path_buf = project_path.join("functions");
-    load_objects_info_by_type(&mut result, &path_buf, &DatabaseObjectType::Function)?;
+    load_objects_info_by_type(&mut result, &mut drop_scripts, &mut line_offsets, &path_buf, &DatabaseObjectType::Function, variables, &mut environments)?;
 
     let path_buf = project_path.join("constraints");
-    load_objects_info_by_type(&mut result, &path_buf, &DatabaseObjectType::Constraint)?;
+    load_objects_info_by_type(&mut result, &mut drop_scripts, &mut line_offsets, &path_buf, &DatabaseObjectType::Constraint, variables, &mut environments)?;
 
     let path_buf = project_path.join("roles");
-    load_objects_info_by_type(&mut result, &path_buf, &DatabaseObjectType::Role)?;
+    load_objects_info_by_type(&mut result, &mut drop_scripts, &mut line_offsets, &path_buf, &DatabaseObjectType::Role, variables, &mut environments)?;
 
     let path_buf = project_path.join("triggers");
-    load_objects_info_by_type(&mut result, &path_buf, &DatabaseObjectType::Trigger)?;
+    load_objects_info_by_type(&mut result, &mut drop_scripts, &mut line_offsets, &path_buf, &DatabaseObjectType::Trigger, variables, &mut environments)?;
 
     let path_buf = project_path.join("schemas");
-    load_objects_info_by_type(&mut result, &path_buf, &DatabaseObjectType::Schema)?;
+    load_objects_info_by_type(&mut result, &mut drop_scripts, &mut line_offsets, &path_buf, &DatabaseObjectType::Schema, variables, &mut environments)?;
 
     let path_buf = project_path.join("policies");
-    load_objects_info_by_type(&mut result, &path_buf, &DatabaseObjectType::Policy)?;
+    load_objects_info_by_type(&mut result, &mut drop_scripts, &mut line_offsets, &path_buf, &DatabaseObjectType::Policy, variables, &mut environments)?;
 
     let path_buf = project_path.join("extensions");
-    load_objects_info_by_type(&mut result, &path_buf, &DatabaseObjectType::Extension)?;
+    load_objects_info_by_type(&mut result, &mut drop_scripts, &mut line_offsets, &path_buf, &DatabaseObjectType::Extension, variables, &mut environments)?;
 
     let path_buf = project_path.join("types");
-    load_objects_info_by_type(&mut result, &path_buf, &DatabaseObjectType::Type)?;
+    load_objects_info_by_type(&mut result, &mut drop_scripts, &mut line_offsets, &path_buf, &DatabaseObjectType::Type, variables, &mut environments)?;
 
-    return Ok(result);
+    let path_buf = project_path.join("event_triggers");
+    load_objects_info_by_type(&mut result, &mut drop_scripts, &mut line_offsets, &path_buf, &DatabaseObjectType::EventTrigger, variables, &mut environments)?;
+
+    let path_buf = project_path.join("rules");
+    load_objects_info_by_type(&mut result, &mut drop_scripts, &mut line_offsets, &path_buf, &DatabaseObjectType::Rule, variables, &mut environments)?;
+
+    let path_buf = project_path.join("publications");
+    load_objects_info_by_type(&mut result, &mut drop_scripts, &mut line_offsets, &path_buf, &DatabaseObjectType::Publication, variables, &mut environments)?;
+
+    let path_buf = project_path.join("foreign_servers");
+    load_objects_info_by_type(&mut result, &mut drop_scripts, &mut line_offsets, &path_buf, &DatabaseObjectType::ForeignServer, variables, &mut environments)?;
+
+    let path_buf = project_path.join("user_mappings");
+    load_objects_info_by_type(&mut result, &mut drop_scripts, &mut line_offsets, &path_buf, &DatabaseObjectType::UserMapping, variables, &mut environments)?;
+
+    let path_buf = project_path.join("foreign_tables");
+    load_objects_info_by_type(&mut result, &mut drop_scripts, &mut line_offsets, &path_buf, &DatabaseObjectType::ForeignTable, variables, &mut environments)?;
+
+    environments.validate(&result)?;
+    return Ok((result, drop_scripts, line_offsets));
 }
 
 fn get_search_term(
@@ -344,27 +919,40 @@ fn get_search_term(
         DatabaseObjectType::Function |
         DatabaseObjectType::Table |
         DatabaseObjectType::Type |
+        DatabaseObjectType::ForeignTable |
         DatabaseObjectType::View => {
             let schema = get_schema(object_id)?;
             let name = get_name(object_id)?;
             if search_schemas.contains(schema) {
-                return Ok(Some(name.clone().into()));
+                return Ok(Some(name.into()));
             } else {
                 return Ok(Some(format!("{}.{}", schema, name)));
             }
         },
         DatabaseObjectType::Policy |
         DatabaseObjectType::Constraint |
-        DatabaseObjectType::Trigger => return Ok(None),
+        DatabaseObjectType::Trigger |
+        DatabaseObjectType::EventTrigger |
+        DatabaseObjectType::Rule |
+        DatabaseObjectType::Publication |
+        DatabaseObjectType::UserMapping => return Ok(None),
         DatabaseObjectType::Extension |
+        DatabaseObjectType::ForeignServer |
         DatabaseObjectType::Role => {
             let name = get_name(object_id)?;
-            return Ok(Some(name.clone().into()));
+            return Ok(Some(name.into()));
         }
         DatabaseObjectType::Schema => bail!("schema dependencies should be derived from object ids"),
     };
 }
 
+// FIXME configurable search schemas???
+fn get_search_schemas() -> HashSet<String> {
+    let mut search_schemas: HashSet<String> = HashSet::new();
+    search_schemas.insert("public".into());
+    return search_schemas;
+}
+
 fn calc_required_by_for_schema(
     object_id: &str,
     objects_info: &HashMap<String, (DatabaseObjectType, PathBuf, String)>,
@@ -376,6 +964,10 @@ fn calc_required_by_for_schema(
         if *object_type == DatabaseObjectType::Extension
         || *object_type == DatabaseObjectType::Schema 
         || *object_type == DatabaseObjectType::Role
+        || *object_type == DatabaseObjectType::EventTrigger
+        || *object_type == DatabaseObjectType::Publication
+        || *object_type == DatabaseObjectType::ForeignServer
+        || *object_type == DatabaseObjectType::UserMapping
         {
             let contains = utils::contains_whole_word_ci(&script, &schema);
             if contains {
@@ -393,7 +985,7 @@ fn calc_required_by_for_schema(
     return Ok(result);
 }
 
-/*  role objects should only depend on other role objects */
+/*  role objects should only depend on other role objects, user mappings depend on their roles */
 fn calc_required_by_for_role(
     object_id: &str,
     objects_info: &HashMap<String, (DatabaseObjectType, PathBuf, String)>,
@@ -402,6 +994,13 @@ fn calc_required_by_for_role(
     let role_name = get_name(object_id)?;
     for (required_by_object_id, (object_type, _, script)) in objects_info {
 
+        if *object_type == DatabaseObjectType::UserMapping {
+            if get_name(required_by_object_id)? == role_name {
+                result.insert(required_by_object_id.clone());
+            }
+            continue;
+        }
+
         if *object_type != DatabaseObjectType::Role {
             continue;
         }
@@ -440,10 +1039,17 @@ fn calc_required_by_for_object(
     }
 
     let search_term = search_term_opt.unwrap();
-    for (required_by_object_id, (_, _, script)) in objects_info {
+    for (required_by_object_id, (required_by_object_type, _, script)) in objects_info {
         if object_id == required_by_object_id {
             continue;
         }
+        // function overloads share the name and would otherwise depend on each other
+        if object_type == DatabaseObjectType::Function
+        && *required_by_object_type == DatabaseObjectType::Function
+        && get_schema(object_id)? == get_schema(required_by_object_id)?
+        && get_name(object_id)? == get_name(required_by_object_id)? {
+            continue;
+        }
         let contains = utils::contains_whole_word_ci(&script, &search_term);
         if contains {
             result.insert(required_by_object_id.clone());
@@ -464,6 +1070,74 @@ fn calc_required_by(
     return Ok(result);
 }
 
+/* values of "-- wo:<name> value0, value1" comment lines */
+fn get_directive_values(script: &str, name: &str) -> Vec<String> {
+    let prefix = format!("-- wo:{}", name);
+    let mut result = vec![];
+    for line in script.lines() {
+        let line = line.trim();
+        if !line.to_lowercase().starts_with(&prefix) {
+            continue;
+        }
+        let rest = &line[prefix.len()..];
+        if !rest.is_empty() && !rest.starts_with(char::is_whitespace) {
+            continue;
+        }
+        for value in rest.split(|c: char| c == ',' || c.is_whitespace()) {
+            if value.len() > 0 {
+                result.push(value.to_lowercase());
+            }
+        }
+    }
+    return result;
+}
+
+/* ids referenced by a directive, function id without signature refers to all its overloads */
+fn resolve_directive_ids(
+    object_id: &str,
+    directive_id: &str,
+    objects_info: &ObjectsInfo
+) -> anyhow::Result<Vec<String>> {
+    if objects_info.contains_key(directive_id) {
+        if directive_id == object_id {
+            bail!("object can not reference itself in dependency directive {:?}", object_id);
+        }
+        return Ok(vec![directive_id.into()]);
+    }
+    let overload_prefix = format!("{}(", directive_id);
+    let mut result: Vec<String> = objects_info.keys()
+        .filter(|id| id.starts_with(&overload_prefix) && *id != object_id)
+        .cloned()
+        .collect();
+    if result.len() == 0 {
+        bail!("dependency directive of {:?} references unknown object {:?}", object_id, directive_id);
+    }
+    result.sort();
+    return Ok(result);
+}
+
+/* merges "-- wo:depends-on <id>" and "-- wo:ignore-dependency <id>" directives into text based dependencies */
+fn apply_dependency_directives(
+    objects_info: &ObjectsInfo,
+    required_by: &mut HashMap<String, HashSet<String>>
+) -> anyhow::Result<()> {
+    for (object_id, (_, _, script)) in objects_info {
+        for directive_id in get_directive_values(script, "depends-on") {
+            for dependency_id in resolve_directive_ids(object_id, &directive_id, objects_info)? {
+                required_by.entry(dependency_id).or_default().insert(object_id.clone());
+            }
+        }
+        for directive_id in get_directive_values(script, "ignore-dependency") {
+            for dependency_id in resolve_directive_ids(object_id, &directive_id, objects_info)? {
+                if let Some(dependency_required_by) = required_by.get_mut(&dependency_id) {
+                    dependency_required_by.remove(object_id);
+                }
+            }
+        }
+    }
+    return Ok(());
+}
+
 fn calc_depends_on_for_object(object_id: &str, required_by: &HashMap<String, HashSet<String>>) -> HashSet<String> {
     let mut result = HashSet::new();
     for (depends_on_object_id, required_by) in required_by {
@@ -488,6 +1162,8 @@ fn calc_depends_on(required_by: &HashMap<String, HashSet<String>>) -> HashMap<St
 
 fn build_database_objects(
     mut objects_info: HashMap<String, (DatabaseObjectType, PathBuf, String)>,
+    mut drop_scripts: HashMap<String, String>,
+    mut line_offsets: LineOffsets,
     mut required_by: HashMap<String, HashSet<String>>,
     mut depends_on: HashMap<String, HashSet<String>>
 ) -> anyhow::Result<HashMap<String, DatabaseObject>> {
@@ -507,6 +1183,8 @@ fn build_database_objects(
             md5: hash_str,
             depends_on: object_depends_on,
             required_by: object_required_by,
+            drop_script: drop_scripts.remove(&object_id),
+            line_offset: line_offsets.remove(&object_id).unwrap_or(0),
         };
         result.insert(object_id, o);
     }
@@ -518,16 +1196,18 @@ fn resolve_dependencies(
     objects: &HashMap<String, DatabaseObject>,
     dependencies_vec: &mut Vec<String>,
     dependencies_set: &mut HashSet<String>,
-    visited: &mut HashSet<String>,
+    path: &mut Vec<String>,
 ) -> anyhow::Result<()> {
     if dependencies_set.contains(object_id) {
         return Ok(());
     }
 
-    if visited.contains(object_id) {
-        bail!("resolve_dependencies error: cycle detected {:?}", object_id);
+    if let Some(i) = path.iter().position(|id| id == object_id) {
+        let mut cycle = path[i..].to_vec();
+        cycle.push(object_id.clone());
+        bail!("resolve_dependencies error: {}", graph::describe_cycle(objects, &cycle)?);
     }
-    visited.insert(object_id.clone());
+    path.push(object_id.clone());
 
     let object = objects.get(object_id).unwrap();
     let mut new_dependencies_sorted: Vec<&String> = Vec::from_iter(&object.depends_on);
@@ -539,10 +1219,11 @@ fn resolve_dependencies(
             &objects,
             dependencies_vec,
             dependencies_set,
-            visited
+            path
         )?;
     }
 
+    path.pop();
     dependencies_set.insert(object_id.clone());
     dependencies_vec.push(object_id.clone());
     return Ok(());
@@ -568,13 +1249,13 @@ fn calc_create_order(objects: &HashMap<String, DatabaseObject>) -> anyhow::Resul
     objects_sorted.extend(roles_sorted);
 
     for object_id in objects_sorted {
-        let mut visited: HashSet<String> = HashSet::new();
+        let mut path: Vec<String> = vec![];
         resolve_dependencies(
             object_id, 
             objects, 
             &mut dependencies_vec, 
             &mut dependencies_set,
-            &mut visited
+            &mut path
         )?;
     }
 
@@ -593,6 +1274,12 @@ pub enum DatabaseObjectType {
     Policy,
     Extension,
     Type,
+    EventTrigger,
+    Rule,
+    Publication,
+    ForeignServer,
+    UserMapping,
+    ForeignTable,
 }
 
 impl From<&DatabaseObjectType> for String {
@@ -608,6 +1295,12 @@ impl From<&DatabaseObjectType> for String {
             DatabaseObjectType::Policy => "policy".into(),
             DatabaseObjectType::Extension => "extension".into(),
             DatabaseObjectType::Type => "type".into(),
+            DatabaseObjectType::EventTrigger => "event_trigger".into(),
+            DatabaseObjectType::Rule => "rule".into(),
+            DatabaseObjectType::Publication => "publication".into(),
+            DatabaseObjectType::ForeignServer => "foreign_server".into(),
+            DatabaseObjectType::UserMapping => "user_mapping".into(),
+            DatabaseObjectType::ForeignTable => "foreign_table".into(),
         }
     }
 }
@@ -627,6 +1320,12 @@ impl FromStr for DatabaseObjectType {
             "policy" => DatabaseObjectType::Policy,
             "extension" => DatabaseObjectType::Extension,
             "type" => DatabaseObjectType::Type,
+            "event_trigger" => DatabaseObjectType::EventTrigger,
+            "rule" => DatabaseObjectType::Rule,
+            "publication" => DatabaseObjectType::Publication,
+            "foreign_server" => DatabaseObjectType::ForeignServer,
+            "user_mapping" => DatabaseObjectType::UserMapping,
+            "foreign_table" => DatabaseObjectType::ForeignTable,
             _ => bail!("could not convert object type from {:?}", s),
         };
         return Ok(object_type);
@@ -641,6 +1340,10 @@ pub struct DatabaseObject {
     pub md5: String,
     pub depends_on: HashSet<String>,
     pub required_by: HashSet<String>,
+    // user defined script executed instead of generated drop statement
+    pub drop_script: Option<String>,
+    // number of file lines preceding the script, overloads split from a single file start in the middle of it
+    pub line_offset: usize,
 }
 
 fn get_id_part<'t>(
@@ -648,7 +1351,7 @@ fn get_id_part<'t>(
     i: usize
 ) -> anyhow::Result<&'t str> {
     validate_object_id(id)?;
-    let id_parts: Vec<&str> = id.split('.').collect();
+    let id_parts: Vec<&str> = split_id(id);
     return Ok(id_parts[i]);
 }
 
@@ -667,13 +1370,19 @@ pub fn get_schema(id: &str) -> anyhow::Result<&str> {
         DatabaseObjectType::Constraint |
         DatabaseObjectType::Trigger |
         DatabaseObjectType::Policy |
+        DatabaseObjectType::Rule |
         DatabaseObjectType::Table |
         DatabaseObjectType::View |
         DatabaseObjectType::Function |
+        DatabaseObjectType::ForeignTable |
         DatabaseObjectType::Type => get_id_part(id, 1),
         DatabaseObjectType::Role => bail!("role object id is not associated with schema {:?}", id),
         DatabaseObjectType::Schema => bail!("schema object id is not associated with another schema {:?}", id),
         DatabaseObjectType::Extension => bail!("extension object id is not associated with schema {:?}", id),
+        DatabaseObjectType::EventTrigger => bail!("event trigger object id is not associated with schema {:?}", id),
+        DatabaseObjectType::Publication => bail!("publication object id is not associated with schema {:?}", id),
+        DatabaseObjectType::ForeignServer => bail!("foreign server object id is not associated with schema {:?}", id),
+        DatabaseObjectType::UserMapping => bail!("user mapping object id is not associated with schema {:?}", id),
     }
 }
 
@@ -683,7 +1392,8 @@ pub fn get_table(id: &str) -> anyhow::Result<&str> {
     match object_type {
         DatabaseObjectType::Constraint |
         DatabaseObjectType::Trigger |
-        DatabaseObjectType::Policy => get_id_part(id, 2),
+        DatabaseObjectType::Policy |
+        DatabaseObjectType::Rule => get_id_part(id, 2),
         DatabaseObjectType::Table => bail!("table object id is not associated with another table {:?}", id),
         DatabaseObjectType::View => bail!("view object id is not associated with table {:?}", id),
         DatabaseObjectType::Function => bail!("function object id is not associated with table {:?}", id),
@@ -691,6 +1401,11 @@ pub fn get_table(id: &str) -> anyhow::Result<&str> {
         DatabaseObjectType::Schema => bail!("schema object id is not associated with table {:?}", id),
         DatabaseObjectType::Extension => bail!("extension object id is not associated with table {:?}", id),
         DatabaseObjectType::Type => bail!("type object id is not associated with table {:?}", id),
+        DatabaseObjectType::EventTrigger => bail!("event trigger object id is not associated with table {:?}", id),
+        DatabaseObjectType::Publication => bail!("publication object id is not associated with table {:?}", id),
+        DatabaseObjectType::ForeignServer => bail!("foreign server object id is not associated with table {:?}", id),
+        DatabaseObjectType::UserMapping => bail!("user mapping object id is not associated with table {:?}", id),
+        DatabaseObjectType::ForeignTable => bail!("foreign table object id is not associated with another table {:?}", id),
     }
 }
 
@@ -700,17 +1415,50 @@ pub fn get_name(id: &str) -> anyhow::Result<&str> {
     match object_type {
         DatabaseObjectType::Constraint |
         DatabaseObjectType::Trigger |
-        DatabaseObjectType::Policy => get_id_part(id, 3),
+        DatabaseObjectType::Policy |
+        DatabaseObjectType::Rule => get_id_part(id, 3),
         DatabaseObjectType::Table |
         DatabaseObjectType::View |
-        DatabaseObjectType::Type |
-        DatabaseObjectType::Function => get_id_part(id, 2),
+        DatabaseObjectType::ForeignTable |
+        DatabaseObjectType::UserMapping |
+        DatabaseObjectType::Type => get_id_part(id, 2),
+        DatabaseObjectType::Function => {
+            let name = get_id_part(id, 2)?;
+            match name.find('(') {
+                Some(i) => Ok(&name[..i]),
+                None => Ok(name),
+            }
+        },
         DatabaseObjectType::Role |
         DatabaseObjectType::Schema |
-        DatabaseObjectType::Extension => get_id_part(id, 1),
+        DatabaseObjectType::Extension |
+        DatabaseObjectType::EventTrigger |
+        DatabaseObjectType::Publication |
+        DatabaseObjectType::ForeignServer => get_id_part(id, 1),
     }
 }
 
+/* foreign server of user mapping id "user_mapping.<server>.<role>" */
+pub fn get_server(id: &str) -> anyhow::Result<&str> {
+    let object_type = get_object_type(id)?;
+    if object_type != DatabaseObjectType::UserMapping {
+        bail!("only user mapping object ids are associated with foreign server {:?}", id);
+    }
+    return get_id_part(id, 1);
+}
+
+/* argument types of function id "function.<schema>.<name>(<signature>)" */
+pub fn get_signature(id: &str) -> anyhow::Result<Option<&str>> {
+    let object_type = get_object_type(id)?;
+    if object_type != DatabaseObjectType::Function {
+        bail!("only function object ids are associated with signature {:?}", id);
+    }
+    validate_object_id(id)?;
+    match id.find('(') {
+        Some(i) => Ok(Some(&id[i + 1..id.len() - 1])),
+        None => Ok(None),
+    }
+}
 
 impl DatabaseObject {
 
@@ -746,7 +1494,8 @@ impl DatabaseObject {
         // po_script text,
         // po_path text,
         // po_depends_on text[],
-        // po_required_by text[]
+        // po_required_by text[],
+        // po_drop_script text
 
         let po_id: String = row.try_get("po_id")?;
         let po_md5: String = row.try_get("po_md5")?;
@@ -754,6 +1503,7 @@ impl DatabaseObject {
         let po_path: String = row.try_get("po_path")?;
         let po_depends_on: Vec<String> = row.try_get("po_depends_on")?;
         let po_required_by: Vec<String> = row.try_get("po_required_by")?;
+        let po_drop_script: Option<String> = row.try_get("po_drop_script")?;
 
         let path_buf = PathBuf::from(po_path);
         let depends_on = HashSet::from_iter(po_depends_on);
@@ -768,6 +1518,8 @@ impl DatabaseObject {
             md5: po_md5,
             depends_on,
             required_by,
+            drop_script: po_drop_script,
+            line_offset: 0,
         };
 
         return Ok(result);
@@ -780,12 +1532,23 @@ pub struct DatabaseProject {
     pub create_scripts: Vec<(PathBuf, String)>,
     pub drop_scripts: Vec<(PathBuf, String)>,
     pub migration_scripts: Vec<(String, String)>,
+    pub comment_scripts: Vec<(PathBuf, String)>,
+    pub grant_scripts: Vec<(PathBuf, String)>,
     pub objects: HashMap<String, DatabaseObject>,
+    // contents of optional ./wo/wo.toml
+    pub config: toml::Value,
+    // template variables of the current profile
+    pub variables: template::Variables,
 }
 
 impl DatabaseProject {
     fn from_path(project_path: &PathBuf) -> anyhow::Result<DatabaseProject> {
 
+        let config = load_config(&project_path.join("wo.toml"))?;
+        let variables = load_variables(&config)
+            .context("load error: invalid project variables")?;
+        secrets::set_sources(secrets::load_sources(&config, project_path)?);
+
         let path_buf = project_path.join("create");
         let create_script_paths = utils::list_files(&path_buf)?;
         let mut create_scripts = vec![];
@@ -807,25 +1570,39 @@ impl DatabaseProject {
         let mut migration_scripts = vec![];
         for p in migration_script_paths {
             let script = utils::read_file(&p)?;
+            let script = template::render(&script, &variables)
+                .context(format!("load error: failed to substitute variables {:?}", p))?;
             let migration_id = migration_id_from_path(&p)?;
             migration_scripts.push((migration_id, script));
         }
         
-        // FIXME configurable search schemas???
-        let mut search_schemas: HashSet<String> = HashSet::new();
-        search_schemas.insert("public".into());
+        let path_buf = project_path.join("comments");
+        let comment_scripts = load_optional_scripts(&path_buf, &variables)?;
+        validate_optional_scripts(&comment_scripts, &[&["comment", "on"]], "comment")?;
+
+        let path_buf = project_path.join("grants");
+        let grant_scripts = load_optional_scripts(&path_buf, &variables)?;
+        validate_optional_scripts(&grant_scripts, &[&["grant"], &["revoke"]], "grant")?;
+
+        let search_schemas = get_search_schemas();
 
-        let objects_info = load_objects_info(&project_path)?;
-        let required_by = calc_required_by(&objects_info, &search_schemas)?;
+        let (objects_info, object_drop_scripts, line_offsets) = load_objects_info(&project_path, &config, &variables)?;
+        let mut required_by = calc_required_by(&objects_info, &search_schemas)?;
+        apply_dependency_directives(&objects_info, &mut required_by)
+            .context("load error: invalid dependency directive")?;
         let depends_on = calc_depends_on(&required_by);
-        let objects = build_database_objects(objects_info, required_by, depends_on)?;
+        let objects = build_database_objects(objects_info, object_drop_scripts, line_offsets, required_by, depends_on)?;
 
         return Ok(DatabaseProject {
-            project_dirpath: path_buf,
+            project_dirpath: project_path.clone(),
             create_scripts,
             drop_scripts,
             migration_scripts,
+            comment_scripts,
+            grant_scripts,
             objects,
+            config,
+            variables,
         });
     }
 
@@ -842,6 +1619,78 @@ impl DatabaseProject {
     pub fn get_create_order(&self) -> anyhow::Result<Vec<String>> {
         return calc_create_order(&self.objects);
     }
+
+    /* problems of all object scripts, see validate_object_script */
+    pub fn validate_scripts(&self) -> anyhow::Result<Vec<String>> {
+        let mut object_ids = Vec::from_iter(self.objects.keys());
+        object_ids.sort();
+        let mut result = vec![];
+        for object_id in object_ids {
+            result.extend(validate_object_script(&self.objects[object_id])?);
+        }
+        return Ok(result);
+    }
+}
+
+/* "<path>:<line>: <text>" of script lines which made the object depend on the dependency */
+pub fn get_dependency_reasons(
+    objects: &HashMap<String, DatabaseObject>,
+    object_id: &str,
+    dependency_id: &str
+) -> anyhow::Result<Vec<String>> {
+    let mut result = vec![];
+    let object = match objects.get(object_id) {
+        Some(object) => object,
+        None => return Ok(result),
+    };
+    let object_type = object.object_type()?;
+    let dependency_type = get_object_type(dependency_id)?;
+    let line_offset = object.line_offset;
+
+    let search_term = match dependency_type {
+        DatabaseObjectType::Schema => {
+            let schema_by_id = !matches!(object_type,
+                DatabaseObjectType::Extension |
+                DatabaseObjectType::Schema |
+                DatabaseObjectType::Role |
+                DatabaseObjectType::EventTrigger |
+                DatabaseObjectType::Publication |
+                DatabaseObjectType::ForeignServer |
+                DatabaseObjectType::UserMapping
+            );
+            if schema_by_id {
+                result.push(format!("{:?} is in schema {:?}", object_id, get_name(dependency_id)?));
+                return Ok(result);
+            }
+            Some(get_name(dependency_id)?.into())
+        },
+        DatabaseObjectType::Role if object_type == DatabaseObjectType::UserMapping => {
+            result.push(format!("{:?} is a user mapping of role {:?}", object_id, get_name(dependency_id)?));
+            return Ok(result);
+        },
+        _ => get_search_term(dependency_id, &dependency_type, &get_search_schemas())?,
+    };
+
+    let has_signature = dependency_type == DatabaseObjectType::Function && get_signature(dependency_id)?.is_some();
+    let directive_id = match has_signature {
+        true => format!("{}.{}.{}", String::from(&dependency_type), get_schema(dependency_id)?, get_name(dependency_id)?),
+        false => dependency_id.into(),
+    };
+    for (i, line) in object.script.lines().enumerate() {
+        let is_directive = get_directive_values(line, "depends-on").iter()
+            .any(|id| *id == dependency_id || *id == directive_id);
+        let contains_term = match &search_term {
+            Some(term) => utils::contains_whole_word_ci(line, term),
+            None => false,
+        };
+        if is_directive || contains_term {
+            result.push(format!("{}:{}: {}", object.path_buf.display(), line_offset + i + 1, line.trim()));
+        }
+    }
+    if result.is_empty() {
+        result.push(format!("{:?} depends on {:?}, no matching script line found", object_id, dependency_id));
+    }
+    return Ok(result);
 }
 
 
@@ -851,3 +1700,130 @@ pub fn load() -> anyhow::Result<DatabaseProject> {
     return Ok(database_project);
 }
 
+/* object type of created object kind, None for kinds which are not tracked (indexes, sequences, aggregates, etc.) */
+fn get_created_object_type(kind: &str) -> Option<DatabaseObjectType> {
+    match kind {
+        "table" => Some(DatabaseObjectType::Table),
+        "view" => Some(DatabaseObjectType::View),
+        "function" | "procedure" => Some(DatabaseObjectType::Function),
+        "trigger" => Some(DatabaseObjectType::Trigger),
+        "constraint" => Some(DatabaseObjectType::Constraint),
+        "policy" => Some(DatabaseObjectType::Policy),
+        "role" => Some(DatabaseObjectType::Role),
+        "schema" => Some(DatabaseObjectType::Schema),
+        "extension" => Some(DatabaseObjectType::Extension),
+        "type" => Some(DatabaseObjectType::Type),
+        "event trigger" => Some(DatabaseObjectType::EventTrigger),
+        "rule" => Some(DatabaseObjectType::Rule),
+        "publication" => Some(DatabaseObjectType::Publication),
+        "server" => Some(DatabaseObjectType::ForeignServer),
+        "user mapping" => Some(DatabaseObjectType::UserMapping),
+        "foreign table" => Some(DatabaseObjectType::ForeignTable),
+        _ => None,
+    }
+}
+
+/* qualified name has to match schema, unqualified name matches any schema */
+fn matches_qualified_name(parts: &[String], schema: &str, name: &str) -> bool {
+    match parts {
+        [n] => n == name,
+        [s, n] => s == schema && n == name,
+        _ => false,
+    }
+}
+
+fn lowercase_parts(parts: &[String]) -> Vec<String> {
+    return parts.iter().map(|p| p.to_lowercase()).collect();
+}
+
+/* whether the statement creates the object declared by object id */
+fn is_declared_object(object_id: &str, object_type: DatabaseObjectType, created: &sql::CreateObject) -> anyhow::Result<bool> {
+    if get_created_object_type(&created.kind) != Some(object_type) {
+        return Ok(false);
+    }
+    let name = lowercase_parts(&created.name);
+    let result = match object_type {
+        DatabaseObjectType::Table |
+        DatabaseObjectType::View |
+        DatabaseObjectType::Function |
+        DatabaseObjectType::Type |
+        DatabaseObjectType::ForeignTable => matches_qualified_name(&name, get_schema(object_id)?, get_name(object_id)?),
+        DatabaseObjectType::Constraint |
+        DatabaseObjectType::Trigger |
+        DatabaseObjectType::Policy |
+        DatabaseObjectType::Rule => {
+            let table = lowercase_parts(created.table.as_deref().unwrap_or_default());
+            name.last().map(|n| n.as_str()) == Some(get_name(object_id)?)
+            && matches_qualified_name(&table, get_schema(object_id)?, get_table(object_id)?)
+        },
+        DatabaseObjectType::UserMapping => name == [get_server(object_id)?, get_name(object_id)?],
+        DatabaseObjectType::Role |
+        DatabaseObjectType::Schema |
+        DatabaseObjectType::Extension |
+        DatabaseObjectType::EventTrigger |
+        DatabaseObjectType::Publication |
+        DatabaseObjectType::ForeignServer => name == [get_name(object_id)?],
+    };
+    return Ok(result);
+}
+
+/* indexes, sequences, constraints, triggers, rules and policies can be created along with their table */
+fn is_table_part(object_id: &str, object_type: DatabaseObjectType, created: &sql::CreateObject) -> anyhow::Result<bool> {
+    match object_type {
+        DatabaseObjectType::Table |
+        DatabaseObjectType::View |
+        DatabaseObjectType::ForeignTable => {},
+        _ => return Ok(false),
+    }
+    match created.kind.as_str() {
+        "sequence" => return Ok(true),
+        "index" | "constraint" | "trigger" | "rule" | "policy" => {},
+        _ => return Ok(false),
+    }
+    let table = lowercase_parts(created.table.as_deref().unwrap_or_default());
+    return Ok(matches_qualified_name(&table, get_schema(object_id)?, get_name(object_id)?));
+}
+
+/* "<path>:<line>: <problem>" for objects created by the script other than the object declared by its filename,
+and for scripts which do not create the declared object */
+pub fn validate_object_script(object: &DatabaseObject) -> anyhow::Result<Vec<String>> {
+    let mut result = vec![];
+    let object_type = object.object_type()?;
+    let line_offset = object.line_offset;
+    let mut found = false;
+    // objects created in "do" blocks can not be checked
+    let mut dynamic = false;
+
+    for statement in sql::split_statements(&object.script) {
+        if statement.starts_with_words(&["do"]) {
+            dynamic = true;
+        }
+        let created = match sql::parse_create_object(&statement) {
+            Some(created) => created,
+            None => continue,
+        };
+        if is_declared_object(&object.id, object_type, &created)? {
+            found = true;
+            continue;
+        }
+        if is_table_part(&object.id, object_type, &created)? {
+            continue;
+        }
+        if created.kind != "index" && get_created_object_type(&created.kind).is_none() {
+            continue;
+        }
+        let line = line_offset + object.script[..statement.offset].matches('\n').count() + 1;
+        let mut name = created.name.join(".");
+        if let Some(table) = &created.table {
+            name = format!("{} on {}", name, table.join(".")).trim().into();
+        }
+        result.push(format!(
+            "{}:{}: creates {} {} which is not {:?} declared by the filename, move it into its own script",
+            object.path_buf.display(), line, created.kind, name, object.id
+        ));
+    }
+    if !found && !dynamic {
+        result.push(format!("{}: does not create {:?} declared by the filename", object.path_buf.display(), object.id));
+    }
+    return Ok(result);
+}
@@ -24,6 +24,8 @@ fn test_calc_execute_order_0() -> anyhow::Result<()> {
         md5: "asd".into(),
         depends_on: set(vec!["table.s.obj_1", "table.s.obj_2"]),
         required_by: set(vec![]),
+        drop_script: None,
+        line_offset: 0,
     };
 
     let obj_1 = DatabaseObject {
@@ -33,6 +35,8 @@ fn test_calc_execute_order_0() -> anyhow::Result<()> {
         md5: "asd".into(),
         depends_on: set(vec!["table.s.obj_2", "table.s.obj_3"]),
         required_by: set(vec!["table.s.obj_0"]),
+        drop_script: None,
+        line_offset: 0,
     };
 
     let obj_2 = DatabaseObject {
@@ -42,6 +46,8 @@ fn test_calc_execute_order_0() -> anyhow::Result<()> {
         md5: "asd".into(),
         depends_on: set(vec!["table.s.obj_3"]),
         required_by: set(vec!["table.s.obj_1", "table.s.obj_0"]),
+        drop_script: None,
+        line_offset: 0,
     };
 
     let obj_3 = DatabaseObject {
@@ -51,6 +57,8 @@ fn test_calc_execute_order_0() -> anyhow::Result<()> {
         md5: "asd".into(),
         depends_on: set(vec![]),
         required_by: set(vec!["table.s.obj_1", "table.s.obj_2"]),
+        drop_script: None,
+        line_offset: 0,
     };
 
     let mut objects: HashMap<String, DatabaseObject> = HashMap::new();
@@ -85,6 +93,8 @@ fn test_calc_execute_order_1() -> anyhow::Result<()> {
         md5: "asd".into(),
         depends_on: set(vec!["table.s.obj_1"]),
         required_by: set(vec!["table.s.obj_3"]),
+        drop_script: None,
+        line_offset: 0,
     };
 
     let obj_1 = DatabaseObject {
@@ -94,6 +104,8 @@ fn test_calc_execute_order_1() -> anyhow::Result<()> {
         md5: "asd".into(),
         depends_on: set(vec!["table.s.obj_2"]),
         required_by: set(vec!["table.s.obj_0"]),
+        drop_script: None,
+        line_offset: 0,
     };
 
     let obj_2 = DatabaseObject {
@@ -103,6 +115,8 @@ fn test_calc_execute_order_1() -> anyhow::Result<()> {
         md5: "asd".into(),
         depends_on: set(vec!["table.s.obj_0"]),
         required_by: set(vec!["table.s.obj_1"]),
+        drop_script: None,
+        line_offset: 0,
     };
 
     let obj_3 = DatabaseObject {
@@ -112,6 +126,8 @@ fn test_calc_execute_order_1() -> anyhow::Result<()> {
         md5: "asd".into(),
         depends_on: set(vec![]),
         required_by: set(vec![]),
+        drop_script: None,
+        line_offset: 0,
     };
 
     let mut objects: HashMap<String, DatabaseObject> = HashMap::new();
@@ -124,5 +140,257 @@ fn test_calc_execute_order_1() -> anyhow::Result<()> {
     let execute_order_actual = calc_create_order(&objects);
     assert_eq!(execute_order_actual.is_err(), true);
 
+    let message = execute_order_actual.unwrap_err().to_string();
+    assert_eq!(message.contains("cycle detected: table.s.obj_0 -> table.s.obj_1 -> table.s.obj_2 -> table.s.obj_0"), true);
+    assert_eq!(message.contains("\n    table.s.obj_2 -> table.s.obj_0\n"), true);
+    assert_eq!(message.contains("hint: move foreign keys"), true);
+
+    return Ok(());
+}
+
+#[test]
+fn test_function_signature_id() -> anyhow::Result<()> {
+    let id = "function.public.add(int,public.money_t)";
+    validate_object_id(id)?;
+    assert_eq!(get_schema(id)?, "public");
+    assert_eq!(get_name(id)?, "add");
+    assert_eq!(get_signature(id)?, Some("int,public.money_t"));
+    assert_eq!(get_signature("function.public.add")?, None);
+    assert_eq!(get_name("function.public.add")?, "add");
+    assert_eq!(normalize_function_signature("public.add(a int,  b double precision)")?, "public.add(int,double precision)");
+    assert_eq!(validate_object_id("view.public.v(int)").is_err(), true);
+    assert_eq!(validate_object_id("function.public.add(int").is_err(), true);
+    return Ok(());
+}
+
+#[test]
+fn test_split_function_script() -> anyhow::Result<()> {
+    let script = "
+-- add overloads
+create or replace function add(a int, b int) returns int as $$ select a + b $$ language sql;
+revoke execute on function add(int, int) from public;
+
+create or replace function public.add(a text, b text) returns text as $$ select a || b $$ language sql;
+";
+    let overloads = split_function_script("function.public.add", script)?;
+    assert_eq!(overloads.len(), 2);
+    assert_eq!(overloads[0].0, "function.public.add(int,int)");
+    assert_eq!(overloads[0].1.contains("revoke execute"), true);
+    assert_eq!(overloads[1].0, "function.public.add(text,text)");
+    assert_eq!(overloads[1].1.trim_start().starts_with("create or replace function public.add(a text"), true);
+
+    let single = split_function_script("function.public.add", "create function add(a int) returns int as $$ select a $$ language sql;")?;
+    assert_eq!(single.len(), 1);
+    assert_eq!(single[0].0, "function.public.add");
+
+    let duplicate = "create function add(a int) returns int as $$ select a $$ language sql;
+create function add(b int) returns int as $$ select b $$ language sql;";
+    assert_eq!(split_function_script("function.public.add", duplicate).is_err(), true);
+    return Ok(());
+}
+
+#[test]
+fn test_new_object_id() -> anyhow::Result<()> {
+    assert_eq!(new_object_id(&DatabaseObjectType::Table, "App.Users")?, "table.app.users");
+    assert_eq!(new_object_id(&DatabaseObjectType::Trigger, "app.users.set_updated_at")?, "trigger.app.users.set_updated_at");
+    assert_eq!(new_object_id(&DatabaseObjectType::Function, "app.add(a int, b int)")?, "function.app.add(int,int)");
+    assert_eq!(new_object_id(&DatabaseObjectType::Role, "reader")?, "role.reader");
+    assert_eq!(new_object_id(&DatabaseObjectType::Trigger, "app.users").is_err(), true);
+    assert_eq!(new_object_id(&DatabaseObjectType::Table, "users").is_err(), true);
+    assert_eq!(new_object_id(&DatabaseObjectType::Table, "app..users").is_err(), true);
+    assert_eq!(new_object_id(&DatabaseObjectType::Table, "app.").is_err(), true);
+    assert_eq!(new_object_id(&DatabaseObjectType::Table, "../app.users").is_err(), true);
+    assert_eq!(new_object_id(&DatabaseObjectType::Role, "app.reader").is_err(), true);
+    return Ok(());
+}
+
+#[test]
+fn test_get_object_template() -> anyhow::Result<()> {
+    assert_eq!(
+        get_object_template(&DatabaseObjectType::Trigger, "app.users.set_updated_at"),
+        "create trigger set_updated_at before update on app.users\nfor each row execute function app.set_updated_at();\n"
+    );
+    assert_eq!(
+        get_object_template(&DatabaseObjectType::Function, "app.add(int,int)"),
+        "create or replace function app.add(int,int) returns void as $$\nbegin\nend;\n$$ language plpgsql;\n"
+    );
+    assert_eq!(get_object_template(&DatabaseObjectType::Role, "reader"), "create role {wo_role_prefix}reader;\n");
+    return Ok(());
+}
+
+#[test]
+fn test_split_drop_section() -> anyhow::Result<()> {
+    let script = "create trigger t0 before update on t for each row execute function f();\n-- wo:drop\ndrop trigger t0 on t;\ndrop function f();\n";
+    let (create_script, drop_script) = split_drop_section(script);
+    assert_eq!(create_script, "create trigger t0 before update on t for each row execute function f();\n");
+    assert_eq!(drop_script, Some("drop trigger t0 on t;\ndrop function f();\n".into()));
+
+    let (create_script, drop_script) = split_drop_section("create view v as select 1; -- wo:drop");
+    assert_eq!(create_script, "create view v as select 1; -- wo:drop");
+    assert_eq!(drop_script, None);
+    return Ok(());
+}
+
+#[test]
+fn test_dependency_directives() -> anyhow::Result<()> {
+    let script = "
+-- wo:depends-on view.app.active_users, function.app.f
+-- wo:ignore-dependency table.public.log
+-- wo:depends-onx table.public.t
+create function app.g() returns int as $$ select 1 $$ language sql;
+";
+    assert_eq!(get_directive_values(script, "depends-on"), vec!["view.app.active_users", "function.app.f"]);
+    assert_eq!(get_directive_values(script, "ignore-dependency"), vec!["table.public.log"]);
+
+    let mut objects_info: ObjectsInfo = HashMap::new();
+    objects_info.insert("function.app.g".into(), (DatabaseObjectType::Function, "app.g.sql".into(), script.into()));
+    objects_info.insert("view.app.active_users".into(), (DatabaseObjectType::View, "app.active_users.sql".into(), "".into()));
+    objects_info.insert("function.app.f(int)".into(), (DatabaseObjectType::Function, "app.f.sql".into(), "".into()));
+    objects_info.insert("function.app.f(text)".into(), (DatabaseObjectType::Function, "app.f.sql".into(), "".into()));
+    objects_info.insert("table.public.log".into(), (DatabaseObjectType::Table, "public.log.sql".into(), "".into()));
+
+    let mut required_by: HashMap<String, HashSet<String>> = HashMap::new();
+    required_by.insert("table.public.log".into(), set(vec!["function.app.g"]));
+    apply_dependency_directives(&objects_info, &mut required_by)?;
+    assert_eq!(required_by["view.app.active_users"], set(vec!["function.app.g"]));
+    assert_eq!(required_by["function.app.f(int)"], set(vec!["function.app.g"]));
+    assert_eq!(required_by["function.app.f(text)"], set(vec!["function.app.g"]));
+    assert_eq!(required_by["table.public.log"], set(vec![]));
+
+    objects_info.insert("view.app.v".into(), (DatabaseObjectType::View, "app.v.sql".into(), "-- wo:depends-on view.app.missing".into()));
+    assert_eq!(apply_dependency_directives(&objects_info, &mut required_by).is_err(), true);
+    return Ok(());
+}
+
+#[test]
+fn test_validate_object_script() -> anyhow::Result<()> {
+    let object = |id: &str, script: &str| DatabaseObject {
+        id: id.into(),
+        path_buf: format!("{}.sql", id).into(),
+        script: script.into(),
+        md5: "asd".into(),
+        depends_on: set(vec![]),
+        required_by: set(vec![]),
+        drop_script: None,
+        line_offset: 0,
+    };
+
+    let table = object("table.s.t0", "
+create table t0 (id int);
+create index on s.t0 (id);
+create sequence t0_seq;
+alter table t0 add constraint t0_c check (id > 0);
+create trigger t0_tr after insert on t0 for each row execute function f();
+create view s.v0 as select * from t0;
+create index t1_idx on t1 (id);
+");
+    assert_eq!(validate_object_script(&table)?, vec![
+        "table.s.t0.sql:7: creates view s.v0 which is not \"table.s.t0\" declared by the filename, move it into its own script",
+        "table.s.t0.sql:8: creates index t1_idx on t1 which is not \"table.s.t0\" declared by the filename, move it into its own script",
+    ]);
+
+    let trigger = object("trigger.s.t0.t0_tr", "
+create function s.f() returns trigger as $$ begin return new; end $$ language plpgsql;
+create trigger t0_tr after insert on s.t0 for each row execute function s.f();
+");
+    assert_eq!(validate_object_script(&trigger)?, vec![
+        "trigger.s.t0.t0_tr.sql:2: creates function s.f which is not \"trigger.s.t0.t0_tr\" declared by the filename, move it into its own script",
+    ]);
+
+    let function = object("function.s.f(int)", "create or replace function \"F\"(a int) returns int as $$ select a $$ language sql;");
+    assert_eq!(validate_object_script(&function)?.len(), 0);
+
+    let view = object("view.s.v1", "create view s.v2 as select 1;");
+    assert_eq!(validate_object_script(&view)?, vec![
+        "view.s.v1.sql:1: creates view s.v2 which is not \"view.s.v1\" declared by the filename, move it into its own script",
+        "view.s.v1.sql: does not create \"view.s.v1\" declared by the filename",
+    ]);
+
+    let role = object("role.r0", "do $$ begin create role r0; exception when duplicate_object then null; end $$;");
+    assert_eq!(validate_object_script(&role)?.len(), 0);
+
+    let user_mapping = object("user_mapping.srv0.r0", "create user mapping for r0 server srv0;");
+    assert_eq!(validate_object_script(&user_mapping)?.len(), 0);
     return Ok(());
-}
\ No newline at end of file
+}
+
+#[test]
+fn test_environments() -> anyhow::Result<()> {
+    let config = "
+        [profiles.dev]
+        [profiles.Test]
+        [profiles.prod]
+        [profiles.stage]
+
+        [environments]
+        \"view.public.debug_stats\" = [\"dev\", \"test\"]
+        \"function.public.reset\" = [\"Test\"]
+    ".parse::<toml::Value>()?;
+    let variables = template::Variables::new();
+    let mut environments = Environments::load(&config, &variables)?;
+    environments.profile = Some("dev".into());
+    assert_eq!(environments.is_included("view.public.users_view", "create view ...")?, true);
+    assert_eq!(environments.is_included("view.public.debug_stats", "create view ...")?, true);
+    assert_eq!(environments.is_included("function.public.reset(int)", "create function ...")?, false);
+    assert_eq!(environments.is_included("role.tester", "-- wo:environments prod, stage\ncreate role ...")?, false);
+
+    environments.profile = Some("test".into());
+    assert_eq!(environments.is_included("function.public.reset(int)", "create function ...")?, true);
+    // both directive and config have to include the environment
+    assert_eq!(environments.is_included("view.public.debug_stats", "-- wo:environments dev\ncreate view ...")?, false);
+
+    // misspelled environment is an error, not an exclusion
+    assert_eq!(environments.is_included("view.public.users_view", "-- wo:environments prdo\ncreate view ...").is_err(), true);
+
+    // excluded tables, schemas, extensions and types would not be dropped
+    assert_eq!(environments.is_included("table.public.debug_log", "-- wo:environments dev\ncreate table ...").is_err(), true);
+    assert_eq!(environments.is_included("schema.debug", "-- wo:environments dev\ncreate schema ...").is_err(), true);
+    assert_eq!(environments.is_included("extension.pg_stat_statements", "-- wo:environments prod\ncreate extension ...").is_err(), true);
+    assert_eq!(environments.is_included("type.public.debug_state", "-- wo:environments dev\ncreate type ...").is_err(), true);
+
+    // restricted objects can not be loaded without a profile
+    environments.profile = None;
+    assert_eq!(environments.is_included("view.public.users_view", "create view ...")?, true);
+    assert_eq!(environments.is_included("view.public.debug_stats", "create view ...").is_err(), true);
+
+    let config = "
+        [profiles.dev]
+
+        [environments]
+        \"view.public.debug_stats\" = [\"dvo\"]
+    ".parse::<toml::Value>()?;
+    assert_eq!(Environments::load(&config, &variables).is_err(), true);
+    return Ok(());
+}
+
+#[test]
+fn test_is_drop_script_path() -> anyhow::Result<()> {
+    let path_buf = std::env::temp_dir().join(format!("wo_test_is_drop_script_path_{}", std::process::id()));
+    std::fs::create_dir_all(&path_buf)?;
+    utils::write_file(&path_buf.join("public.trigger0.sql"), "")?;
+    utils::write_file(&path_buf.join("public.trigger0.drop.sql"), "")?;
+    utils::write_file(&path_buf.join("public.drop.sql"), "")?;
+
+    assert_eq!(is_drop_script_path(&path_buf.join("public.trigger0.drop.sql")), true);
+    assert_eq!(is_drop_script_path(&path_buf.join("public.trigger0.sql")), false);
+    // view named "drop" without public.sql next to it
+    assert_eq!(is_drop_script_path(&path_buf.join("public.drop.sql")), false);
+
+    std::fs::remove_dir_all(&path_buf)?;
+    return Ok(());
+}
+
+#[test]
+fn test_validate_optional_scripts() -> anyhow::Result<()> {
+    let scripts = vec![(PathBuf::from("comments/all.sql"), String::from("comment on table t0 is 'zero';\n-- note\nComment On column t0.id is null;"))];
+    validate_optional_scripts(&scripts, &[&["comment", "on"]], "comment")?;
+
+    let scripts = vec![(PathBuf::from("comments/all.sql"), String::from("comment on table t0 is 'zero';\n\nupdate t0 set id = 1;"))];
+    let error = validate_optional_scripts(&scripts, &[&["comment", "on"]], "comment").unwrap_err();
+    assert_eq!(error.to_string(), "load error: comment scripts can only contain comment on statements \"comments/all.sql\" line 3");
+
+    let scripts = vec![(PathBuf::from("grants/all.sql"), String::from("grant select on t0 to r0;\nrevoke all on t0 from public;\ncreate role r2;"))];
+    let error = validate_optional_scripts(&scripts, &[&["grant"], &["revoke"]], "grant").unwrap_err();
+    assert_eq!(error.to_string(), "load error: grant scripts can only contain grant or revoke statements \"grants/all.sql\" line 3");
+    return Ok(());
+}
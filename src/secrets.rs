@@ -0,0 +1,316 @@
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::env;
+use std::path::Path;
+use std::path::PathBuf;
+use std::process::Command;
+use std::sync::Mutex;
+use anyhow;
+use anyhow::Context;
+use serde_json;
+use toml;
+use crate::utils;
+use crate::connection;
+
+#[cfg(test)]
+mod tests;
+
+
+const MASK: &str = "********";
+// shorter values are masked only as sql literals, masking them everywhere would garble unrelated output
+const MIN_MASK_LENGTH: usize = 4;
+
+// secret sources declared in project config, set when project is loaded
+static SOURCES: Mutex<Option<HashMap<String, Source>>> = Mutex::new(None);
+// values of read secrets and passwords, masked in all output
+static VALUES: Mutex<Vec<String>> = Mutex::new(Vec::new());
+// (name, value) of secrets which were already read, so that commands are executed once
+static CACHE: Mutex<Vec<(String, String)>> = Mutex::new(Vec::new());
+
+
+/* where the value of a secret is read from */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Source {
+    Env(String),
+    // e.g. docker or kubernetes secret file
+    File(PathBuf),
+    // .pgpass entry, missing fields are taken from WO_CONNECTION_STRING (or PG* variables)
+    Pgpass {
+        host: Option<String>,
+        port: Option<u16>,
+        database: Option<String>,
+        user: Option<String>,
+    },
+    // program with arguments printing the value to stdout
+    Command(Vec<String>),
+}
+
+fn get_string(table: &toml::value::Table, key: &str, name: &str) -> anyhow::Result<Option<String>> {
+    return match table.get(key) {
+        Some(value) => {
+            let value = value.as_str()
+                .ok_or(anyhow!("load_secrets error: {} of secret {:?} has to be a string", key, name))?;
+            Ok(Some(value.into()))
+        },
+        None => Ok(None),
+    };
+}
+
+fn parse_source(name: &str, value: &toml::Value, project_path: &Path) -> anyhow::Result<Source> {
+    let table = value.as_table()
+        .ok_or(anyhow!("load_secrets error: secret {:?} has to be a table", name))?;
+    if table.len() != 1 {
+        bail!("load_secrets error: secret {:?} has to declare exactly one of env, file, pgpass or command", name);
+    }
+    if let Some(var) = get_string(table, "env", name)? {
+        return Ok(Source::Env(var));
+    }
+    if let Some(file) = get_string(table, "file", name)? {
+        // relative paths are relative to the project directory
+        return Ok(Source::File(project_path.join(file)));
+    }
+    if let Some(pgpass) = table.get("pgpass") {
+        let empty = toml::value::Table::new();
+        let pgpass = match pgpass {
+            toml::Value::Boolean(true) => &empty,
+            toml::Value::Table(t) => t,
+            _ => bail!("load_secrets error: pgpass of secret {:?} has to be true or a table of host, port, database and user", name),
+        };
+        let port = match pgpass.get("port") {
+            Some(port) => {
+                let port = port.as_integer()
+                    .ok_or(anyhow!("load_secrets error: pgpass port of secret {:?} has to be a number", name))?;
+                let port = u16::try_from(port)
+                    .map_err(|_| anyhow!("load_secrets error: pgpass port of secret {:?} is out of range {}", name, port))?;
+                Some(port)
+            },
+            None => None,
+        };
+        return Ok(Source::Pgpass {
+            host: get_string(pgpass, "host", name)?,
+            port,
+            database: get_string(pgpass, "database", name)?,
+            user: get_string(pgpass, "user", name)?,
+        });
+    }
+    if let Some(command) = table.get("command") {
+        let command = match command {
+            // string is executed by shell
+            toml::Value::String(s) => vec!["sh".into(), "-c".into(), s.clone()],
+            toml::Value::Array(a) => {
+                let mut result = vec![];
+                for arg in a.iter() {
+                    let arg = arg.as_str()
+                        .ok_or(anyhow!("load_secrets error: command of secret {:?} has to be an array of strings", name))?;
+                    result.push(String::from(arg));
+                }
+                result
+            },
+            _ => bail!("load_secrets error: command of secret {:?} has to be a string or an array of strings", name),
+        };
+        if command.is_empty() {
+            bail!("load_secrets error: command of secret {:?} is empty", name);
+        }
+        return Ok(Source::Command(command));
+    }
+    bail!("load_secrets error: secret {:?} has to declare one of env, file, pgpass or command", name);
+}
+
+/* [secrets.<name>] tables of the project config */
+pub fn load_sources(config: &toml::Value, project_path: &Path) -> anyhow::Result<HashMap<String, Source>> {
+    let mut result = HashMap::new();
+    let secrets = match config.get("secrets") {
+        Some(secrets) => secrets.as_table()
+            .ok_or(anyhow!("load_secrets error: secrets has to be a table"))?,
+        None => return Ok(result),
+    };
+    for (name, value) in secrets.iter() {
+        result.insert(name.clone(), parse_source(name, value, project_path)?);
+    }
+    return Ok(result);
+}
+
+pub fn set_sources(sources: HashMap<String, Source>) {
+    if let Ok(mut current) = SOURCES.lock() {
+        *current = Some(sources);
+    }
+}
+
+/* value is masked in all output from now on */
+pub fn register(value: &str) {
+    if value.is_empty() {
+        return;
+    }
+    if let Ok(mut values) = VALUES.lock() {
+        if !values.iter().any(|v| v == value) {
+            values.push(value.into());
+        }
+    }
+}
+
+/* text with all registered secret values (also quoted as sql literal or json string) replaced by a mask,
+values shorter than MIN_MASK_LENGTH are replaced only as whole sql literals */
+pub fn mask(text: &str) -> String {
+    let values = match VALUES.lock() {
+        Ok(values) => values.clone(),
+        Err(_) => return text.into(),
+    };
+    let mut result = String::from(text);
+    for value in values.iter() {
+        let sql_quoted = value.replace('\\', "\\\\").replace('\'', "''");
+        if value.chars().count() < MIN_MASK_LENGTH {
+            result = result.replace(&format!("'{}'", sql_quoted), &format!("'{}'", MASK));
+            continue;
+        }
+        let json_string = serde_json::to_string(value).unwrap_or_default();
+        let json_quoted = json_string.get(1..json_string.len().saturating_sub(1)).unwrap_or("");
+        for form in [sql_quoted.as_str(), value.as_str(), json_quoted].iter() {
+            if !form.is_empty() {
+                result = result.replace(form, MASK);
+            }
+        }
+    }
+    return result;
+}
+
+/* value without the trailing newline which files and command output usually end with */
+fn trim_newline(value: &str) -> &str {
+    return value.strip_suffix('\n')
+        .map(|v| v.strip_suffix('\r').unwrap_or(v))
+        .unwrap_or(value);
+}
+
+fn read_source(name: &str, source: &Source) -> anyhow::Result<String> {
+    match source {
+        Source::Env(var) => {
+            return utils::read_env_var(var)
+                .context(format!("get_secret error: failed to read secret {:?}", name));
+        },
+        Source::File(path_buf) => {
+            let value = utils::read_file(path_buf)
+                .context(format!("get_secret error: failed to read secret {:?}", name))?;
+            return Ok(trim_newline(&value).into());
+        },
+        Source::Pgpass { host, port, database, user } => {
+            let params = connection::get_params("WO_CONNECTION_STRING")?;
+            let get_first = |keyword: &str| Some(connection::get_first(&params, keyword))
+                .filter(|value| !value.is_empty())
+                .map(String::from);
+            let host = host.clone().or(get_first("host")).unwrap_or("localhost".into());
+            let port = match port {
+                Some(port) => *port,
+                None => get_first("port").and_then(|p| p.parse().ok()).unwrap_or(5432),
+            };
+            let database = database.clone().or(get_first("dbname")).unwrap_or_default();
+            let user = user.clone().or(get_first("user")).unwrap_or_default();
+            return find_pgpass_password(&host, port, &database, &user)?
+                .ok_or(anyhow!("get_secret error: no .pgpass entry for secret {:?} ({}:{}:{}:{})", name, host, port, database, user));
+        },
+        Source::Command(command) => {
+            let output = Command::new(&command[0])
+                .args(&command[1..])
+                .output()
+                .context(format!("get_secret error: failed to run command of secret {:?}", name))?;
+            if !output.status.success() {
+                bail!("get_secret error: command of secret {:?} failed with {}: {}",
+                    name,
+                    output.status,
+                    String::from_utf8_lossy(&output.stderr).trim(),
+                );
+            }
+            let value = String::from_utf8(output.stdout)
+                .context(format!("get_secret error: command of secret {:?} printed invalid utf-8", name))?;
+            return Ok(trim_newline(&value).into());
+        },
+    }
+}
+
+/* value of the declared secret (read once), none if secret is not declared */
+pub fn get_secret(name: &str) -> anyhow::Result<Option<String>> {
+    let mut cache = CACHE.lock()
+        .map_err(|_| anyhow!("get_secret error: secrets are not available"))?;
+    if let Some((_, value)) = cache.iter().find(|(n, _)| n == name) {
+        return Ok(Some(value.clone()));
+    }
+    let source = SOURCES.lock()
+        .map_err(|_| anyhow!("get_secret error: secrets are not available"))?
+        .as_ref()
+        .and_then(|sources| sources.get(name).cloned());
+    let source = match source {
+        Some(source) => source,
+        None => return Ok(None),
+    };
+    let value = read_source(name, &source)?;
+    register(&value);
+    cache.push((name.into(), value.clone()));
+    return Ok(Some(value));
+}
+
+/* fields of .pgpass line split by unescaped colons */
+fn parse_pgpass_line(line: &str) -> Vec<String> {
+    let mut result = vec![];
+    let mut field = String::new();
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => {
+                if let Some(next) = chars.next() {
+                    field.push(next);
+                }
+            },
+            ':' => result.push(std::mem::take(&mut field)),
+            _ => field.push(c),
+        }
+    }
+    result.push(field);
+    return result;
+}
+
+/* PGPASSFILE or ~/.pgpass */
+fn get_pgpass_path() -> Option<PathBuf> {
+    if let Ok(path) = env::var("PGPASSFILE") {
+        return Some(PathBuf::from(path));
+    }
+    return env::var("HOME").ok().map(|home| PathBuf::from(home).join(".pgpass"));
+}
+
+/* password of the first matching "host:port:database:user:password" line, "*" matches anything */
+pub fn find_pgpass_password_in(
+    content: &str,
+    host: &str,
+    port: u16,
+    database: &str,
+    user: &str
+) -> Option<String> {
+    let port = port.to_string();
+    for line in content.lines() {
+        if line.trim().is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let fields = parse_pgpass_line(line);
+        if fields.len() != 5 {
+            continue;
+        }
+        let matches = [host, port.as_str(), database, user].iter().zip(fields.iter())
+            .all(|(value, field)| field == "*" || field == value);
+        if matches {
+            return Some(fields[4].clone());
+        }
+    }
+    return None;
+}
+
+/* password from the .pgpass file like libpq does, none if there is no file or no matching line */
+pub fn find_pgpass_password(
+    host: &str,
+    port: u16,
+    database: &str,
+    user: &str
+) -> anyhow::Result<Option<String>> {
+    let path_buf = match get_pgpass_path() {
+        Some(path_buf) if path_buf.exists() => path_buf,
+        _ => return Ok(None),
+    };
+    let content = utils::read_file(&path_buf)?;
+    return Ok(find_pgpass_password_in(&content, host, port, database, user));
+}
@@ -0,0 +1,75 @@
+use anyhow;
+use super::*;
+
+
+#[test]
+fn test_load_sources() -> anyhow::Result<()> {
+    let config = "
+        [secrets.a]
+        env = \"A_PASSWORD\"
+        [secrets.b]
+        file = \"secrets/b\"
+        [secrets.c]
+        pgpass = true
+        [secrets.d]
+        pgpass = { user = \"reader\", port = 5433 }
+        [secrets.e]
+        command = [\"vault\", \"read\", \"-field=password\", \"secret/e\"]
+        [secrets.f]
+        command = \"echo f\"
+    ".parse::<toml::Value>()?;
+    let sources = load_sources(&config, Path::new("/wo"))?;
+    assert_eq!(sources["a"], Source::Env("A_PASSWORD".into()));
+    assert_eq!(sources["b"], Source::File("/wo/secrets/b".into()));
+    assert_eq!(sources["c"], Source::Pgpass { host: None, port: None, database: None, user: None });
+    assert_eq!(sources["d"], Source::Pgpass { host: None, port: Some(5433), database: None, user: Some("reader".into()) });
+    assert_eq!(sources["e"], Source::Command(vec!["vault".into(), "read".into(), "-field=password".into(), "secret/e".into()]));
+    assert_eq!(sources["f"], Source::Command(vec!["sh".into(), "-c".into(), "echo f".into()]));
+
+    let config = "[secrets.a]\nenv = \"A\"\nfile = \"b\"".parse::<toml::Value>()?;
+    assert_eq!(load_sources(&config, Path::new("/wo")).is_err(), true);
+    let config = "[secrets.a]\nvault = \"a\"".parse::<toml::Value>()?;
+    assert_eq!(load_sources(&config, Path::new("/wo")).is_err(), true);
+    let config = "[secrets.a]\npgpass = { port = 70000 }".parse::<toml::Value>()?;
+    assert_eq!(load_sources(&config, Path::new("/wo")).is_err(), true);
+    return Ok(());
+}
+
+#[test]
+fn test_read_source() -> anyhow::Result<()> {
+    let command = Source::Command(vec!["sh".into(), "-c".into(), "printf 's3cr\"et\\n'".into()]);
+    assert_eq!(read_source("x", &command)?, "s3cr\"et");
+    let failing = Source::Command(vec!["sh".into(), "-c".into(), "exit 3".into()]);
+    assert_eq!(read_source("x", &failing).is_err(), true);
+    return Ok(());
+}
+
+#[test]
+fn test_mask() -> anyhow::Result<()> {
+    register("p@ss\"word");
+    assert_eq!(mask("create role r password 'p@ss\"word';"), "create role r password '********';");
+    assert_eq!(mask("{\"sql\":\"password 'p@ss\\\"word'\"}"), "{\"sql\":\"password '********'\"}");
+    assert_eq!(mask("nothing to hide"), "nothing to hide");
+    register("it's");
+    assert_eq!(mask("password 'it''s'"), "password '********'");
+    // short values are masked only as whole sql literals
+    register("a1");
+    assert_eq!(mask("create role a1 password 'a1';"), "create role a1 password '********';");
+    assert_eq!(mask("select a1, 'a12' from t0;"), "select a1, 'a12' from t0;");
+    return Ok(());
+}
+
+#[test]
+fn test_find_pgpass_password() -> anyhow::Result<()> {
+    let content = "
+# comment
+db.local:5432:app:reader:first
+*:*:app:writer:pa\\:ss
+*:5433:*:*:fallback
+";
+    assert_eq!(find_pgpass_password_in(content, "db.local", 5432, "app", "reader"), Some("first".into()));
+    assert_eq!(find_pgpass_password_in(content, "other", 5432, "app", "writer"), Some("pa:ss".into()));
+    assert_eq!(find_pgpass_password_in(content, "other", 5433, "x", "y"), Some("fallback".into()));
+    assert_eq!(find_pgpass_password_in(content, "other", 5432, "x", "y"), None);
+    return Ok(());
+}
@@ -0,0 +1,1102 @@
+
+#[cfg(test)]
+mod tests;
+
+
+#[derive(PartialEq, Eq, Debug, Copy, Clone)]
+pub enum TokenKind {
+    Word,
+    QuotedIdent,
+    Literal,
+    Number,
+    Symbol,
+}
+
+#[derive(Debug, Copy, Clone)]
+pub struct Token<'t> {
+    pub kind: TokenKind,
+    pub text: &'t str,
+    pub offset: usize,
+}
+
+impl<'t> Token<'t> {
+    pub fn is_word(&self, word: &str) -> bool {
+        return self.kind == TokenKind::Word && self.text.eq_ignore_ascii_case(word);
+    }
+
+    pub fn is_symbol(&self, symbol: &str) -> bool {
+        return self.kind == TokenKind::Symbol && self.text == symbol;
+    }
+
+    pub fn is_identifier(&self) -> bool {
+        return self.kind == TokenKind::Word || self.kind == TokenKind::QuotedIdent;
+    }
+
+    pub fn end(&self) -> usize {
+        return self.offset + self.text.len();
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Statement<'t> {
+    pub offset: usize,
+    pub text: &'t str,
+    pub tokens: Vec<Token<'t>>,
+}
+
+impl<'t> Statement<'t> {
+    pub fn starts_with_words(&self, words: &[&str]) -> bool {
+        if self.tokens.len() < words.len() {
+            return false;
+        }
+        for (token, word) in self.tokens.iter().zip(words) {
+            if !token.is_word(word) {
+                return false;
+            }
+        }
+        return true;
+    }
+}
+
+fn is_ident_start(c: u8) -> bool {
+    return c.is_ascii_alphabetic() || c == b'_' || c >= 0x80;
+}
+
+fn is_ident_char(c: u8) -> bool {
+    return c.is_ascii_alphanumeric() || c == b'_' || c == b'$' || c >= 0x80;
+}
+
+fn skip_quoted(bytes: &[u8], start: usize, quote: u8, backslash_escapes: bool) -> usize {
+    let mut i = start + 1;
+    while i < bytes.len() {
+        let c = bytes[i];
+        if backslash_escapes && c == b'\\' {
+            i += 2;
+            continue;
+        }
+        if c == quote {
+            if i + 1 < bytes.len() && bytes[i + 1] == quote {
+                i += 2;
+                continue;
+            }
+            return i + 1;
+        }
+        i += 1;
+    }
+    return bytes.len();
+}
+
+/* returns dollar quote tag length including both dollar signs */
+fn dollar_tag_len(bytes: &[u8], start: usize) -> Option<usize> {
+    let mut i = start + 1;
+    if i < bytes.len() && bytes[i].is_ascii_digit() {
+        return None;
+    }
+    while i < bytes.len() && bytes[i] != b'$' {
+        if !(bytes[i].is_ascii_alphanumeric() || bytes[i] == b'_' || bytes[i] >= 0x80) {
+            return None;
+        }
+        i += 1;
+    }
+    if i >= bytes.len() {
+        return None;
+    }
+    return Some(i + 1 - start);
+}
+
+fn skip_dollar_quoted(text: &str, start: usize, tag_len: usize) -> usize {
+    let tag = &text[start..start + tag_len];
+    match text[start + tag_len..].find(tag) {
+        Some(i) => start + tag_len + i + tag_len,
+        None => text.len(),
+    }
+}
+
+fn skip_block_comment(bytes: &[u8], start: usize) -> usize {
+    let mut depth = 0;
+    let mut i = start;
+    while i + 1 < bytes.len() {
+        if bytes[i] == b'/' && bytes[i + 1] == b'*' {
+            depth += 1;
+            i += 2;
+        } else if bytes[i] == b'*' && bytes[i + 1] == b'/' {
+            depth -= 1;
+            i += 2;
+            if depth == 0 {
+                return i;
+            }
+        } else {
+            i += 1;
+        }
+    }
+    return bytes.len();
+}
+
+/* splits sql text into tokens, comments are skipped */
+pub fn tokenize(text: &str) -> Vec<Token<'_>> {
+    let bytes = text.as_bytes();
+    let mut result = vec![];
+    let mut i = 0;
+    while i < bytes.len() {
+        let c = bytes[i];
+        let start = i;
+        let kind;
+
+        if c.is_ascii_whitespace() {
+            i += 1;
+            continue;
+        } else if c == b'-' && i + 1 < bytes.len() && bytes[i + 1] == b'-' {
+            while i < bytes.len() && bytes[i] != b'\n' {
+                i += 1;
+            }
+            continue;
+        } else if c == b'/' && i + 1 < bytes.len() && bytes[i + 1] == b'*' {
+            i = skip_block_comment(bytes, i);
+            continue;
+        } else if c == b'\'' {
+            i = skip_quoted(bytes, i, b'\'', false);
+            kind = TokenKind::Literal;
+        } else if (c == b'e' || c == b'E') && i + 1 < bytes.len() && bytes[i + 1] == b'\'' {
+            i = skip_quoted(bytes, i + 1, b'\'', true);
+            kind = TokenKind::Literal;
+        } else if c == b'"' {
+            i = skip_quoted(bytes, i, b'"', false);
+            kind = TokenKind::QuotedIdent;
+        } else if c == b'$' && dollar_tag_len(bytes, i).is_some() {
+            let tag_len = dollar_tag_len(bytes, i).unwrap();
+            i = skip_dollar_quoted(text, i, tag_len);
+            kind = TokenKind::Literal;
+        } else if is_ident_start(c) {
+            while i < bytes.len() && is_ident_char(bytes[i]) {
+                i += 1;
+            }
+            kind = TokenKind::Word;
+        } else if c.is_ascii_digit() {
+            while i < bytes.len() && (bytes[i].is_ascii_alphanumeric() || bytes[i] == b'.') {
+                i += 1;
+            }
+            kind = TokenKind::Number;
+        } else {
+            i += 1;
+            while i < bytes.len() && !text.is_char_boundary(i) {
+                i += 1;
+            }
+            kind = TokenKind::Symbol;
+        }
+
+        result.push(Token {
+            kind,
+            text: &text[start..i],
+            offset: start,
+        });
+    }
+    return result;
+}
+
+/* splits script into top level statements separated by semicolons */
+pub fn split_statements(script: &str) -> Vec<Statement<'_>> {
+    let mut result = vec![];
+    let mut tokens: Vec<Token> = vec![];
+    for token in tokenize(script) {
+        if token.is_symbol(";") {
+            if let Some(first) = tokens.first() {
+                result.push(Statement {
+                    offset: first.offset,
+                    text: &script[first.offset..token.end()],
+                    tokens,
+                });
+            }
+            tokens = vec![];
+        } else {
+            tokens.push(token);
+        }
+    }
+
+    if !tokens.is_empty() {
+        let start = tokens[0].offset;
+        let end = tokens[tokens.len() - 1].end();
+        result.push(Statement {
+            offset: start,
+            text: &script[start..end],
+            tokens,
+        });
+    }
+    return result;
+}
+
+pub fn unquote_ident(text: &str) -> String {
+    if text.len() >= 2 && text.starts_with('"') && text.ends_with('"') {
+        return text[1..text.len() - 1].replace("\"\"", "\"");
+    }
+    return text.to_lowercase();
+}
+
+/* parses dot separated identifier starting at tokens[i], returns name parts and next token index */
+pub fn parse_qualified_name(tokens: &[Token], i: usize) -> Option<(Vec<String>, usize)> {
+    let mut parts = vec![];
+    let mut i = i;
+    loop {
+        let token = tokens.get(i)?;
+        if !token.is_identifier() {
+            return None;
+        }
+        parts.push(unquote_ident(token.text));
+        i += 1;
+        match tokens.get(i) {
+            Some(t) if t.is_symbol(".") => i += 1,
+            _ => return Some((parts, i)),
+        }
+    }
+}
+
+/* returns index of the closing parenthesis matching the one at tokens[i] */
+pub fn find_closing_paren(tokens: &[Token], i: usize) -> Option<usize> {
+    let mut depth = 0;
+    for (j, token) in tokens.iter().enumerate().skip(i) {
+        if token.is_symbol("(") {
+            depth += 1;
+        } else if token.is_symbol(")") {
+            depth -= 1;
+            if depth == 0 {
+                return Some(j);
+            }
+        }
+    }
+    return None;
+}
+
+/* splits tokens by top level commas */
+pub fn split_by_commas<'a, 't>(tokens: &'a [Token<'t>]) -> Vec<&'a [Token<'t>]> {
+    let mut result = vec![];
+    let mut depth = 0;
+    let mut start = 0;
+    for (i, token) in tokens.iter().enumerate() {
+        if token.is_symbol("(") || token.is_symbol("[") {
+            depth += 1;
+        } else if token.is_symbol(")") || token.is_symbol("]") {
+            depth -= 1;
+        } else if token.is_symbol(",") && depth == 0 {
+            result.push(&tokens[start..i]);
+            start = i + 1;
+        }
+    }
+    if start < tokens.len() {
+        result.push(&tokens[start..]);
+    }
+    return result;
+}
+
+/* joins tokens into normalized text: lowercase keywords, single spaces between words only */
+pub fn join_tokens(tokens: &[Token]) -> String {
+    let mut result = String::new();
+    let mut previous: Option<&Token> = None;
+    for token in tokens {
+        if let Some(p) = previous {
+            let glued = p.kind == TokenKind::Symbol || token.kind == TokenKind::Symbol;
+            if !glued {
+                result.push(' ');
+            }
+        }
+        if token.kind == TokenKind::Word {
+            result.push_str(&token.text.to_lowercase());
+        } else {
+            result.push_str(token.text);
+        }
+        previous = Some(token);
+    }
+    return result;
+}
+
+// first words of built-in types consisting of several words
+const MULTIWORD_TYPES: [&str; 8] = [
+    "double",
+    "character",
+    "char",
+    "bit",
+    "timestamp",
+    "time",
+    "interval",
+    "national",
+];
+
+fn normalize_argument(tokens: &[Token]) -> Option<String> {
+    let mut tokens = tokens;
+
+    let default_pos = tokens.iter().position(|t| t.is_word("default") || t.is_symbol("="));
+    if let Some(pos) = default_pos {
+        tokens = &tokens[..pos];
+    }
+
+    if let Some(first) = tokens.first() {
+        if first.is_word("out") {
+            return None;
+        }
+        if first.is_word("in") || first.is_word("inout") || first.is_word("variadic") {
+            tokens = &tokens[1..];
+        }
+    }
+
+    if tokens.len() >= 2 && tokens[0].is_identifier() && tokens[1].is_identifier() {
+        let first = tokens[0].text.to_lowercase();
+        if tokens[0].kind == TokenKind::QuotedIdent || !MULTIWORD_TYPES.contains(&first.as_str()) {
+            tokens = &tokens[1..];
+        }
+    }
+
+    return Some(join_tokens(tokens));
+}
+
+/* argument types of a function signature as accepted by to_regprocedure, e.g. "(a int, b text)" -> "int,text" */
+pub fn normalize_signature(tokens: &[Token]) -> String {
+    let mut args = vec![];
+    for arg_tokens in split_by_commas(tokens) {
+        if let Some(arg) = normalize_argument(arg_tokens) {
+            args.push(arg);
+        }
+    }
+    return args.join(",");
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CreateFunction {
+    pub schema: Option<String>,
+    pub name: String,
+    pub signature: String,
+    pub or_replace: bool,
+}
+
+/* parses "create [or replace] function <name>(<args>)" statement header */
+pub fn parse_create_function(statement: &Statement) -> Option<CreateFunction> {
+    let tokens = &statement.tokens;
+    if !tokens.first()?.is_word("create") {
+        return None;
+    }
+    let mut i = 1;
+    let mut or_replace = false;
+    if tokens.get(i)?.is_word("or") && tokens.get(i + 1)?.is_word("replace") {
+        or_replace = true;
+        i += 2;
+    }
+    if !tokens.get(i)?.is_word("function") {
+        return None;
+    }
+    let (mut parts, i) = parse_qualified_name(tokens, i + 1)?;
+    if !tokens.get(i)?.is_symbol("(") {
+        return None;
+    }
+    let close = find_closing_paren(tokens, i)?;
+    let signature = normalize_signature(&tokens[i + 1..close]);
+
+    let name = parts.pop()?;
+    let schema = parts.pop();
+    return Some(CreateFunction {
+        schema,
+        name,
+        signature,
+        or_replace,
+    });
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CreateView {
+    pub schema: Option<String>,
+    pub name: String,
+    pub or_replace: bool,
+    pub materialized: bool,
+}
+
+/* parses "create [or replace] [temp] [recursive|materialized] view <name>" statement header */
+pub fn parse_create_view(statement: &Statement) -> Option<CreateView> {
+    let tokens = &statement.tokens;
+    if !tokens.first()?.is_word("create") {
+        return None;
+    }
+    let mut i = 1;
+    let mut or_replace = false;
+    let mut materialized = false;
+    if tokens.get(i)?.is_word("or") && tokens.get(i + 1)?.is_word("replace") {
+        or_replace = true;
+        i += 2;
+    }
+    if tokens.get(i)?.is_word("temp") || tokens.get(i)?.is_word("temporary") {
+        i += 1;
+    }
+    if tokens.get(i)?.is_word("recursive") {
+        i += 1;
+    } else if tokens.get(i)?.is_word("materialized") {
+        materialized = true;
+        i += 1;
+    }
+    if !tokens.get(i)?.is_word("view") {
+        return None;
+    }
+    i += 1;
+    if tokens.get(i)?.is_word("if") {
+        i += 3;
+    }
+    let (mut parts, _) = parse_qualified_name(tokens, i)?;
+    let name = parts.pop()?;
+    let schema = parts.pop();
+    return Some(CreateView {
+        schema,
+        name,
+        or_replace,
+        materialized,
+    });
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CreatePublication {
+    pub name: String,
+    pub objects: Option<String>,
+    pub options: Option<String>,
+}
+
+/* parses "create publication <name> [for <objects>] [with (<options>)]" statement */
+pub fn parse_create_publication(statement: &Statement) -> Option<CreatePublication> {
+    let tokens = &statement.tokens;
+    if !statement.starts_with_words(&["create", "publication"]) {
+        return None;
+    }
+    let name_token = tokens.get(2)?;
+    if !name_token.is_identifier() {
+        return None;
+    }
+
+    let with_pos = tokens.iter().position(|t| t.is_word("with"));
+    let objects_end = with_pos.unwrap_or(tokens.len());
+
+    let mut objects = None;
+    if let Some(for_token) = tokens.get(3) {
+        if for_token.is_word("for") && objects_end > 4 {
+            let start = tokens[4].offset - statement.offset;
+            let end = tokens[objects_end - 1].end() - statement.offset;
+            objects = Some(statement.text[start..end].into());
+        }
+    }
+
+    let mut options = None;
+    if let Some(with_pos) = with_pos {
+        let open = with_pos + 1;
+        if tokens.get(open)?.is_symbol("(") {
+            let close = find_closing_paren(tokens, open)?;
+            if close > open + 1 {
+                let start = tokens[open + 1].offset - statement.offset;
+                let end = tokens[close - 1].end() - statement.offset;
+                options = Some(statement.text[start..end].into());
+            }
+        }
+    }
+
+    return Some(CreatePublication {
+        name: name_token.text.into(),
+        objects,
+        options,
+    });
+}
+
+// object kinds with comments stored in pg_description which can be compared before applying
+const COMMENT_KINDS: [&str; 11] = [
+    "materialized view",
+    "foreign table",
+    "table",
+    "view",
+    "index",
+    "sequence",
+    "column",
+    "function",
+    "procedure",
+    "schema",
+    "type",
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CommentOn {
+    pub kind: String,
+    pub target: String,
+    pub value: String,
+}
+
+/* value of string literal ('a''b', E'a\nb' or $$a$$), none for other expressions and unsupported escapes */
+pub fn unquote_literal(text: &str) -> Option<String> {
+    if text.len() >= 3 && (text.starts_with("E'") || text.starts_with("e'")) && text.ends_with('\'') {
+        let mut result = String::new();
+        let mut chars = text[2..text.len() - 1].chars();
+        while let Some(c) = chars.next() {
+            match c {
+                '\\' => match chars.next()? {
+                    'n' => result.push('\n'),
+                    'r' => result.push('\r'),
+                    't' => result.push('\t'),
+                    c @ '\\' | c @ '\'' => result.push(c),
+                    _ => return None,
+                },
+                '\'' => {
+                    if chars.next()? != '\'' {
+                        return None;
+                    }
+                    result.push('\'');
+                },
+                c => result.push(c),
+            }
+        }
+        return Some(result);
+    }
+    if text.len() >= 2 && text.starts_with('\'') && text.ends_with('\'') {
+        let inner = &text[1..text.len() - 1];
+        // adjacent literals ('a' 'b') are not supported
+        if inner.replace("''", "").contains('\'') {
+            return None;
+        }
+        return Some(inner.replace("''", "'"));
+    }
+    if text.starts_with('$') {
+        let tag_len = dollar_tag_len(text.as_bytes(), 0)?;
+        let tag = &text[..tag_len];
+        if text.len() >= 2 * tag_len && text.ends_with(tag) {
+            return Some(text[tag_len..text.len() - tag_len].into());
+        }
+    }
+    return None;
+}
+
+/* parses "comment on <kind> <target> is <value>" statement, only kinds listed in COMMENT_KINDS are recognized */
+pub fn parse_comment_on(statement: &Statement) -> Option<CommentOn> {
+    let tokens = &statement.tokens;
+    if !statement.starts_with_words(&["comment", "on"]) {
+        return None;
+    }
+
+    let mut kind = None;
+    for k in COMMENT_KINDS.iter() {
+        let words: Vec<&str> = k.split(' ').collect();
+        let matches = words.iter().enumerate()
+            .all(|(i, w)| tokens.get(2 + i).is_some_and(|t| t.is_word(w)));
+        if matches {
+            kind = Some((*k, words.len()));
+            break;
+        }
+    }
+    let (kind, kind_len) = kind?;
+
+    let target_start = 2 + kind_len;
+    let mut depth = 0;
+    let mut is_pos = None;
+    for (i, token) in tokens.iter().enumerate().skip(target_start) {
+        if token.is_symbol("(") {
+            depth += 1;
+        } else if token.is_symbol(")") {
+            depth -= 1;
+        } else if depth == 0 && token.is_word("is") {
+            is_pos = Some(i);
+            break;
+        }
+    }
+    let is_pos = is_pos?;
+    if is_pos == target_start || is_pos + 1 >= tokens.len() {
+        return None;
+    }
+
+    let target = &statement.text[tokens[target_start].offset - statement.offset..tokens[is_pos - 1].end() - statement.offset];
+    let value = &statement.text[tokens[is_pos + 1].offset - statement.offset..tokens[tokens.len() - 1].end() - statement.offset];
+    return Some(CommentOn {
+        kind: kind.into(),
+        target: target.into(),
+        value: value.into(),
+    });
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Grant {
+    pub privileges: Vec<String>,
+    pub kind: String,
+    pub targets: Vec<String>,
+    pub grantees: Vec<String>,
+}
+
+// object kinds with acl which can be compared before applying grants
+const GRANT_KINDS: [&str; 9] = [
+    "table",
+    "foreign table",
+    "sequence",
+    "schema",
+    "function",
+    "procedure",
+    "routine",
+    "type",
+    "domain",
+];
+
+// object kinds of grant statements which are executed without comparing acl
+const OTHER_GRANT_KINDS: [&str; 8] = [
+    "database",
+    "foreign data wrapper",
+    "foreign server",
+    "language",
+    "large object",
+    "parameter",
+    "tablespace",
+    "all",
+];
+
+pub fn find_word(tokens: &[Token], word: &str, start: usize) -> Option<usize> {
+    let mut depth = 0;
+    for (i, token) in tokens.iter().enumerate().skip(start) {
+        if token.is_symbol("(") {
+            depth += 1;
+        } else if token.is_symbol(")") {
+            depth -= 1;
+        } else if depth == 0 && token.is_word(word) {
+            return Some(i);
+        }
+    }
+    return None;
+}
+
+fn tokens_text<'t>(statement: &Statement<'t>, tokens: &[Token]) -> &'t str {
+    let start = tokens[0].offset - statement.offset;
+    let end = tokens[tokens.len() - 1].end() - statement.offset;
+    return &statement.text[start..end];
+}
+
+/* parses "grant <privileges> on [<kind>] <targets> to <grantees>" statement, 
+returns None for statements which can not be compared with acl (column privileges, role membership, grant option, etc.) */
+pub fn parse_grant(statement: &Statement) -> Option<Grant> {
+    let tokens = &statement.tokens;
+    if !tokens.first()?.is_word("grant") {
+        return None;
+    }
+    let on_pos = find_word(tokens, "on", 1)?;
+    let to_pos = find_word(tokens, "to", on_pos)?;
+    if tokens.iter().any(|t| t.is_word("with") || t.is_word("granted")) {
+        return None;
+    }
+
+    let mut privileges = vec![];
+    for privilege_tokens in split_by_commas(&tokens[1..on_pos]) {
+        let words: Vec<String> = privilege_tokens.iter()
+            .filter(|t| t.kind == TokenKind::Word)
+            .map(|t| t.text.to_lowercase())
+            .collect();
+        if words.len() != privilege_tokens.len() {
+            return None;
+        }
+        match words.as_slice() {
+            [privilege] => privileges.push(privilege.clone()),
+            [all, privileges_word] if all == "all" && privileges_word == "privileges" => privileges.push("all".into()),
+            _ => return None,
+        }
+    }
+
+    let mut i = on_pos + 1;
+    let mut kind = String::from("table");
+    // kind words are followed by targets, otherwise they are a table name
+    let kind_words: Vec<String> = tokens[i..to_pos - 1].iter()
+        .take_while(|t| t.kind == TokenKind::Word)
+        .map(|t| t.text.to_lowercase())
+        .collect();
+    for count in (1..=kind_words.len()).rev() {
+        let words = kind_words[..count].join(" ");
+        if OTHER_GRANT_KINDS.contains(&words.as_str()) {
+            return None;
+        }
+        if GRANT_KINDS.contains(&words.as_str()) {
+            kind = words;
+            i += count;
+            break;
+        }
+    }
+    if i >= to_pos || to_pos + 1 >= tokens.len() {
+        return None;
+    }
+
+    let targets = split_by_commas(&tokens[i..to_pos]).iter()
+        .map(|t| tokens_text(statement, t).into())
+        .collect();
+
+    let mut grantees = vec![];
+    for grantee_tokens in split_by_commas(&tokens[to_pos + 1..]) {
+        let grantee_tokens = match grantee_tokens.first() {
+            Some(t) if t.is_word("group") => &grantee_tokens[1..],
+            _ => grantee_tokens,
+        };
+        if grantee_tokens.len() != 1 || !grantee_tokens[0].is_identifier() {
+            return None;
+        }
+        grantees.push(grantee_tokens[0].text.into());
+    }
+
+    return Some(Grant {
+        privileges,
+        kind,
+        targets,
+        grantees,
+    });
+}
+
+/* privileges of "all privileges" for given object kind */
+pub fn get_all_privileges(kind: &str) -> Vec<&'static str> {
+    match kind {
+        "table" |
+        "foreign table" => vec!["select", "insert", "update", "delete", "truncate", "references", "trigger"],
+        "sequence" => vec!["usage", "select", "update"],
+        "schema" => vec!["usage", "create"],
+        "function" | "procedure" | "routine" => vec!["execute"],
+        "type" | "domain" => vec!["usage"],
+        _ => vec![],
+    }
+}
+
+/* rewrites function reference "name(a int, b text)" into "name(int,text)" as accepted by regprocedure */
+pub fn normalize_function_reference(text: &str) -> String {
+    let tokens = tokenize(text);
+    let open = match tokens.iter().position(|t| t.is_symbol("(")) {
+        Some(open) => open,
+        None => return text.trim().into(),
+    };
+    let close = find_closing_paren(&tokens, open).unwrap_or(tokens.len());
+    let signature = normalize_signature(&tokens[open + 1..close]);
+    return format!("{}({})", text[..tokens[open].offset].trim(), signature);
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CreateRole {
+    pub name: String,
+    // (attribute key, sql) pairs e.g. ("login", "nologin"), ("connection limit", "connection limit 10")
+    pub attributes: Vec<(String, String)>,
+    pub in_roles: Vec<String>,
+    pub members: Vec<String>,
+    pub admins: Vec<String>,
+}
+
+// attributes which can be toggled with "no" prefix and their defaults
+const ROLE_FLAGS: [(&str, &str); 7] = [
+    ("superuser", "nosuperuser"),
+    ("createdb", "nocreatedb"),
+    ("createrole", "nocreaterole"),
+    ("inherit", "inherit"),
+    ("login", "nologin"),
+    ("replication", "noreplication"),
+    ("bypassrls", "nobypassrls"),
+];
+
+fn get_role_attribute_default(key: &str) -> Option<String> {
+    if let Some((_, default)) = ROLE_FLAGS.iter().find(|(flag, _)| *flag == key) {
+        return Some(String::from(*default));
+    }
+    match key {
+        "connection limit" => Some("connection limit -1".into()),
+        "password" => Some("password null".into()),
+        "valid until" => Some("valid until 'infinity'".into()),
+        _ => None,
+    }
+}
+
+fn parse_role_list(tokens: &[Token], start: usize) -> Option<(Vec<String>, usize)> {
+    let mut result = vec![];
+    let mut i = start;
+    loop {
+        let token = tokens.get(i)?;
+        if !token.is_identifier() {
+            return None;
+        }
+        result.push(token.text.into());
+        i += 1;
+        match tokens.get(i) {
+            Some(t) if t.is_symbol(",") => i += 1,
+            _ => return Some((result, i)),
+        }
+    }
+}
+
+/* parses "create role|user|group <name> [with] <options>" statement */
+pub fn parse_create_role(statement: &Statement) -> Option<CreateRole> {
+    let tokens = &statement.tokens;
+    let is_user = statement.starts_with_words(&["create", "user"]);
+    if !is_user
+        && !statement.starts_with_words(&["create", "role"])
+        && !statement.starts_with_words(&["create", "group"]) {
+        return None;
+    }
+    let name_token = tokens.get(2)?;
+    if !name_token.is_identifier() {
+        return None;
+    }
+
+    let mut result = CreateRole {
+        name: name_token.text.into(),
+        attributes: vec![],
+        in_roles: vec![],
+        members: vec![],
+        admins: vec![],
+    };
+
+    let mut i = 3;
+    if tokens.get(i).map(|t| t.is_word("with")) == Some(true) {
+        i += 1;
+    }
+    while i < tokens.len() {
+        let token = &tokens[i];
+        if token.is_symbol(";") {
+            break;
+        }
+        if token.kind != TokenKind::Word {
+            return None;
+        }
+        let word = token.text.to_lowercase();
+        let flag = ROLE_FLAGS.iter()
+            .find(|(flag, _)| word == *flag || word == format!("no{}", flag));
+        if let Some((flag, _)) = flag {
+            result.attributes.push((String::from(*flag), word));
+            i += 1;
+            continue;
+        }
+
+        match word.as_str() {
+            "connection" | "valid" => {
+                let key = if word == "connection" { "connection limit" } else { "valid until" };
+                let value = tokens.get(i + 2)?;
+                if !tokens.get(i + 1)?.is_word(key.split(' ').nth(1)?) {
+                    return None;
+                }
+                let mut end = i + 3;
+                if value.is_symbol("-") {
+                    end += 1;
+                }
+                result.attributes.push((key.into(), tokens_text(statement, tokens.get(i..end)?).into()));
+                i = end;
+            },
+            "encrypted" | "unencrypted" | "password" => {
+                let password_pos = if word == "password" { i } else { i + 1 };
+                if !tokens.get(password_pos)?.is_word("password") {
+                    return None;
+                }
+                let end = password_pos + 2;
+                result.attributes.push(("password".into(), tokens_text(statement, tokens.get(password_pos..end)?).into()));
+                i = end;
+            },
+            "in" => {
+                let next = tokens.get(i + 1)?;
+                if !next.is_word("role") && !next.is_word("group") {
+                    return None;
+                }
+                let (roles, end) = parse_role_list(tokens, i + 2)?;
+                result.in_roles.extend(roles);
+                i = end;
+            },
+            "role" | "user" => {
+                let (roles, end) = parse_role_list(tokens, i + 1)?;
+                result.members.extend(roles);
+                i = end;
+            },
+            "admin" => {
+                let (roles, end) = parse_role_list(tokens, i + 1)?;
+                result.admins.extend(roles);
+                i = end;
+            },
+            "sysid" => {
+                i += 2;
+            },
+            _ => return None,
+        }
+    }
+
+    // "create user" is "create role" with login by default
+    if is_user && !result.attributes.iter().any(|(key, _)| key == "login") {
+        result.attributes.push(("login".into(), "login".into()));
+    }
+    return Some(result);
+}
+
+fn contains_role(roles: &[String], role: &str) -> bool {
+    return roles.iter().any(|r| unquote_ident(r) == unquote_ident(role));
+}
+
+/* statements which change existing role created by old statement into role created by new statement,
+attributes removed from the new statement are reset to defaults */
+pub fn get_alter_role_statements(old: &CreateRole, new: &CreateRole) -> Vec<String> {
+    let mut result = vec![];
+    let name = &new.name;
+
+    let mut attributes: Vec<String> = new.attributes.iter().map(|(_, sql)| sql.clone()).collect();
+    for (key, _) in old.attributes.iter() {
+        if !new.attributes.iter().any(|(k, _)| k == key) {
+            if let Some(default) = get_role_attribute_default(key) {
+                attributes.push(default);
+            }
+        }
+    }
+    if attributes.len() > 0 {
+        result.push(format!("alter role {} with {};", name, attributes.join(" ")));
+    }
+
+    for role in new.in_roles.iter().filter(|r| !contains_role(&old.in_roles, r)) {
+        result.push(format!("grant {} to {};", role, name));
+    }
+    for role in old.in_roles.iter().filter(|r| !contains_role(&new.in_roles, r)) {
+        result.push(format!("revoke {} from {};", role, name));
+    }
+    for member in new.members.iter().filter(|r| !contains_role(&old.members, r) && !contains_role(&old.admins, r)) {
+        result.push(format!("grant {} to {};", name, member));
+    }
+    for member in old.members.iter().filter(|r| !contains_role(&new.members, r) && !contains_role(&new.admins, r)) {
+        result.push(format!("revoke {} from {};", name, member));
+    }
+    for admin in new.admins.iter().filter(|r| !contains_role(&old.admins, r)) {
+        result.push(format!("grant {} to {} with admin option;", name, admin));
+    }
+    for admin in old.admins.iter().filter(|r| !contains_role(&new.admins, r)) {
+        if contains_role(&new.members, admin) {
+            result.push(format!("revoke admin option for {} from {};", name, admin));
+        } else {
+            result.push(format!("revoke {} from {};", name, admin));
+        }
+    }
+    return result;
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CreateObject {
+    // object kind as written, e.g. "table", "event trigger", "user mapping", domains are reported as "type"
+    pub kind: String,
+    // name parts as written, [<server>, <role>] for user mappings, empty for unnamed indexes
+    pub name: Vec<String>,
+    // table of triggers, policies, rules, indexes and constraints
+    pub table: Option<Vec<String>>,
+}
+
+const CREATE_MODIFIERS: [&str; 11] = [
+    "temp",
+    "temporary",
+    "unlogged",
+    "global",
+    "local",
+    "materialized",
+    "recursive",
+    "unique",
+    "constraint",
+    "trusted",
+    "procedural",
+];
+
+/* parses table name following "on" / "to" word, skipping "only" */
+fn parse_object_table(tokens: &[Token], word: &str, start: usize) -> Option<Vec<String>> {
+    let mut i = find_word(tokens, word, start)? + 1;
+    if tokens.get(i)?.is_word("only") {
+        i += 1;
+    }
+    let (parts, _) = parse_qualified_name(tokens, i)?;
+    return Some(parts);
+}
+
+/* parses "alter table [if exists] [only] <table>" statement header, returns table name parts and index of the first action token */
+pub fn parse_alter_table(statement: &Statement) -> Option<(Vec<String>, usize)> {
+    let tokens = &statement.tokens;
+    if !statement.starts_with_words(&["alter", "table"]) {
+        return None;
+    }
+    let mut i = 2;
+    if tokens.get(i)?.is_word("if") {
+        i += 2;
+    }
+    if tokens.get(i)?.is_word("only") {
+        i += 1;
+    }
+    return parse_qualified_name(tokens, i);
+}
+
+/* parses "alter table [if exists] [only] <table> add constraint <name>" statement */
+fn parse_add_constraint(statement: &Statement) -> Option<CreateObject> {
+    let tokens = &statement.tokens;
+    let (table, next) = parse_alter_table(statement)?;
+    if !tokens.get(next)?.is_word("add") || !tokens.get(next + 1)?.is_word("constraint") {
+        return None;
+    }
+    let (name, _) = parse_qualified_name(tokens, next + 2)?;
+    return Some(CreateObject {
+        kind: "constraint".into(),
+        name,
+        table: Some(table),
+    });
+}
+
+/* parses header of statement creating an object: "create [or replace] [<modifiers>] <kind> [if not exists] <name>" 
+or "alter table <table> add constraint <name>" */
+pub fn parse_create_object(statement: &Statement) -> Option<CreateObject> {
+    let tokens = &statement.tokens;
+    if statement.starts_with_words(&["alter", "table"]) {
+        return parse_add_constraint(statement);
+    }
+    if !tokens.first()?.is_word("create") {
+        return None;
+    }
+    let mut i = 1;
+    if tokens.get(i)?.is_word("or") && tokens.get(i + 1)?.is_word("replace") {
+        i += 2;
+    }
+    while CREATE_MODIFIERS.iter().any(|m| tokens.get(i).map(|t| t.is_word(m)) == Some(true)) {
+        i += 1;
+    }
+
+    let first = tokens.get(i)?;
+    let second = tokens.get(i + 1);
+    let second_is = |word: &str| second.map(|t| t.is_word(word)) == Some(true);
+    let kind = if first.is_word("event") && second_is("trigger") {
+        i += 2;
+        "event trigger"
+    } else if first.is_word("foreign") && second_is("table") {
+        i += 2;
+        "foreign table"
+    } else if first.is_word("user") && second_is("mapping") {
+        i += 2;
+        "user mapping"
+    } else if first.is_word("user") || first.is_word("group") {
+        i += 1;
+        "role"
+    } else if first.is_word("domain") {
+        i += 1;
+        "type"
+    } else if first.kind == TokenKind::Word {
+        i += 1;
+        first.text
+    } else {
+        return None;
+    };
+    let kind = kind.to_lowercase();
+
+    if kind == "index" && tokens.get(i)?.is_word("concurrently") {
+        i += 1;
+    }
+    if tokens.get(i).map(|t| t.is_word("if")) == Some(true) {
+        i += 3;
+    }
+
+    if kind == "user mapping" {
+        // create user mapping for <role> server <server>
+        let (role, next) = parse_qualified_name(tokens, i + 1)?;
+        let (server, _) = parse_qualified_name(tokens, next + 1)?;
+        return Some(CreateObject {
+            kind,
+            name: vec![server.last()?.clone(), role.last()?.clone()],
+            table: None,
+        });
+    }
+    if kind == "schema" && tokens.get(i)?.is_word("authorization") {
+        i += 1;
+    }
+
+    let name = match kind.as_str() {
+        "index" if tokens.get(i)?.is_word("on") => vec![],
+        _ => parse_qualified_name(tokens, i)?.0,
+    };
+    let table = match kind.as_str() {
+        "trigger" | "policy" | "index" => parse_object_table(tokens, "on", i),
+        "rule" => parse_object_table(tokens, "to", i),
+        _ => None,
+    };
+    return Some(CreateObject {
+        kind,
+        name,
+        table,
+    });
+}
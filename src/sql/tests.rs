@@ -0,0 +1,334 @@
+use anyhow;
+use super::*;
+
+
+fn statement_texts(script: &str) -> Vec<&str> {
+    return split_statements(script).iter().map(|s| s.text).collect();
+}
+
+
+#[test]
+fn test_split_statements() -> anyhow::Result<()> {
+    assert_eq!(statement_texts("select 1; select 2"), vec!["select 1;", "select 2"]);
+    assert_eq!(statement_texts("  -- comment;\nselect ';'; /* ; */"), vec!["select ';';"]);
+    assert_eq!(statement_texts("select 'it''s; here';"), vec!["select 'it''s; here';"]);
+    assert_eq!(statement_texts("select E'\\'; x';"), vec!["select E'\\'; x';"]);
+    assert_eq!(statement_texts("select \"a;b\";"), vec!["select \"a;b\";"]);
+    assert_eq!(statement_texts("select /* a /* ; */ b */ 1;"), vec!["select /* a /* ; */ b */ 1;"]);
+    assert_eq!(
+        statement_texts("create function f() returns int as $body$ select 1; $body$ language sql; select $1;"),
+        vec!["create function f() returns int as $body$ select 1; $body$ language sql;", "select $1;"]
+    );
+    assert_eq!(statement_texts("do $$ begin perform 1; end $$;"), vec!["do $$ begin perform 1; end $$;"]);
+    assert_eq!(statement_texts(""), Vec::<&str>::new());
+    assert_eq!(statement_texts(";;"), Vec::<&str>::new());
+    return Ok(());
+}
+
+#[test]
+fn test_split_statements_offset() -> anyhow::Result<()> {
+    let script = "select 1;\n\n-- c\nselect 2;";
+    let statements = split_statements(script);
+    assert_eq!(statements.len(), 2);
+    assert_eq!(statements[1].offset, 16);
+    assert_eq!(&script[statements[1].offset..], "select 2;");
+    return Ok(());
+}
+
+#[test]
+fn test_parse_create_function() -> anyhow::Result<()> {
+    let script = "
+        create or replace function public.add(a int, b int default 1) returns int as $$ select a + b $$ language sql;
+        create function add(out r int, variadic xs int[]) as $$ select 1 $$ language sql;
+        create function \"Add\"(double precision, a character varying(10), inout t timestamp with time zone) returns int as $$ select 1 $$ language sql;
+        create function s.f() returns int as $$ select 1 $$ language sql;
+        create view add as select 1;
+    ";
+    let functions: Vec<Option<CreateFunction>> = split_statements(script).iter()
+        .map(|s| parse_create_function(s))
+        .collect();
+
+    assert_eq!(functions[0], Some(CreateFunction {
+        schema: Some("public".into()),
+        name: "add".into(),
+        signature: "int,int".into(),
+        or_replace: true,
+    }));
+    assert_eq!(functions[1], Some(CreateFunction {
+        schema: None,
+        name: "add".into(),
+        signature: "int[]".into(),
+        or_replace: false,
+    }));
+    assert_eq!(functions[2], Some(CreateFunction {
+        schema: None,
+        name: "Add".into(),
+        signature: "double precision,character varying(10),timestamp with time zone".into(),
+        or_replace: false,
+    }));
+    assert_eq!(functions[3], Some(CreateFunction {
+        schema: Some("s".into()),
+        name: "f".into(),
+        signature: "".into(),
+        or_replace: false,
+    }));
+    assert_eq!(functions[4], None);
+    return Ok(());
+}
+
+#[test]
+fn test_parse_create_view() -> anyhow::Result<()> {
+    let script = "
+        create or replace view public.v0 as select 1;
+        create view v1 as select 1;
+        create materialized view if not exists s.v2 as select 1;
+        create or replace recursive view v3 (n) as select 1;
+        create table v4 (id int);
+    ";
+    let views: Vec<Option<CreateView>> = split_statements(script).iter()
+        .map(|s| parse_create_view(s))
+        .collect();
+
+    assert_eq!(views[0], Some(CreateView { schema: Some("public".into()), name: "v0".into(), or_replace: true, materialized: false }));
+    assert_eq!(views[1], Some(CreateView { schema: None, name: "v1".into(), or_replace: false, materialized: false }));
+    assert_eq!(views[2], Some(CreateView { schema: Some("s".into()), name: "v2".into(), or_replace: false, materialized: true }));
+    assert_eq!(views[3], Some(CreateView { schema: None, name: "v3".into(), or_replace: true, materialized: false }));
+    assert_eq!(views[4], None);
+    return Ok(());
+}
+
+#[test]
+fn test_parse_create_publication() -> anyhow::Result<()> {
+    let script = "
+        create publication pub0 for table public.t0, t1 with (publish = 'insert, update');
+        create publication pub1 for all tables;
+        create publication pub2;
+    ";
+    let publications: Vec<Option<CreatePublication>> = split_statements(script).iter()
+        .map(|s| parse_create_publication(s))
+        .collect();
+
+    assert_eq!(publications[0], Some(CreatePublication {
+        name: "pub0".into(),
+        objects: Some("table public.t0, t1".into()),
+        options: Some("publish = 'insert, update'".into()),
+    }));
+    assert_eq!(publications[1], Some(CreatePublication {
+        name: "pub1".into(),
+        objects: Some("all tables".into()),
+        options: None,
+    }));
+    assert_eq!(publications[2], Some(CreatePublication {
+        name: "pub2".into(),
+        objects: None,
+        options: None,
+    }));
+    return Ok(());
+}
+
+#[test]
+fn test_unquote_literal() -> anyhow::Result<()> {
+    assert_eq!(unquote_literal("'table ''zero'''"), Some("table 'zero'".into()));
+    assert_eq!(unquote_literal("E'line\\n\\'q\\''"), Some("line\n'q'".into()));
+    assert_eq!(unquote_literal("$$it's$$"), Some("it's".into()));
+    assert_eq!(unquote_literal("$c$a$$b$c$"), Some("a$$b".into()));
+    assert_eq!(unquote_literal("'Mixed Case'"), Some("Mixed Case".into()));
+    assert_eq!(unquote_literal("''"), Some("".into()));
+    assert_eq!(unquote_literal("'a' 'b'"), None);
+    assert_eq!(unquote_literal("E'\\x41'"), None);
+    assert_eq!(unquote_literal("null"), None);
+    return Ok(());
+}
+
+#[test]
+fn test_parse_comment_on() -> anyhow::Result<()> {
+    let script = "
+        comment on table public.t0 is 'table ''zero''';
+        comment on column \"T1\".\"is\" is null;
+        comment on materialized view mv is E'line\\n';
+        comment on function f(a int, b text) is 'adds';
+        comment on role r is 'not compared';
+    ";
+    let comments: Vec<Option<CommentOn>> = split_statements(script).iter()
+        .map(|s| parse_comment_on(s))
+        .collect();
+
+    assert_eq!(comments[0], Some(CommentOn { kind: "table".into(), target: "public.t0".into(), value: "'table ''zero'''".into() }));
+    assert_eq!(comments[1], Some(CommentOn { kind: "column".into(), target: "\"T1\".\"is\"".into(), value: "null".into() }));
+    assert_eq!(comments[2], Some(CommentOn { kind: "materialized view".into(), target: "mv".into(), value: "E'line\\n'".into() }));
+    assert_eq!(comments[3], Some(CommentOn { kind: "function".into(), target: "f(a int, b text)".into(), value: "'adds'".into() }));
+    assert_eq!(comments[4], None);
+    return Ok(());
+}
+
+#[test]
+fn test_parse_grant() -> anyhow::Result<()> {
+    let script = "
+        grant select, insert on public.t0, t1 to r0, group \"R1\";
+        grant usage on schema app to public;
+        grant all privileges on function f(a int, b int), g() to r0;
+        grant select (id) on t0 to r0;
+        grant select on all tables in schema public to r0;
+        grant r0 to r1;
+        grant select on t0 to r0 with grant option;
+        grant select on foreign table app.ft to r0;
+        grant usage on type app.t, app.\"T\" to r0;
+        grant connect on database app to r0;
+        grant select on domain to r0;
+    ";
+    let grants: Vec<Option<Grant>> = split_statements(script).iter()
+        .map(|s| parse_grant(s))
+        .collect();
+
+    assert_eq!(grants[0], Some(Grant {
+        privileges: vec!["select".into(), "insert".into()],
+        kind: "table".into(),
+        targets: vec!["public.t0".into(), "t1".into()],
+        grantees: vec!["r0".into(), "\"R1\"".into()],
+    }));
+    assert_eq!(grants[1], Some(Grant {
+        privileges: vec!["usage".into()],
+        kind: "schema".into(),
+        targets: vec!["app".into()],
+        grantees: vec!["public".into()],
+    }));
+    assert_eq!(grants[2], Some(Grant {
+        privileges: vec!["all".into()],
+        kind: "function".into(),
+        targets: vec!["f(a int, b int)".into(), "g()".into()],
+        grantees: vec!["r0".into()],
+    }));
+    assert_eq!(grants[3], None);
+    assert_eq!(grants[4], None);
+    assert_eq!(grants[5], None);
+    assert_eq!(grants[6], None);
+    assert_eq!(grants[7], Some(Grant {
+        privileges: vec!["select".into()],
+        kind: "foreign table".into(),
+        targets: vec!["app.ft".into()],
+        grantees: vec!["r0".into()],
+    }));
+    assert_eq!(grants[8], Some(Grant {
+        privileges: vec!["usage".into()],
+        kind: "type".into(),
+        targets: vec!["app.t".into(), "app.\"T\"".into()],
+        grantees: vec!["r0".into()],
+    }));
+    assert_eq!(grants[9], None);
+    assert_eq!(grants[10], Some(Grant {
+        privileges: vec!["select".into()],
+        kind: "table".into(),
+        targets: vec!["domain".into()],
+        grantees: vec!["r0".into()],
+    }));
+    return Ok(());
+}
+
+#[test]
+fn test_normalize_function_reference() -> anyhow::Result<()> {
+    assert_eq!(normalize_function_reference("public.f(a int, b double precision)"), "public.f(int,double precision)");
+    assert_eq!(normalize_function_reference("g()"), "g()");
+    assert_eq!(normalize_function_reference(" g "), "g");
+    return Ok(());
+}
+
+#[test]
+fn test_parse_create_role() -> anyhow::Result<()> {
+    let script = "
+        create role r0 with login password 'secret' connection limit 5 in role r1, \"R2\" admin r3;
+        create user r4 nocreatedb valid until '2030-01-01' encrypted password 'x' connection limit -1;
+        create role r5 with unknown_option;
+        create table r6 (id int);
+    ";
+    let roles: Vec<Option<CreateRole>> = split_statements(script).iter()
+        .map(|s| parse_create_role(s))
+        .collect();
+
+    assert_eq!(roles[0], Some(CreateRole {
+        name: "r0".into(),
+        attributes: vec![
+            ("login".into(), "login".into()),
+            ("password".into(), "password 'secret'".into()),
+            ("connection limit".into(), "connection limit 5".into()),
+        ],
+        in_roles: vec!["r1".into(), "\"R2\"".into()],
+        members: vec![],
+        admins: vec!["r3".into()],
+    }));
+    assert_eq!(roles[1], Some(CreateRole {
+        name: "r4".into(),
+        attributes: vec![
+            ("createdb".into(), "nocreatedb".into()),
+            ("valid until".into(), "valid until '2030-01-01'".into()),
+            ("password".into(), "password 'x'".into()),
+            ("connection limit".into(), "connection limit -1".into()),
+            ("login".into(), "login".into()),
+        ],
+        in_roles: vec![],
+        members: vec![],
+        admins: vec![],
+    }));
+    assert_eq!(roles[2], None);
+    assert_eq!(roles[3], None);
+    return Ok(());
+}
+
+#[test]
+fn test_get_alter_role_statements() -> anyhow::Result<()> {
+    let old_script = "create role r0 with login createdb in role r1, r2 role r3 admin r4;";
+    let new_script = "create role r0 with password 'x' in role r2, r5 role r3, r4;";
+    let old = parse_create_role(&split_statements(old_script)[0]).unwrap();
+    let new = parse_create_role(&split_statements(new_script)[0]).unwrap();
+
+    assert_eq!(get_alter_role_statements(&old, &new), vec![
+        "alter role r0 with password 'x' nologin nocreatedb;",
+        "grant r5 to r0;",
+        "revoke r1 from r0;",
+        "revoke admin option for r0 from r4;",
+    ]);
+    assert_eq!(get_alter_role_statements(&new, &new).len(), 1);
+    return Ok(());
+}
+
+#[test]
+fn test_parse_create_object() -> anyhow::Result<()> {
+    let script = "
+        create unlogged table if not exists s.t0 (id int);
+        create or replace materialized view v0 as select 1;
+        create or replace function s.\"F\"(a int) returns int as $$ select a; $$ language sql;
+        create constraint trigger tr0 after insert on only s.t0 for each row execute function s.f();
+        create unique index concurrently on s.t0 (id);
+        create rule r0 as on insert to s.t0 do nothing;
+        create user mapping if not exists for current_user server srv0;
+        create event trigger et0 on ddl_command_start execute function f();
+        create domain d0 as int;
+        create schema authorization r0;
+        alter table only s.t0 add constraint c0 check (id > 0);
+        alter table s.t0 add column c1 int;
+        select 1;
+    ";
+    let objects: Vec<Option<CreateObject>> = split_statements(script).iter()
+        .map(parse_create_object)
+        .collect();
+    let object = |kind: &str, name: Vec<&str>, table: Option<Vec<&str>>| Some(CreateObject {
+        kind: kind.into(),
+        name: name.iter().map(|n| String::from(*n)).collect(),
+        table: table.map(|t| t.iter().map(|n| String::from(*n)).collect()),
+    });
+    assert_eq!(objects, vec![
+        object("table", vec!["s", "t0"], None),
+        object("view", vec!["v0"], None),
+        object("function", vec!["s", "F"], None),
+        object("trigger", vec!["tr0"], Some(vec!["s", "t0"])),
+        object("index", vec![], Some(vec!["s", "t0"])),
+        object("rule", vec!["r0"], Some(vec!["s", "t0"])),
+        object("user mapping", vec!["srv0", "current_user"], None),
+        object("event trigger", vec!["et0"], None),
+        object("type", vec!["d0"], None),
+        object("schema", vec!["r0"], None),
+        object("constraint", vec!["c0"], Some(vec!["s", "t0"])),
+        None,
+        None,
+    ]);
+    return Ok(());
+}
@@ -0,0 +1,239 @@
+use std::collections::HashMap;
+use anyhow;
+use toml;
+use crate::sql;
+use crate::utils;
+use crate::secrets;
+
+#[cfg(test)]
+mod tests;
+
+
+// placeholders with these namespaces are substituted right before execution, e.g. {env:NAME} or {secret:name}
+const NAMESPACES: &[&str] = &["env", "secret"];
+
+// variable name -> value
+pub type Variables = HashMap<String, String>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Filter {
+    Raw,
+    Ident,
+    Literal,
+}
+
+#[derive(Debug)]
+struct Placeholder<'t> {
+    text: &'t str,
+    namespace: Option<&'t str>,
+    name: &'t str,
+    filter: Filter,
+}
+
+enum Resolved {
+    Value(String),
+    Undefined,
+    Keep,
+}
+
+fn parse_filter(text: &str) -> Option<Filter> {
+    return match text {
+        "ident" => Some(Filter::Ident),
+        "literal" => Some(Filter::Literal),
+        _ => None,
+    };
+}
+
+/* "{name}", "{name:filter}", "{namespace:name}" or "{namespace:name:filter}" at the start of text */
+fn parse_placeholder(text: &str) -> Option<Placeholder<'_>> {
+    let end = text.find('}')?;
+    let inner = &text[1..end];
+    let parts: Vec<&str> = inner.split(':').collect();
+    let is_valid_part = |part: &&str| !part.is_empty() && part.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+    if !parts.iter().all(is_valid_part) {
+        return None;
+    }
+    let (namespace, name, filter) = match parts.as_slice() {
+        [name] => (None, *name, Filter::Raw),
+        [namespace, name] if NAMESPACES.contains(namespace) => (Some(*namespace), *name, Filter::Raw),
+        [name, filter] => (None, *name, parse_filter(filter)?),
+        [namespace, name, filter] if NAMESPACES.contains(namespace) => (Some(*namespace), *name, parse_filter(filter)?),
+        _ => return None,
+    };
+    return Some(Placeholder {
+        text: &text[..end + 1],
+        namespace,
+        name,
+        filter,
+    });
+}
+
+/* quoted identifier, e.g. my "db" -> "my ""db""" */
+pub fn quote_ident(value: &str) -> String {
+    return format!("\"{}\"", value.replace('"', "\"\""));
+}
+
+/* quoted string literal, backslashes are escaped using E'' syntax like quote_literal() of postgres */
+pub fn quote_literal(value: &str) -> String {
+    if value.contains('\\') {
+        return format!("E'{}'", value.replace('\\', "\\\\").replace('\'', "''"));
+    }
+    return format!("'{}'", value.replace('\'', "''"));
+}
+
+/* offset is inside sql code, not in string literal, dollar quoted body or comment */
+fn is_code(tokens: &[sql::Token], offset: usize) -> bool {
+    let i = tokens.partition_point(|t| t.end() <= offset);
+    return match tokens.get(i) {
+        Some(token) => token.offset <= offset && token.kind != sql::TokenKind::Literal,
+        None => false,
+    };
+}
+
+/* replaces placeholders resolved by resolve, undefined placeholders are errors in sql code and with namespace,
+in literals and comments they are kept as is (e.g. array literal '{a,b}') */
+fn substitute<F>(script: &str, resolve: F) -> anyhow::Result<String>
+where F: Fn(Option<&str>, &str) -> anyhow::Result<Resolved>
+{
+    let tokens = sql::tokenize(script);
+    let mut result = String::new();
+    let mut offset = 0;
+    while let Some(i) = script[offset..].find('{') {
+        let start = offset + i;
+        result.push_str(&script[offset..start]);
+        let placeholder = match parse_placeholder(&script[start..]) {
+            Some(placeholder) => placeholder,
+            None => {
+                result.push('{');
+                offset = start + 1;
+                continue;
+            }
+        };
+        offset = start + placeholder.text.len();
+
+        let value = match resolve(placeholder.namespace, placeholder.name)? {
+            Resolved::Value(value) => value,
+            Resolved::Keep => {
+                result.push_str(placeholder.text);
+                continue;
+            },
+            Resolved::Undefined => {
+                if placeholder.namespace.is_none() && !is_code(&tokens, start) {
+                    result.push_str(placeholder.text);
+                    continue;
+                }
+                let line = script[..start].matches('\n').count() + 1;
+                bail!("undefined variable {} at line {}", placeholder.text, line);
+            }
+        };
+        match placeholder.filter {
+            // external values could contain quotes, so they can not be spliced into sql as is
+            Filter::Raw if placeholder.namespace.is_some() => {
+                bail!("{} has to be quoted, use {{{ns}:{name}:literal}} or {{{ns}:{name}:ident}}",
+                    placeholder.text,
+                    ns = placeholder.namespace.unwrap_or(""),
+                    name = placeholder.name,
+                );
+            },
+            Filter::Raw => result.push_str(&value),
+            Filter::Ident => result.push_str(&quote_ident(&value)),
+            Filter::Literal => result.push_str(&quote_literal(&value)),
+        }
+    }
+    result.push_str(&script[offset..]);
+    return Ok(result);
+}
+
+/* substitutes project variables, namespaced placeholders ({env:NAME}) are kept until execution */
+pub fn render(script: &str, variables: &Variables) -> anyhow::Result<String> {
+    return substitute(script, |namespace, name| {
+        if namespace.is_some() {
+            return Ok(Resolved::Keep);
+        }
+        return Ok(match variables.get(name) {
+            Some(value) => Resolved::Value(value.clone()),
+            None => Resolved::Undefined,
+        });
+    });
+}
+
+/* substitutes {env:NAME} placeholders with environment variables and {secret:name} with values of secret providers */
+pub fn render_external(script: &str) -> anyhow::Result<String> {
+    return substitute(script, |namespace, name| {
+        match namespace {
+            Some("env") => {
+                return match utils::read_env_var(name) {
+                    Ok(value) => Ok(Resolved::Value(value)),
+                    Err(_) => Ok(Resolved::Undefined),
+                };
+            },
+            Some("secret") => {
+                return match secrets::get_secret(name)? {
+                    Some(value) => Ok(Resolved::Value(value)),
+                    None => Ok(Resolved::Undefined),
+                };
+            },
+            _ => return Ok(Resolved::Keep),
+        }
+    });
+}
+
+/* substitutes {env:NAME} and {secret:name} placeholders with empty values, so that the script can be parsed
+without reading external values (e.g. previous script of an object) */
+pub fn blank_external(script: &str) -> anyhow::Result<String> {
+    return substitute(script, |namespace, _| {
+        return Ok(match namespace {
+            Some(_) => Resolved::Value(String::new()),
+            None => Resolved::Keep,
+        });
+    });
+}
+
+/* script references env variables or secrets, e.g. {secret:name:literal} */
+pub fn has_external_placeholders(script: &str) -> bool {
+    return script.match_indices('{')
+        .filter_map(|(i, _)| parse_placeholder(&script[i..]))
+        .any(|placeholder| placeholder.namespace.is_some());
+}
+
+fn get_config_variables(value: &toml::Value, key: &str) -> anyhow::Result<Variables> {
+    let mut result = Variables::new();
+    let table = value.as_table()
+        .ok_or(anyhow!("load_variables error: {} has to be a table", key))?;
+    for (name, value) in table.iter() {
+        let value = match value {
+            toml::Value::String(s) => s.clone(),
+            toml::Value::Integer(i) => i.to_string(),
+            toml::Value::Float(f) => f.to_string(),
+            toml::Value::Boolean(b) => b.to_string(),
+            _ => bail!("load_variables error: variable {}.{} has to be a string, number or boolean", key, name),
+        };
+        result.insert(name.clone(), value);
+    }
+    return Ok(result);
+}
+
+/* [variables] of the project config overridden by [profiles.<profile>.variables], plus built-in wo_role_prefix */
+pub fn load_variables(
+    config: &toml::Value,
+    profile: Option<&str>,
+    role_prefix: &str
+) -> anyhow::Result<Variables> {
+    let mut result = Variables::new();
+    if let Some(variables) = config.get("variables") {
+        result.extend(get_config_variables(variables, "variables")?);
+    }
+    if let Some(profile) = profile {
+        let profile_config = config.get("profiles")
+            .and_then(|profiles| profiles.get(profile))
+            .ok_or(anyhow!("load_variables error: profile {:?} is not defined in project config", profile))?;
+        if let Some(variables) = profile_config.get("variables") {
+            result.extend(get_config_variables(variables, &format!("profiles.{}.variables", profile))?);
+        }
+    }
+    if result.contains_key("wo_role_prefix") {
+        bail!("load_variables error: wo_role_prefix is a built-in variable, set it using WO_ROLE_PREFIX");
+    }
+    result.insert("wo_role_prefix".into(), role_prefix.into());
+    return Ok(result);
+}
@@ -0,0 +1,85 @@
+use anyhow;
+use std::env;
+use super::*;
+
+
+fn variables(list: Vec<(&str, &str)>) -> Variables {
+    return list.into_iter().map(|(k, v)| (String::from(k), String::from(v))).collect();
+}
+
+#[test]
+fn test_render() -> anyhow::Result<()> {
+    let vars = variables(vec![("app", "prod_app"), ("quote", "it's \"x\"")]);
+    assert_eq!(render("grant select on t to {app};", &vars)?, "grant select on t to prod_app;");
+    assert_eq!(render("create role {app:ident};", &vars)?, "create role \"prod_app\";");
+    assert_eq!(render("select {quote:literal}, {quote:ident};", &vars)?, "select 'it''s \"x\"', \"it's \"\"x\"\"\";");
+    assert_eq!(render("select '{app}';", &vars)?, "select 'prod_app';");
+    assert_eq!(render("select '{env:HOME}';", &vars)?, "select '{env:HOME}';");
+    return Ok(());
+}
+
+#[test]
+fn test_render_undefined() -> anyhow::Result<()> {
+    let vars = variables(vec![]);
+    assert_eq!(render("select 1;\ncreate role {missing};", &vars).is_err(), true);
+    assert_eq!(format!("{}", render("select 1;\ncreate role {missing};", &vars).unwrap_err()), "undefined variable {missing} at line 2");
+    // literals, dollar quoted bodies and comments are kept as is
+    assert_eq!(render("select '{a,b}'::text[], $$ {x} $$; -- {param}", &vars)?, "select '{a,b}'::text[], $$ {x} $$; -- {param}");
+    assert_eq!(render("select '{\"a\": 1}'::jsonb, 'a{2}';", &vars)?, "select '{\"a\": 1}'::jsonb, 'a{2}';");
+    assert_eq!(render("select {unknown:upper};", &vars)?, "select {unknown:upper};");
+    return Ok(());
+}
+
+#[test]
+fn test_render_external() -> anyhow::Result<()> {
+    env::set_var("WO_TEST_RENDER_ENV", "o'k");
+    assert_eq!(render_external("select {env:WO_TEST_RENDER_ENV:literal}, '{app}';")?, "select 'o''k', '{app}';");
+    assert_eq!(render_external("select '{env:WO_TEST_RENDER_ENV_MISSING}';").is_err(), true);
+    assert_eq!(render_external("select '{env:WO_TEST_RENDER_ENV}';").is_err(), true);
+    assert_eq!(render_external("select '{secret:undeclared}';").is_err(), true);
+    return Ok(());
+}
+
+#[test]
+fn test_quote() -> anyhow::Result<()> {
+    assert_eq!(quote_ident("my \"db\""), "\"my \"\"db\"\"\"");
+    assert_eq!(quote_literal("a'b"), "'a''b'");
+    assert_eq!(quote_literal("a\\b'"), "E'a\\\\b'''");
+    return Ok(());
+}
+
+#[test]
+fn test_load_variables() -> anyhow::Result<()> {
+    let config = "
+        [variables]
+        schema = \"app\"
+        batch_size = 100
+
+        [profiles.prod.variables]
+        batch_size = 1000
+    ".parse::<toml::Value>()?;
+    let vars = load_variables(&config, None, "t_")?;
+    assert_eq!(vars["schema"], "app");
+    assert_eq!(vars["batch_size"], "100");
+    assert_eq!(vars["wo_role_prefix"], "t_");
+
+    let vars = load_variables(&config, Some("prod"), "")?;
+    assert_eq!(vars["schema"], "app");
+    assert_eq!(vars["batch_size"], "1000");
+
+    assert_eq!(load_variables(&config, Some("stage"), "").is_err(), true);
+    let config = "[variables]\nwo_role_prefix = \"x\"".parse::<toml::Value>()?;
+    assert_eq!(load_variables(&config, None, "").is_err(), true);
+    return Ok(());
+}
+
+#[test]
+fn test_has_external_placeholders() -> anyhow::Result<()> {
+    assert_eq!(has_external_placeholders("password {secret:role0_password:literal}"), true);
+    assert_eq!(has_external_placeholders("password {env:ROLE0_PASSWORD:literal}"), true);
+    assert_eq!(has_external_placeholders("password {password:literal}"), false);
+    assert_eq!(has_external_placeholders("password '{secret'"), false);
+    assert_eq!(blank_external("create role r0 password {secret:r0_password:literal} in role {r1};")?,
+        "create role r0 password '' in role {r1};");
+    return Ok(());
+}
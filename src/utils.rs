@@ -7,6 +7,8 @@ use std::env;
 use path::PathBuf;
 use anyhow;
 use anyhow::Context;
+use crate::template;
+use crate::connection;
 
 #[cfg(test)]
 mod tests;
@@ -44,7 +46,7 @@ pub fn read_env_var(key: &str) -> anyhow::Result<String> {
     return Ok(result);
 }
 
-fn validate_admin_script_param(p: &str) -> anyhow::Result<()> {
+pub fn validate_admin_script_param(p: &str) -> anyhow::Result<()> {
     if p.len() == 0 {
         bail!("admin script parameter is empty");
     }
@@ -59,51 +61,26 @@ fn validate_admin_script_param(p: &str) -> anyhow::Result<()> {
     return Ok(());
 }
 
+/* password of WO_CONNECTION_STRING (or PGPASSWORD, service, .pgpass), the value is masked in all output */
 pub fn get_password() -> anyhow::Result<Option<String>> {
-    let connection_string = read_env_var("WO_CONNECTION_STRING")?;
-    let pg_config = connection_string.parse::<postgres::Config>()?;
-    let password_result = pg_config.get_password();
-    match password_result {
-        Some(bytes) => {
-            let password_str = std::str::from_utf8(bytes)
-                .context("get_pasword failed convert utf-8")?;
-            validate_admin_script_param(&password_str)?;
-            return Ok(Some(password_str.into()));
-        }
-        None => {
-            return Ok(None);
-        }
-    }
+    let params = connection::get_params("WO_CONNECTION_STRING")?;
+    return Ok(params.get("password").cloned());
 }
 
+/* dbname of WO_CONNECTION_STRING (or PGDATABASE, service, user) */
 pub fn get_database_name() -> anyhow::Result<String> {
-    let connection_string = read_env_var("WO_CONNECTION_STRING")?;
-    let pg_config = connection_string.parse::<postgres::Config>()?;
-    let database_name_result = pg_config.get_dbname();
-    match database_name_result {
-        Some(database_name) => {
-            validate_admin_script_param(&database_name)?;
-            return Ok(database_name.into());
-        }
-        None => {
-            bail!("could not read the dbname parameter from connection string WO_CONNECTION_STRING");
-        }
-    }
+    let params = connection::get_params("WO_CONNECTION_STRING")?;
+    let database_name = params.get("dbname")
+        .ok_or(anyhow!("could not read the dbname parameter from connection string WO_CONNECTION_STRING"))?;
+    return Ok(database_name.clone());
 }
 
+/* user of WO_CONNECTION_STRING (or PGUSER, service, USER) */
 pub fn get_role_name() -> anyhow::Result<String> {
-    let connection_string = read_env_var("WO_CONNECTION_STRING")?;
-    let pg_config = connection_string.parse::<postgres::Config>()?;
-    let role_name_result = pg_config.get_user();
-    match role_name_result {
-        Some(role_name) => {
-            validate_admin_script_param(&role_name)?;
-            return Ok(role_name.into());
-        }
-        None => {
-            bail!("could not get user parameter from connection string WO_CONNECTION_STRING");
-        }
-    }
+    let params = connection::get_params("WO_CONNECTION_STRING")?;
+    let role_name = params.get("user")
+        .ok_or(anyhow!("could not get user parameter from connection string WO_CONNECTION_STRING"))?;
+    return Ok(role_name.clone());
 }
 
 pub fn contains_whole_word(text: &str, search_term: &str) -> bool {
@@ -143,13 +120,48 @@ pub fn contains_whole_word_ci(text: &str, search_term: &str) -> bool {
     return contains_whole_word(&text_lower, &search_term_lower);
 }
 
+/* substitutes {env:NAME} and {secret:name} placeholders, values are never stored in wo_objects */
+pub fn expand_env_vars(script: &str) -> anyhow::Result<String> {
+    return template::render_external(script);
+}
+
+/* utc timestamp "YYYYMMDDHHMMSS", used to keep generated migrations in alphabetical order */
+pub fn get_timestamp() -> anyhow::Result<String> {
+    let unix_secs = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .context("get_timestamp error: system time is before unix epoch")?
+        .as_secs();
+    return Ok(format_timestamp(unix_secs));
+}
+
+fn format_timestamp(unix_secs: u64) -> String {
+    let days = (unix_secs / 86400) as i64;
+    let secs_of_day = unix_secs % 86400;
+
+    // civil date from days since epoch (Howard Hinnant's algorithm)
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let day_of_era = z - era * 146097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    return format!(
+        "{:04}{:02}{:02}{:02}{:02}{:02}",
+        year, month, day,
+        secs_of_day / 3600, secs_of_day % 3600 / 60, secs_of_day % 60
+    );
+}
+
 pub fn validate_environment() -> anyhow::Result<()> {
     let err_msg = "all wo variables are mandatory to avoid mixed environments";
     read_env_var("WO_CONNECTION_STRING").context(err_msg)?;
     read_env_var("WO_ADMIN_CONNECTION_STRING").context(err_msg)?;
     read_env_var("WO_DIR").context(err_msg)?;
     read_env_var("WO_ROLE_PREFIX").context(err_msg)?;
-    read_env_var("WO_ROOT_CERT").context(err_msg)?;
     return Ok(());
 }
 
@@ -47,4 +47,24 @@ fn test_contains_whole_word() -> anyhow::Result<()> {
     assert_eq!(contains_whole_word(".", ""), true);
     assert_eq!(contains_whole_word("", "."), false);
     return Ok(());
-}
\ No newline at end of file
+}
+
+#[test]
+fn test_expand_env_vars() -> anyhow::Result<()> {
+    env::set_var("WO_TEST_EXPAND_ENV_VARS", "secret");
+    assert_eq!(expand_env_vars("password {env:WO_TEST_EXPAND_ENV_VARS:literal}")?, "password 'secret'");
+    assert_eq!(expand_env_vars("{env:WO_TEST_EXPAND_ENV_VARS:ident}.{env:WO_TEST_EXPAND_ENV_VARS:ident}")?, "\"secret\".\"secret\"");
+    // unquoted values could break the script
+    assert_eq!(expand_env_vars("password '{env:WO_TEST_EXPAND_ENV_VARS}'").is_err(), true);
+    assert_eq!(expand_env_vars("select '{env:}', '{env:a b}', '{env:'")?, "select '{env:}', '{env:a b}', '{env:'");
+    assert_eq!(expand_env_vars("{env:WO_TEST_EXPAND_ENV_VARS_MISSING}").is_err(), true);
+    return Ok(());
+}
+
+#[test]
+fn test_format_timestamp() -> anyhow::Result<()> {
+    assert_eq!(format_timestamp(0), "19700101000000");
+    assert_eq!(format_timestamp(951782400), "20000229000000");
+    assert_eq!(format_timestamp(1792327505), "20261018124505");
+    return Ok(());
+}